@@ -102,7 +102,7 @@ impl<'a> RouteBlobRef<'a> {
     }
 
     /// Check if this blob contains error recursively
-    pub fn checked(&self) -> Result<SafeRouteBlob<'_>, RouteBlobError> {
+    pub fn checked(&self) -> Result<SafeRouteBlob<'a>, RouteBlobError> {
         match self {
             Self::Value(v) => Ok(v.ref_into_unchecked()),
             Self::Blob(b) => b.checked(),
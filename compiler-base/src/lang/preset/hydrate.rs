@@ -1,7 +1,5 @@
 use std::collections::BTreeMap;
 
-use serde_json::Value;
-
 use crate::json::{IntoSafeRouteBlob, SafeRouteBlob};
 
 use super::{Preset, PresetBlob};
@@ -31,10 +29,7 @@ impl PresetBlob {
     pub fn hydrate(&self, args: &[String]) -> SafeRouteBlob<'_> {
         match self {
             PresetBlob::NonTemplate(value) => value.ref_into_unchecked(),
-            PresetBlob::Template(tempstr) => {
-                let str = tempstr.hydrate(args);
-                Value::String(str).into_unchecked()
-            }
+            PresetBlob::Template(tempstr) => tempstr.hydrate_json(args).into_unchecked(),
             PresetBlob::Array(arr) => {
                 let mut out = vec![];
                 for x in arr {
@@ -58,7 +53,7 @@ impl PresetBlob {
 #[cfg(test)]
 mod test {
     use map_macro::btree_map;
-    use serde_json::json;
+    use serde_json::{json, Value};
 
     use super::*;
 
@@ -132,4 +127,35 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_filters() {
+        let preset = Preset::compile(json!({
+            "text": "$(0|upper)",
+            "icon": "$(0|kebab)",
+            "comment": "$(1|default:none)",
+            "movements": [{
+                "to": ["$(2|number)", "$(3|number)"]
+            }],
+            "tags": {
+                "$(0|lower)": true
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            preset.test_hydrate(&["Master Sword", "", "100", "-2.5"]),
+            btree_map! {
+                "text".to_string() => json!("MASTER SWORD"),
+                "icon".to_string() => json!("master-sword"),
+                "comment".to_string() => json!("none"),
+                "movements".to_string() => json!([{
+                    "to": [100, -2.5]
+                }]),
+                "tags".to_string() => json!({
+                    "master sword": true
+                }),
+            }
+        );
+    }
 }
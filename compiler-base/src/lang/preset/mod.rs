@@ -9,6 +9,7 @@ mod grammar;
 mod hydrate;
 pub use hydrate::*;
 mod optimize;
+pub use optimize::*;
 mod parse;
 
 /// A preset is an arbitrary json object blob that can contain template strings
@@ -28,7 +29,7 @@ pub enum PresetBlob {
 }
 
 /// Instantiation of a preset with the name and args
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct PresetInst {
     /// Name of the preset, such as _Foo::Bar
     pub name: String,
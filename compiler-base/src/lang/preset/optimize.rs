@@ -1,6 +1,27 @@
 use std::collections::BTreeMap;
 
-use super::Preset;
+use serde_json::Value;
+
+use crate::env::yield_budget;
+use crate::json::{Cast, IntoSafeRouteBlob, SafeRouteBlob};
+use crate::lang::TempStr;
+use crate::macros::async_recursion;
+use crate::prop;
+
+use super::{Preset, PresetBlob, PresetInst};
+
+/// Optimize all presets
+///
+/// Presets that refer to each other are optimized in dependency order, and each
+/// preset is only optimized once
+pub async fn optimize_presets(mut presets: BTreeMap<String, Preset>) -> BTreeMap<String, Preset> {
+    let mut optimized_presets = BTreeMap::new();
+    while let Some((name, mut preset)) = presets.pop_first() {
+        preset.optimize(&mut presets, &mut optimized_presets).await;
+        optimized_presets.insert(name, preset);
+    }
+    optimized_presets
+}
 
 impl Preset {
     /// Optimize the preset
@@ -11,26 +32,561 @@ impl Preset {
     /// This will also attempt to optimize any preset referred to by this preset. However, this
     /// will not give error if the referred preset is not found or there is a cycle. It will leave it unoptimized and
     /// defer to the compiler to give error, if this preset is ever instantiated.
+    ///
+    /// References to other presets with only literal arguments are flattened into this preset:
+    /// - `extends` is flattened by deep merging the properties on top of the extended preset
+    ///   and the flattened `presets`. If some of `presets` can't be flattened, `extends` isn't either,
+    ///   since the extended preset needs to be applied before them
+    /// - The longest suffix of `presets` is flattened by merging the properties on top of each other.
+    ///   The rest of `presets` are still applied by the compiler, since properties from
+    ///   them are overridden by the flattened ones
+    ///
+    /// Since flattened references are not applied by the compiler, they don't count towards
+    /// the max depth of preset references
+    #[async_recursion(auto)]
     pub async fn optimize(
         &mut self,
-        _presets: &mut BTreeMap<String, Preset>,
-        _optimized_presets: &mut BTreeMap<String, Preset>,
+        presets: &mut BTreeMap<String, Preset>,
+        optimized_presets: &mut BTreeMap<String, Preset>,
     ) {
-        // TODO #114: optimize presets
-
-        // Generally should work like this:
+        yield_budget(64).await;
         // if self top level has any tempstr key, it's not optimizable
-        // because the key could be "presets"
-        //
-        // Otherwise, if there is a static key "presets", we can optimize
-        //
-        // if the preset is a static preset (i.e. TempStr::is_literal)
-        // parse it as PresetInst and hydrate it, store it temporarily
-        // do this until the first non-static preset is found
-        //
-        // Lastly, apply self to the hydrated presets
+        // because the key could be "presets" or "extends"
+        let mut extends = None;
+        let mut preset_refs = None;
+        let mut properties = PropMap::default();
+        for (key, value) in &self.0 {
+            let key = match key.as_literal() {
+                Some(key) => key,
+                None => return,
+            };
+            match key.as_str() {
+                prop::EXTENDS => extends = Some(value),
+                prop::PRESETS => preset_refs = Some(value),
+                _ => properties.insert(key, value.clone()),
+            }
+        }
+
+        // the extended preset is applied first, then the presets, then the properties
+        let base = match extends {
+            Some(extends) => match resolve_static(extends, presets, optimized_presets).await {
+                Some(base) => Some(base),
+                None => return,
+            },
+            None => None,
+        };
+
+        let mut remaining_refs = None;
+        let mut flattened = vec![];
+        if let Some(preset_refs) = preset_refs {
+            let (mut refs, is_array) = match preset_refs.as_list() {
+                Some(refs) => (refs, true),
+                None => (vec![preset_refs.clone()], false),
+            };
+            while let Some(preset_ref) = refs.pop() {
+                match resolve_static(&preset_ref, presets, optimized_presets).await {
+                    Some(preset_properties) => flattened.push(preset_properties),
+                    None => {
+                        refs.push(preset_ref);
+                        break;
+                    }
+                }
+            }
+            if !refs.is_empty() {
+                // the extended preset has to be applied before the remaining presets
+                if base.is_some() {
+                    return;
+                }
+                remaining_refs = Some(if is_array {
+                    PresetBlob::Array(refs).compact()
+                } else {
+                    preset_refs.clone()
+                });
+            }
+        }
+
+        // later presets override earlier ones, and self overrides all of them
+        let has_base = base.is_some();
+        let mut merged = base.unwrap_or_default();
+        for preset_properties in flattened.into_iter().rev() {
+            merged.extend(preset_properties);
+        }
+        if has_base {
+            // the properties are deep merged on top of the extended preset
+            if !merged.deep_merge(properties) {
+                return;
+            }
+        } else {
+            merged.extend(properties);
+        }
+        let properties = merged;
+
+        let mut output = Vec::with_capacity(properties.0.len() + 1);
+        if let Some(remaining_refs) = remaining_refs {
+            output.push((TempStr::literal(prop::PRESETS), remaining_refs));
+        }
+        for (key, value) in properties.0 {
+            output.push((TempStr::literal(key), value));
+        }
+        self.0 = output;
+    }
+
+    /// Hydrate the preset into static properties
+    ///
+    /// Returns None if the hydrated preset still refers to other presets,
+    /// which means it cannot be flattened
+    fn hydrate_static(&self, args: &[String]) -> Option<PropMap> {
+        let mut properties = PropMap::default();
+        for (key, value) in &self.0 {
+            let key = key.hydrate(args);
+            if key == prop::PRESETS || key == prop::EXTENDS {
+                return None;
+            }
+            properties.insert(key, value.hydrate_static(args));
+        }
+        Some(properties)
+    }
+}
+
+impl PresetBlob {
+    /// Hydrate the blob into a blob without template strings
+    fn hydrate_static(&self, args: &[String]) -> Self {
+        match self {
+            Self::NonTemplate(value) => Self::NonTemplate(value.clone()),
+            Self::Template(tempstr) => {
+                Self::NonTemplate(tempstr.hydrate_json(args).into_unchecked())
+            }
+            Self::Array(arr) => {
+                Self::Array(arr.iter().map(|x| x.hydrate_static(args)).collect()).compact()
+            }
+            Self::Object(props) => Self::Object(
+                props
+                    .iter()
+                    .map(|(key, value)| {
+                        (
+                            TempStr::literal(key.hydrate(args)),
+                            value.hydrate_static(args),
+                        )
+                    })
+                    .collect(),
+            )
+            .compact(),
+        }
+    }
+
+    /// Turn arrays and objects that don't contain any template strings into [`PresetBlob::NonTemplate`],
+    /// so they don't need to be hydrated
+    fn compact(self) -> Self {
+        match self {
+            Self::Array(arr) => {
+                if !arr.iter().all(|x| matches!(x, Self::NonTemplate(_))) {
+                    return Self::Array(arr);
+                }
+                let arr = arr
+                    .into_iter()
+                    .filter_map(|x| match x {
+                        Self::NonTemplate(value) => Some(value),
+                        _ => None,
+                    })
+                    .collect();
+                Self::NonTemplate(SafeRouteBlob::OwnedArray(arr))
+            }
+            Self::Object(props) => {
+                let is_static = props
+                    .iter()
+                    .all(|(key, value)| key.is_literal() && matches!(value, Self::NonTemplate(_)));
+                if !is_static {
+                    return Self::Object(props);
+                }
+                let mut obj = BTreeMap::new();
+                for (key, value) in props {
+                    if let (Some(key), Self::NonTemplate(value)) = (key.as_literal(), value) {
+                        obj.insert(key, value);
+                    }
+                }
+                Self::NonTemplate(SafeRouteBlob::OwnedObject(obj))
+            }
+            _ => self,
+        }
+    }
+
+    /// Get the elements if the blob is an array
+    fn as_list(&self) -> Option<Vec<Self>> {
+        match self {
+            Self::Array(arr) => Some(arr.clone()),
+            Self::NonTemplate(value) => match Value::from(value.clone()) {
+                Value::Array(arr) => Some(
+                    arr.into_iter()
+                        .map(|x| Self::NonTemplate(x.into_unchecked()))
+                        .collect(),
+                ),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the properties if the blob is an object. Returns None if it is not an object,
+    /// or any key is a template string
+    fn as_literal_object(&self) -> Option<Vec<(String, Self)>> {
+        match self {
+            Self::Object(props) => props
+                .iter()
+                .map(|(key, value)| Some((key.as_literal()?, value.clone())))
+                .collect(),
+            Self::NonTemplate(value) => match Value::from(value.clone()) {
+                Value::Object(obj) => Some(
+                    obj.into_iter()
+                        .map(|(key, value)| (key, Self::NonTemplate(value.into_unchecked())))
+                        .collect(),
+                ),
+                _ => None,
+            },
+            _ => None,
+        }
     }
+
+    fn is_object(&self) -> bool {
+        match self {
+            Self::Object(_) => true,
+            Self::NonTemplate(value) => value.as_object().is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// Resolve a preset reference that doesn't have any template strings to
+/// the static properties of the preset
+async fn resolve_static(
+    preset_ref: &PresetBlob,
+    presets: &mut BTreeMap<String, Preset>,
+    optimized_presets: &mut BTreeMap<String, Preset>,
+) -> Option<PropMap> {
+    let preset_str = match preset_ref {
+        PresetBlob::NonTemplate(value) => value.as_str()?,
+        _ => return None,
+    };
+    if !preset_str.starts_with('_') {
+        return None;
+    }
+    let inst = PresetInst::try_parse(preset_str)?;
+    if !optimized_presets.contains_key(&inst.name) {
+        let mut preset = presets.remove(&inst.name)?;
+        preset.optimize(presets, optimized_presets).await;
+        optimized_presets.insert(inst.name.clone(), preset);
+    }
+    optimized_presets
+        .get(&inst.name)?
+        .hydrate_static(&inst.args)
+}
+
+/// Top-level properties of a preset with literal keys
+///
+/// The properties are desugared the same way as the line properties in the compiler,
+/// so merging them gives the same result as applying them one after another
+#[derive(Default)]
+struct PropMap(BTreeMap<String, PresetBlob>);
+
+impl PropMap {
+    /// Insert a property and automatically desugar it
+    fn insert(&mut self, key: String, value: PresetBlob) {
+        match key.as_str() {
+            prop::COORD => {
+                let value = PresetBlob::Array(vec![value]).compact();
+                self.insert(prop::MOVEMENTS.to_string(), value);
+            }
+            prop::ICON => {
+                self.insert(prop::ICON_DOC.to_string(), value.clone());
+                self.insert(prop::ICON_MAP.to_string(), value);
+            }
+            _ => {
+                self.0.insert(key, value);
+            }
+        }
+    }
+
+    /// Override the properties with properties in `other`
+    fn extend(&mut self, other: PropMap) {
+        self.0.extend(other.0);
+    }
+
+    /// Deep merge the properties in `other` into self
+    ///
+    /// Returns false if the properties cannot be merged statically
+    fn deep_merge(&mut self, other: PropMap) -> bool {
+        for (key, value) in other.0 {
+            let value = match self.0.remove(&key) {
+                Some(base) => match deep_merge_blob(base, value) {
+                    Some(value) => value,
+                    None => return false,
+                },
+                None => value,
+            };
+            self.0.insert(key, value);
+        }
+        true
+    }
+}
+
+fn deep_merge_blob(base: PresetBlob, other: PresetBlob) -> Option<PresetBlob> {
+    if !base.is_object() || !other.is_object() {
+        return Some(other);
+    }
+    let mut out = base
+        .as_literal_object()?
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    for (key, value) in other.as_literal_object()? {
+        let value = match out.remove(&key) {
+            Some(base) => deep_merge_blob(base, value)?,
+            None => value,
+        };
+        out.insert(key, value);
+    }
+    let props = out
+        .into_iter()
+        .map(|(key, value)| (TempStr::literal(key), value))
+        .collect();
+    Some(PresetBlob::Object(props).compact())
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn compile_presets(presets: Value) -> BTreeMap<String, Preset> {
+        match presets {
+            Value::Object(obj) => obj
+                .into_iter()
+                .map(|(k, v)| (k, Preset::compile(v).unwrap()))
+                .collect(),
+            _ => panic!("presets must be an object"),
+        }
+    }
+
+    fn hydrate(preset: &Preset, args: &[&str]) -> BTreeMap<String, Value> {
+        let args = args.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        let mut map = BTreeMap::new();
+        preset.hydrate(&args, &mut map);
+        map
+    }
+
+    fn to_map(value: Value) -> BTreeMap<String, Value> {
+        match value {
+            Value::Object(obj) => obj.into_iter().collect(),
+            _ => panic!("value must be an object"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_no_refs() {
+        let presets = compile_presets(json!({
+            "_a": {
+                "text": "hello $(0)",
+                "comment": "world",
+                "$(1)": "key",
+            },
+            "_b": {
+                "text": "hello $(0)",
+                "vars": {"a": "$(1)", "b": 1},
+            },
+        }));
+        let optimized = optimize_presets(presets.clone()).await;
+        // not optimizable because of template key
+        assert_eq!(optimized.get("_a"), presets.get("_a"));
+        assert_eq!(
+            hydrate(optimized.get("_b").unwrap(), &["foo", "bar"]),
+            to_map(json!({
+                "text": "hello foo",
+                "vars": {"a": "bar", "b": 1},
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flatten_presets() {
+        let presets = compile_presets(json!({
+            "_one": {
+                "text": "one $(0)",
+                "comment": "one",
+                "icon": "one",
+            },
+            "_two": {
+                "comment": "two",
+                "coord": [1, 2],
+            },
+            "_three": {
+                "presets": ["_one<x>", "_two"],
+                "text": "three $(0)",
+            },
+            "_four": {
+                "presets": "_three",
+                "icon-map": "four",
+            },
+        }));
+        let optimized = optimize_presets(presets).await;
+        let expected = to_map(json!({
+            "text": "three ",
+            "comment": "two",
+            "icon-doc": "one",
+            "icon-map": "four",
+            "movements": [[1, 2]],
+        }));
+        assert_eq!(hydrate(optimized.get("_four").unwrap(), &[]), expected);
+        assert_eq!(
+            hydrate(optimized.get("_three").unwrap(), &["foo"]),
+            to_map(json!({
+                "text": "three foo",
+                "comment": "two",
+                "icon-doc": "one",
+                "icon-map": "one",
+                "movements": [[1, 2]],
+            }))
+        );
+        // everything is static
+        assert!(optimized
+            .get("_four")
+            .unwrap()
+            .0
+            .iter()
+            .all(|(_, v)| matches!(v, PresetBlob::NonTemplate(_))));
+    }
+
+    #[tokio::test]
+    async fn test_flatten_suffix_only() {
+        let presets = compile_presets(json!({
+            "_one": {
+                "text": "one $(0)",
+            },
+            "_two": {
+                "comment": "two",
+            },
+            "_three": {
+                "presets": ["_one", "_one<$(0)>", "_two"],
+                "text": "three",
+            },
+            "_four": {
+                "presets": ["_two", "_notfound"],
+            },
+        }));
+        let optimized = optimize_presets(presets).await;
+        assert_eq!(
+            hydrate(optimized.get("_three").unwrap(), &["foo"]),
+            to_map(json!({
+                "presets": ["_one", "_one<foo>"],
+                "text": "three",
+                "comment": "two",
+            }))
+        );
+        assert_eq!(
+            hydrate(optimized.get("_four").unwrap(), &[]),
+            to_map(json!({
+                "presets": ["_two", "_notfound"],
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flatten_extends() {
+        let presets = compile_presets(json!({
+            "_base": {
+                "text": "base $(0)",
+                "vars": {"a": 1, "b": "$(0)"},
+            },
+            "_child": {
+                "extends": "_base<x>",
+                "text": "child $(0)",
+                "vars": {"c": "$(0)"},
+            },
+            "_template": {
+                "extends": "_base<$(0)>",
+                "text": "template",
+            },
+        }));
+        let optimized = optimize_presets(presets.clone()).await;
+        assert_eq!(
+            hydrate(optimized.get("_child").unwrap(), &["foo"]),
+            to_map(json!({
+                "text": "child foo",
+                "vars": {"a": 1, "b": "x", "c": "foo"},
+            }))
+        );
+        assert_eq!(optimized.get("_template"), presets.get("_template"));
+    }
+
+    #[tokio::test]
+    async fn test_flatten_extends_with_presets() {
+        let presets = compile_presets(json!({
+            "_base": {
+                "text": "base",
+                "comment": "base",
+                "vars": {"a": 1},
+            },
+            "_other": {
+                "comment": "other",
+                "vars": {"b": 2},
+            },
+            "_child": {
+                "extends": "_base",
+                "presets": ["_other"],
+                "vars": {"c": 3},
+            },
+            "_partial": {
+                "extends": "_base",
+                "presets": ["_notfound", "_other"],
+                "text": "partial",
+            },
+        }));
+        let optimized = optimize_presets(presets.clone()).await;
+        // presets replace the properties of the extended preset
+        assert_eq!(
+            hydrate(optimized.get("_child").unwrap(), &[]),
+            to_map(json!({
+                "text": "base",
+                "comment": "other",
+                "vars": {"b": 2, "c": 3},
+            }))
+        );
+        assert_eq!(optimized.get("_partial"), presets.get("_partial"));
+    }
+
+    #[tokio::test]
+    async fn test_cycle() {
+        let presets = compile_presets(json!({
+            "_a": {
+                "presets": ["_b"],
+                "text": "a",
+            },
+            "_b": {
+                "presets": ["_a"],
+                "text": "b",
+            },
+            "_c": {
+                "extends": "_c",
+            },
+        }));
+        let optimized = optimize_presets(presets).await;
+        assert_eq!(
+            hydrate(optimized.get("_a").unwrap(), &[]),
+            to_map(json!({
+                "presets": ["_b"],
+                "text": "a",
+            }))
+        );
+        assert_eq!(
+            hydrate(optimized.get("_b").unwrap(), &[]),
+            to_map(json!({
+                "presets": ["_a"],
+                "text": "b",
+            }))
+        );
+        assert_eq!(
+            hydrate(optimized.get("_c").unwrap(), &[]),
+            to_map(json!({
+                "extends": "_c",
+            }))
+        );
+    }
+}
@@ -26,80 +26,82 @@ fn from_pts(pt: &[pt::Block]) -> Vec<DocRichTextBlock> {
     let mut out = vec![];
     for pt in pt {
         match pt {
-            pt::Block::Text(pt_text) => match out.last_mut() {
-                Some(DocRichTextBlock {
-                    tag: None,
-                    ref mut text,
-                    ..
-                }) => {
-                    for pt_unit in pt_text.m_t.iter() {
-                        append_unit_to_string(pt_unit, text);
-                    }
-                }
-                _ => {
-                    let mut text = String::new();
-                    for pt_unit in pt_text.m_t.iter() {
-                        append_unit_to_string(pt_unit, &mut text);
-                    }
-                    out.push(DocRichTextBlock {
-                        tag: None,
-                        text,
-                        link: None,
-                    });
+            pt::Block::Text(pt_text) => {
+                let text = append_untagged(&mut out);
+                for pt_unit in pt_text.m_t.iter() {
+                    append_unit_to_string(pt_unit, text);
                 }
-            },
+            }
             pt::Block::TagExp(pt_tagexp) => {
-                out.push(parse_tagexp(pt_tagexp));
+                parse_tagexp(pt_tagexp, &mut vec![], &mut out);
+            }
+            pt::Block::Symbol(pt_symbol) => {
+                append_untagged(&mut out).push_str(&pt_symbol.m_t);
+            }
+            pt::Block::Space(pt_space) => {
+                append_untagged(&mut out).push_str(&pt_space.m_t);
             }
-            pt::Block::Symbol(pt_symbol) => match out.last_mut() {
-                Some(DocRichTextBlock {
-                    tag: None, text, ..
-                }) => {
-                    text.push_str(&pt_symbol.m_t);
-                }
-                _ => {
-                    out.push(DocRichTextBlock {
-                        tag: None,
-                        text: pt_symbol.m_t.to_string(),
-                        link: None,
-                    });
-                }
-            },
-            pt::Block::Space(pt_space) => match out.last_mut() {
-                Some(DocRichTextBlock {
-                    tag: None, text, ..
-                }) => {
-                    text.push_str(&pt_space.m_t);
-                }
-                _ => {
-                    out.push(DocRichTextBlock {
-                        tag: None,
-                        text: pt_space.m_t.to_string(),
-                        link: None,
-                    });
-                }
-            },
         }
     }
     out
 }
 
+/// Get the text of the last block to append untagged text to,
+/// or push a new block if the last block is tagged
+fn append_untagged(out: &mut Vec<DocRichTextBlock>) -> &mut String {
+    let need_new_block = !matches!(out.last(), Some(DocRichTextBlock { tag: None, .. }));
+    if need_new_block {
+        out.push(DocRichTextBlock::text(""));
+    }
+    let last = out.len() - 1;
+    &mut out[last].text
+}
+
 /// Parse tree hook for TagExp
-fn parse_tagexp(pt: &pt::TagExp) -> DocRichTextBlock {
+///
+/// Nested tags are flattened into blocks with the enclosing tags in `outer_tags`
+fn parse_tagexp(pt: &pt::TagExp, outer_tags: &mut Vec<String>, out: &mut Vec<DocRichTextBlock>) {
     let tag = pt.m_tag.to_string();
     let mut arg = String::new();
     if let Some(str) = &pt.m_space {
         arg.push_str(str);
     }
-    for pt_unit in pt.m_arg.iter() {
-        append_unit_inside_tag_to_string(pt_unit, &mut arg);
+    let mut has_block = false;
+    for pt_content in pt.m_arg.iter() {
+        match pt_content {
+            pt::TagContent::TagExp(pt_inner) => {
+                if !arg.is_empty() {
+                    out.push(tagged_block(&tag, outer_tags, std::mem::take(&mut arg)));
+                }
+                outer_tags.push(tag.clone());
+                parse_tagexp(pt_inner, outer_tags, out);
+                outer_tags.pop();
+                has_block = true;
+            }
+            pt::TagContent::UnitInsideTag(pt_unit) => {
+                append_unit_inside_tag_to_string(pt_unit, &mut arg);
+            }
+            pt::TagContent::UnitSpace(pt_space) => {
+                arg.push_str(&pt_space.m_t);
+            }
+        }
+    }
+    // keep the empty block for empty tags like `.tag()`
+    if !arg.is_empty() || !has_block {
+        out.push(tagged_block(&tag, outer_tags, arg));
     }
+}
+
+fn tagged_block(tag: &str, outer_tags: &[String], text: String) -> DocRichTextBlock {
     DocRichTextBlock {
-        tag: Some(tag),
-        text: arg,
+        tag: Some(tag.to_string()),
+        outer_tags: outer_tags.to_vec(),
+        text,
         link: None,
+        icon: None,
     }
 }
+
 fn append_unit_inside_tag_to_string(pt: &pt::UnitInsideTag, out: &mut String) {
     match pt {
         pt::UnitInsideTag::Unit(pt) => {
@@ -152,16 +154,20 @@ mod test {
             parse_rich("hello"),
             DocRichText(vec![DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: "hello".to_string(),
                 link: None,
+                icon: None,
             }])
         );
         assert_eq!(
             parse_rich("hello world"),
             DocRichText(vec![DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: "hello world".to_string(),
                 link: None,
+                icon: None,
             }])
         );
     }
@@ -172,8 +178,10 @@ mod test {
             parse_rich(".tag(hello)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: Some("tag".to_string()),
+                outer_tags: vec![],
                 text: "hello".to_string(),
                 link: None,
+                icon: None,
             }])
         );
         assert_eq!(
@@ -181,13 +189,17 @@ mod test {
             DocRichText(vec![
                 DocRichTextBlock {
                     tag: Some("tag".to_string()),
+                    outer_tags: vec![],
                     text: "hello".to_string(),
                     link: None,
+                    icon: None,
                 },
                 DocRichTextBlock {
                     tag: Some("tag2-zzz".to_string()),
+                    outer_tags: vec![],
                     text: "world foo bar".to_string(),
                     link: None,
+                    icon: None,
                 }
             ])
         );
@@ -200,13 +212,17 @@ mod test {
             DocRichText(vec![
                 DocRichTextBlock {
                     tag: None,
+                    outer_tags: vec![],
                     text: "something".to_string(),
-                    link: None
+                    link: None,
+                    icon: None
                 },
                 DocRichTextBlock {
                     tag: Some("tag".to_string()),
+                    outer_tags: vec![],
                     text: "".to_string(),
-                    link: None
+                    link: None,
+                    icon: None
                 }
             ])
         );
@@ -218,16 +234,20 @@ mod test {
             parse_rich("this is a normal sentence. this is normal"),
             DocRichText(vec![DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: "this is a normal sentence. this is normal".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
         assert_eq!(
             parse_rich("this is a (normal sentence). this (is) normal"),
             DocRichText(vec![DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: "this is a (normal sentence). this (is) normal".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
     }
@@ -238,32 +258,40 @@ mod test {
             parse_rich("\\.tag(hello)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: ".tag(hello)".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
         assert_eq!(
             parse_rich(".tag(hello\\) continue)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: Some("tag".to_string()),
+                outer_tags: vec![],
                 text: "hello) continue".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
         assert_eq!(
             parse_rich(".tag(hello\\continue)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: Some("tag".to_string()),
+                outer_tags: vec![],
                 text: "hello\\continue".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
         assert_eq!(
             parse_rich(".\\\\tag(hellocontinue)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: ".\\tag(hellocontinue)".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
     }
@@ -274,8 +302,10 @@ mod test {
             parse_rich(".tag([hello]continue.me)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: Some("tag".to_string()),
+                outer_tags: vec![],
                 text: "[hello]continue.me".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
     }
@@ -286,9 +316,88 @@ mod test {
             parse_rich(".tag([hello]co(ntinue.me)"),
             DocRichText(vec![DocRichTextBlock {
                 tag: Some("tag".to_string()),
+                outer_tags: vec![],
                 text: "[hello]co(ntinue.me".to_string(),
-                link: None
+                link: None,
+                icon: None
             }])
         );
     }
+
+    #[test]
+    fn test_nested_tags() {
+        assert_eq!(
+            parse_rich(".item(.important(Master Sword))"),
+            DocRichText(vec![DocRichTextBlock {
+                tag: Some("important".to_string()),
+                outer_tags: vec!["item".to_string()],
+                text: "Master Sword".to_string(),
+                link: None,
+                icon: None
+            }])
+        );
+        assert_eq!(
+            parse_rich("get .item(the .important(Master .b(Sword)) now) ok"),
+            DocRichText(vec![
+                DocRichTextBlock::text("get "),
+                DocRichTextBlock::with_tag("item", "the "),
+                DocRichTextBlock {
+                    tag: Some("important".to_string()),
+                    outer_tags: vec!["item".to_string()],
+                    text: "Master ".to_string(),
+                    link: None,
+                    icon: None
+                },
+                DocRichTextBlock {
+                    tag: Some("b".to_string()),
+                    outer_tags: vec!["item".to_string(), "important".to_string()],
+                    text: "Sword".to_string(),
+                    link: None,
+                    icon: None
+                },
+                DocRichTextBlock::with_tag("item", " now"),
+                DocRichTextBlock::text(" ok"),
+            ])
+        );
+        assert_eq!(
+            parse_rich(".a(.b()).c(.d(x)y)"),
+            DocRichText(vec![
+                DocRichTextBlock {
+                    tag: Some("b".to_string()),
+                    outer_tags: vec!["a".to_string()],
+                    text: "".to_string(),
+                    link: None,
+                    icon: None
+                },
+                DocRichTextBlock {
+                    tag: Some("d".to_string()),
+                    outer_tags: vec!["c".to_string()],
+                    text: "x".to_string(),
+                    link: None,
+                    icon: None
+                },
+                DocRichTextBlock::with_tag("c", "y"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_nested_tags_unclosed() {
+        // the outer tag is not closed, so only the inner tag is parsed
+        assert_eq!(
+            parse_rich(".a(.b(x)"),
+            DocRichText(vec![
+                DocRichTextBlock::text(".a("),
+                DocRichTextBlock::with_tag("b", "x"),
+            ])
+        );
+        // escaped dot is not a tag
+        assert_eq!(
+            parse_rich(".a(\\.b(x))"),
+            DocRichText(vec![
+                DocRichTextBlock::with_tag("a", ".b(x"),
+                DocRichTextBlock::text(")"),
+            ])
+        );
+    }
 }
@@ -15,12 +15,25 @@ pub struct DocRichText(pub Vec<DocRichTextBlock>);
 pub struct DocRichTextBlock {
     /// The tag name of the text
     ///
-    /// Each block only contains one tag
+    /// If the text is in nested tags, this is the innermost tag
     pub tag: Option<String>,
+    /// The tags enclosing [`tag`](Self::tag), from outermost to innermost
+    ///
+    /// For example, `.item(.important(Master Sword))` is one block with
+    /// tag `important` and outer tags `[item]`. This is omitted when serialized
+    /// if empty, so text without nested tags is the same as before
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[tsify(optional)]
+    pub outer_tags: Vec<String>,
     /// The text content
     pub text: String,
     /// The hyperlink of the text.
     pub link: Option<String>,
+    /// The icon id to show in place of the text, for inline icons like `.icon(korok)`
+    ///
+    /// This is set when executing the document, if the icon is defined in the config.
+    /// The text is kept as the icon id, so it can still be displayed if icons are not supported
+    pub icon: Option<String>,
 }
 
 impl DocRichText {
@@ -70,8 +83,10 @@ impl DocRichTextBlock {
     pub fn text(text: &str) -> Self {
         Self {
             tag: None,
+            outer_tags: vec![],
             text: text.to_string(),
             link: None,
+            icon: None,
         }
     }
 
@@ -79,10 +94,17 @@ impl DocRichTextBlock {
     pub fn with_tag(tag: &str, text: &str) -> Self {
         Self {
             tag: Some(tag.to_string()),
+            outer_tags: vec![],
             text: text.to_string(),
             link: None,
+            icon: None,
         }
     }
+
+    /// Iterate over all tags of the block, from outermost to innermost
+    pub fn tags(&self) -> impl Iterator<Item = &String> {
+        self.outer_tags.iter().chain(self.tag.iter())
+    }
 }
 
 impl Display for DocRichText {
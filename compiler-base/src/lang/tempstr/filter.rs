@@ -0,0 +1,159 @@
+use serde_json::Number;
+
+use super::TempStrFilter;
+
+impl TempStrFilter {
+    /// Parse a filter from its name in the template string, such as `upper` or `default:x`
+    ///
+    /// Returns None if the filter is not recognized
+    pub fn try_parse(s: &str) -> Option<Self> {
+        if let Some(value) = s.strip_prefix("default:") {
+            return Some(Self::Default(value.to_string()));
+        }
+        match s {
+            "upper" => Some(Self::Upper),
+            "lower" => Some(Self::Lower),
+            "kebab" => Some(Self::Kebab),
+            "number" => Some(Self::Number),
+            _ => None,
+        }
+    }
+
+    /// Apply the filter to a value
+    pub fn apply(&self, value: String) -> String {
+        match self {
+            Self::Upper => value.to_uppercase(),
+            Self::Lower => value.to_lowercase(),
+            Self::Kebab => to_kebab_case(&value),
+            Self::Number => match parse_number(&value) {
+                Some(number) => number.to_string(),
+                None => value,
+            },
+            Self::Default(default) => {
+                if value.is_empty() {
+                    default.clone()
+                } else {
+                    value
+                }
+            }
+        }
+    }
+
+    /// Apply a chain of filters to a value, from left to right
+    pub fn apply_all(filters: &[Self], value: &str) -> String {
+        filters
+            .iter()
+            .fold(value.to_string(), |value, filter| filter.apply(value))
+    }
+}
+
+/// Parse a string as a json number, ignoring surrounding whitespaces
+///
+/// Returns None if the string is not a finite number
+pub(super) fn parse_number(value: &str) -> Option<Number> {
+    let value = value.trim();
+    if let Ok(x) = value.parse::<i64>() {
+        return Some(x.into());
+    }
+    value.parse::<f64>().ok().and_then(Number::from_f64)
+}
+
+/// Convert a string to kebab-case
+///
+/// Words are split on non-alphanumeric characters and lower-to-upper case boundaries
+fn to_kebab_case(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut need_dash = false;
+    let mut prev_lower = false;
+    for c in value.chars() {
+        if !c.is_alphanumeric() {
+            need_dash = !out.is_empty();
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            need_dash = true;
+        }
+        if need_dash {
+            out.push('-');
+            need_dash = false;
+        }
+        prev_lower = c.is_lowercase() || c.is_numeric();
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            TempStrFilter::try_parse("upper"),
+            Some(TempStrFilter::Upper)
+        );
+        assert_eq!(
+            TempStrFilter::try_parse("lower"),
+            Some(TempStrFilter::Lower)
+        );
+        assert_eq!(
+            TempStrFilter::try_parse("kebab"),
+            Some(TempStrFilter::Kebab)
+        );
+        assert_eq!(
+            TempStrFilter::try_parse("number"),
+            Some(TempStrFilter::Number)
+        );
+        assert_eq!(
+            TempStrFilter::try_parse("default:x y"),
+            Some(TempStrFilter::Default("x y".to_string()))
+        );
+        assert_eq!(
+            TempStrFilter::try_parse("default:"),
+            Some(TempStrFilter::Default("".to_string()))
+        );
+        assert_eq!(TempStrFilter::try_parse("Upper"), None);
+        assert_eq!(TempStrFilter::try_parse("default"), None);
+        assert_eq!(TempStrFilter::try_parse(" upper"), None);
+    }
+
+    #[test]
+    fn test_kebab() {
+        assert_eq!(to_kebab_case(""), "");
+        assert_eq!(to_kebab_case("Master Sword"), "master-sword");
+        assert_eq!(to_kebab_case("  Master   Sword "), "master-sword");
+        assert_eq!(to_kebab_case("MasterSword"), "master-sword");
+        assert_eq!(to_kebab_case("hyrule_castle2"), "hyrule-castle2");
+        assert_eq!(to_kebab_case("HP Up"), "hp-up");
+        assert_eq!(to_kebab_case("Korok's Seed"), "korok-s-seed");
+    }
+
+    #[test]
+    fn test_number() {
+        assert_eq!(parse_number("12"), Some(12.into()));
+        assert_eq!(parse_number(" -12 "), Some((-12).into()));
+        assert_eq!(parse_number("1.5"), Number::from_f64(1.5));
+        assert_eq!(parse_number("abc"), None);
+        assert_eq!(parse_number("inf"), None);
+        assert_eq!(parse_number(""), None);
+    }
+
+    #[test]
+    fn test_apply_all() {
+        let filters = [TempStrFilter::Default("Hello World".to_string())];
+        assert_eq!(TempStrFilter::apply_all(&filters, ""), "Hello World");
+        assert_eq!(TempStrFilter::apply_all(&filters, "hi"), "hi");
+        let filters = [
+            TempStrFilter::Default("Hello World".to_string()),
+            TempStrFilter::Kebab,
+        ];
+        assert_eq!(TempStrFilter::apply_all(&filters, ""), "hello-world");
+        let filters = [TempStrFilter::Kebab, TempStrFilter::Upper];
+        assert_eq!(TempStrFilter::apply_all(&filters, "a b"), "A-B");
+        let filters = [TempStrFilter::Number];
+        assert_eq!(TempStrFilter::apply_all(&filters, " 100 "), "100");
+        assert_eq!(TempStrFilter::apply_all(&filters, "x"), "x");
+    }
+}
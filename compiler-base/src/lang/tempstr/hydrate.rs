@@ -1,4 +1,7 @@
-use super::{TempStr, TempStrBlock};
+use serde_json::Value;
+
+use super::filter::parse_number;
+use super::{TempStr, TempStrBlock, TempStrFilter};
 
 impl TempStr {
     /// Replace variable in a template string with arguments
@@ -17,14 +20,40 @@ impl TempStr {
                         s.push_str(arg.as_ref());
                     }
                 }
+                TempStrBlock::Filtered(idx, filters) => {
+                    let arg = args.get(*idx).map(|x| x.as_ref()).unwrap_or_default();
+                    s.push_str(&TempStrFilter::apply_all(filters, arg));
+                }
             }
         }
         s
     }
+
+    /// Replace variable in a template string with arguments, and return a json value
+    ///
+    /// The result is a number if the template string is a single variable with
+    /// the `number` filter as the last filter, and the value is a valid number.
+    /// Otherwise, the result is the same as [`hydrate`](Self::hydrate) as a string.
+    pub fn hydrate_json<S>(&self, args: &[S]) -> Value
+    where
+        S: AsRef<str>,
+    {
+        let s = self.hydrate(args);
+        if let [TempStrBlock::Filtered(_, filters)] = self.0.as_slice() {
+            if filters.last() == Some(&TempStrFilter::Number) {
+                if let Some(number) = parse_number(&s) {
+                    return Value::Number(number);
+                }
+            }
+        }
+        Value::String(s)
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use serde_json::json;
+
     use super::*;
 
     #[test]
@@ -61,4 +90,48 @@ mod test {
             "bar tempworldtemp"
         );
     }
+
+    #[test]
+    fn test_filters() {
+        let args = &["Master Sword", "", " 100"];
+        assert_eq!(TempStr::from("$(0|upper)").hydrate(args), "MASTER SWORD");
+        assert_eq!(TempStr::from("$(0|lower)").hydrate(args), "master sword");
+        assert_eq!(
+            TempStr::from("icon-$(0|kebab)").hydrate(args),
+            "icon-master-sword"
+        );
+        assert_eq!(TempStr::from("$(1|default:x)").hydrate(args), "x");
+        assert_eq!(TempStr::from("$(3|default:x)").hydrate(args), "x");
+        assert_eq!(
+            TempStr::from("$(0|default:x)").hydrate(args),
+            "Master Sword"
+        );
+        assert_eq!(TempStr::from("$(2|number)").hydrate(args), "100");
+        assert_eq!(TempStr::from("$(0|number)").hydrate(args), "Master Sword");
+        assert_eq!(TempStr::from("$(3|upper)").hydrate(args), "");
+    }
+
+    #[test]
+    fn test_json() {
+        let args = &["Master Sword", "", " 100", "1.5"];
+        assert_eq!(TempStr::from("$(2|number)").hydrate_json(args), json!(100));
+        assert_eq!(TempStr::from("$(3|number)").hydrate_json(args), json!(1.5));
+        assert_eq!(
+            TempStr::from("$(1|default:-3|number)").hydrate_json(args),
+            json!(-3)
+        );
+        assert_eq!(
+            TempStr::from("$(0|number)").hydrate_json(args),
+            json!("Master Sword")
+        );
+        assert_eq!(
+            TempStr::from("x$(2|number)").hydrate_json(args),
+            json!("x100")
+        );
+        assert_eq!(
+            TempStr::from("$(2|number|upper)").hydrate_json(args),
+            json!("100")
+        );
+        assert_eq!(TempStr::from("$(2)").hydrate_json(args), json!(" 100"));
+    }
 }
@@ -3,6 +3,7 @@
 //! Template string is the $(number) syntax used in defining presets
 use serde::{Deserialize, Serialize};
 
+mod filter;
 mod grammar;
 mod parse;
 use parse::*;
@@ -11,7 +12,8 @@ mod hydrate;
 /// A template string
 ///
 /// Template string can have variables in it represented by $(number),
-/// where number is the index of the variable in the list of variables (starts from 0)
+/// where number is the index of the variable in the list of variables (starts from 0).
+/// Variables can also have filters, like $(0|upper)
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 pub struct TempStr(Vec<TempStrBlock>);
 
@@ -20,9 +22,45 @@ pub struct TempStr(Vec<TempStrBlock>);
 pub enum TempStrBlock {
     Lit(String),
     Var(usize),
+    /// Variable with filters applied from left to right, like $(0|lower|kebab)
+    Filtered(usize, Vec<TempStrFilter>),
+}
+
+/// A transformation applied to a variable in a template string
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum TempStrFilter {
+    /// `upper`: Convert to upper case
+    Upper,
+    /// `lower`: Convert to lower case
+    Lower,
+    /// `kebab`: Convert to kebab-case, for example `Master Sword` becomes `master-sword`
+    Kebab,
+    /// `number`: Trim and normalize the value as a number, if it is a number
+    Number,
+    /// `default:x`: Use `x` if the value is empty or missing
+    Default(String),
 }
 
 impl TempStr {
+    /// Create a template string that is the literal string, without parsing it
+    pub fn literal<S: Into<String>>(s: S) -> Self {
+        let s = s.into();
+        if s.is_empty() {
+            return Self(vec![]);
+        }
+        Self(vec![TempStrBlock::Lit(s)])
+    }
+
+    /// Get the string if the template string has no variables
+    pub fn as_literal(&self) -> Option<String> {
+        match self.0.as_slice() {
+            [] => Some(String::new()),
+            [TempStrBlock::Lit(s)] => Some(s.clone()),
+            _ => None,
+        }
+    }
+
     /// Return if the template string has no variables
     pub fn is_literal(&self) -> bool {
         if self.0.len() == 1 {
@@ -1,7 +1,7 @@
 use regen::sdk::{ASTParser, CreateParseTree, ParseTreeResult, TokenStream};
 
 use super::grammar::{self, pt, Ctx};
-use super::{TempStr, TempStrBlock};
+use super::{TempStr, TempStrBlock, TempStrFilter};
 
 impl<S> From<S> for TempStr
 where
@@ -39,27 +39,27 @@ pub fn from_pts(pts: Vec<pt::Block>) -> Vec<TempStrBlock> {
                 let block = pt.take_unchecked();
                 match &block {
                     TempStrBlock::Lit(block_str) => match output.last_mut() {
-                        None | Some(TempStrBlock::Var(_)) => {
-                            output.push(block);
-                        }
                         Some(TempStrBlock::Lit(s)) => {
                             s.push_str(block_str);
                         }
+                        _ => {
+                            output.push(block);
+                        }
                     },
-                    TempStrBlock::Var(_) => {
+                    _ => {
                         output.push(block);
                     }
                 }
             }
             pt::Block::NonDollar(pt) => match output.last_mut() {
-                None | Some(TempStrBlock::Var(_)) => {
+                Some(TempStrBlock::Lit(s)) => {
+                    append_non_dollar(&pt, s);
+                }
+                _ => {
                     let mut str = String::new();
                     append_non_dollar(&pt, &mut str);
                     output.push(TempStrBlock::Lit(str));
                 }
-                Some(TempStrBlock::Lit(s)) => {
-                    append_non_dollar(&pt, s);
-                }
             },
         }
     }
@@ -88,15 +88,19 @@ pub fn parse_dollar(pt: &pt::Dollar, _ctx: &mut Ctx) -> Option<TempStrBlock> {
                     Some(TempStrBlock::Lit("$".to_string()))
                 }
                 pt::DollarTail::Variable(pt_variable) => {
-                    match pt_variable.m_arg.parse::<usize>() {
-                        Ok(arg) => {
-                            // variable
-                            Some(TempStrBlock::Var(arg))
-                        }
-                        Err(_) => {
-                            // cannot parse the number as valid usize
+                    match parse_variable(pt_variable) {
+                        Some(block) => Some(block),
+                        None => {
+                            // cannot parse the number as valid usize,
+                            // or one of the filters is not valid
                             // treat as string
-                            Some(TempStrBlock::Lit(format!("$({})", pt_variable.m_arg)))
+                            let mut s = format!("$({}", pt_variable.m_arg);
+                            for pt_filter in &pt_variable.m_filters {
+                                s.push('|');
+                                s.push_str(&pt_filter.m_name);
+                            }
+                            s.push(')');
+                            Some(TempStrBlock::Lit(s))
                         }
                     }
                 }
@@ -105,6 +109,19 @@ pub fn parse_dollar(pt: &pt::Dollar, _ctx: &mut Ctx) -> Option<TempStrBlock> {
     }
 }
 
+fn parse_variable(pt: &pt::Variable) -> Option<TempStrBlock> {
+    let arg = pt.m_arg.parse::<usize>().ok()?;
+    if pt.m_filters.is_empty() {
+        return Some(TempStrBlock::Var(arg));
+    }
+    let filters = pt
+        .m_filters
+        .iter()
+        .map(|pt_filter| TempStrFilter::try_parse(&pt_filter.m_name))
+        .collect::<Option<Vec<_>>>()?;
+    Some(TempStrBlock::Filtered(arg, filters))
+}
+
 fn append_non_dollar(pt: &pt::NonDollar, out: &mut String) {
     match pt {
         pt::NonDollar::Text(pt) => out.push_str(&pt.m_t),
@@ -257,4 +274,60 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(
+            TempStr::from("$(0|upper)").0,
+            vec![TempStrBlock::Filtered(0, vec![TempStrFilter::Upper])]
+        );
+        assert_eq!(
+            TempStr::from("icon-$(0|lower|kebab)").0,
+            vec![
+                TempStrBlock::Lit("icon-".to_string()),
+                TempStrBlock::Filtered(0, vec![TempStrFilter::Lower, TempStrFilter::Kebab]),
+            ]
+        );
+        assert_eq!(
+            TempStr::from("$(1|default:Hello World)!").0,
+            vec![
+                TempStrBlock::Filtered(1, vec![TempStrFilter::Default("Hello World".to_string())]),
+                TempStrBlock::Lit("!".to_string()),
+            ]
+        );
+        assert_eq!(
+            TempStr::from("$(1|default:)").0,
+            vec![TempStrBlock::Filtered(
+                1,
+                vec![TempStrFilter::Default("".to_string())]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        assert_eq!(
+            TempStr::from("$(0|foo)").0,
+            vec![TempStrBlock::Lit("$(0|foo)".to_string())]
+        );
+        assert_eq!(
+            TempStr::from("$(0|upper|foo)$(1)").0,
+            vec![
+                TempStrBlock::Lit("$(0|upper|foo)".to_string()),
+                TempStrBlock::Var(1),
+            ]
+        );
+        assert_eq!(
+            TempStr::from("$(0|)").0,
+            vec![TempStrBlock::Lit("$(0|)".to_string())]
+        );
+        assert_eq!(
+            TempStr::from("$(0|upper").0,
+            vec![TempStrBlock::Lit("$(0|upper".to_string())]
+        );
+        assert_eq!(
+            TempStr::from("a|b$$(0|upper)").0,
+            vec![TempStrBlock::Lit("a|b$(0|upper)".to_string())]
+        );
+    }
 }
@@ -19,6 +19,7 @@ pub const DETAILED: &str = "detailed";
 pub const ENTRY_POINTS: &str = "entry-points";
 pub const EXCLUDE: &str = "exclude";
 pub const EXPOSE: &str = "expose";
+pub const EXTENDS: &str = "extends";
 pub const ICON: &str = "icon";
 pub const ICON_DOC: &str = "icon-doc";
 pub const ICON_MAP: &str = "icon-map";
@@ -0,0 +1,96 @@
+//! Converting other data formats to JSON values
+
+use serde_json::{Map, Value};
+
+/// Convert a TOML value to JSON. Date and time values are converted to strings
+pub fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(arr) => Value::Array(arr.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Convert CSV with a header row to an array of objects, keyed by the header
+///
+/// Rows can have fewer columns than the header, in which case the missing
+/// columns are not in the object
+pub fn csv_to_json(bytes: &[u8]) -> Result<Value, csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::Headers)
+        .from_reader(bytes);
+    let headers = reader.headers()?.clone();
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let mut row = Map::new();
+        for (key, value) in headers.iter().zip(record.iter()) {
+            row.insert(key.to_string(), Value::String(value.to_string()));
+        }
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_toml_to_json() {
+        let toml = r#"
+title = "Hello"
+count = 3
+ratio = 0.5
+enabled = true
+date = 1979-05-27
+
+[presets.foo]
+text = "foo"
+icons = ["a", "b"]
+"#;
+        let value = toml_to_json(toml::from_str(toml).unwrap());
+        assert_eq!(
+            value,
+            json!({
+                "title": "Hello",
+                "count": 3,
+                "ratio": 0.5,
+                "enabled": true,
+                "date": "1979-05-27",
+                "presets": {
+                    "foo": {
+                        "text": "foo",
+                        "icons": ["a", "b"],
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_csv_to_json() {
+        let csv = b"name, price,note\nApple,10,\"red, sweet\"\nBanana,5\n";
+        assert_eq!(
+            csv_to_json(csv).unwrap(),
+            json!([
+                { "name": "Apple", "price": "10", "note": "red, sweet" },
+                { "name": "Banana", "price": "5" },
+            ])
+        );
+        assert_eq!(csv_to_json(b"name,price\n").unwrap(), json!([]));
+        assert_eq!(csv_to_json(b"").unwrap(), json!([]));
+    }
+}
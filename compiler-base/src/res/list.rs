@@ -0,0 +1,147 @@
+//! Resolving directory and glob `use`s to multiple resources
+
+use super::{Loader, ResError, ResPath, ResResult, Resource, ValidUse};
+
+impl<'a, L> Resource<'a, L>
+where
+    L: Loader,
+{
+    /// Resolve a directory (`./icons/`) or glob (`./sections/*.yaml`) `use` to the resources
+    /// it refers to, sorted by path
+    ///
+    /// Only files directly inside the directory are included. Sub-directories are skipped.
+    /// The directory is listed with [`Loader::list_dir`], which not all loaders support.
+    pub async fn resolve_list(&self, target: &ValidUse) -> ResResult<Vec<Resource<'a, L>>> {
+        let resolved = self.resolve(target)?;
+        let (dir, pattern) = if target.is_dir() {
+            (resolved.path, "*")
+        } else {
+            let path = resolved.path.as_path();
+            let pattern = path.file_name().unwrap_or("*");
+            let parent = path.parent().map(|x| x.to_path_buf()).unwrap_or_default();
+            let dir = match &resolved.path {
+                ResPath::Local(_) => ResPath::new_local_unchecked(parent),
+                ResPath::Remote(url, _) => ResPath::new_remote_unchecked(url.clone(), parent),
+            };
+            (dir, pattern)
+        };
+
+        let mut names = self
+            .loader
+            .list_dir(&dir)
+            .await?
+            .into_iter()
+            .filter(|name| !name.ends_with('/') && glob_match(pattern, name))
+            .collect::<Vec<_>>();
+        names.sort();
+
+        names
+            .into_iter()
+            .map(|name| match dir.join_resolve(&name) {
+                Some(path) => Ok(self.with_path(path)),
+                None => Err(ResError::CannotResolve(dir.to_string(), name)),
+            })
+            .collect()
+    }
+}
+
+/// Match a file name against a pattern, where `*` matches any number of characters
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // unwrap is safe because split always returns at least one element
+    let first = parts.next().unwrap();
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // the last part must match the end
+            return rest.len() >= part.len() && rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    // no `*` in the pattern
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::env::RefCounted;
+    use crate::macros::async_trait;
+
+    struct TestLoader;
+    #[async_trait(auto)]
+    impl Loader for TestLoader {
+        async fn load_raw(&self, _: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            unreachable!()
+        }
+
+        async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+            match path.to_string().as_str() {
+                "" => Ok(vec!["project.yaml".to_string(), "sections/".to_string()]),
+                "sections" => Ok(vec![
+                    "b.yaml".to_string(),
+                    "a.yaml".to_string(),
+                    "readme.md".to_string(),
+                    "nested/".to_string(),
+                ]),
+                _ => Err(ResError::FailToListDir(path.to_string(), "404".to_string())),
+            }
+        }
+    }
+
+    fn paths(resources: Vec<Resource<TestLoader>>) -> Vec<String> {
+        resources.iter().map(|r| r.path().to_string()).collect()
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "a.yaml"));
+        assert!(glob_match("*.yaml", "a.yaml"));
+        assert!(glob_match("*.yaml", ".yaml"));
+        assert!(!glob_match("*.yaml", "a.yml"));
+        assert!(glob_match("a*", "a.yaml"));
+        assert!(glob_match("a*b*c", "aXbYc"));
+        assert!(!glob_match("a*b*c", "aXcYb"));
+        assert!(!glob_match("a*a", "a"));
+        assert!(glob_match("a.yaml", "a.yaml"));
+        assert!(!glob_match("a.yaml", "a.yamlx"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_list() {
+        let res = Resource::new(
+            ResPath::new_local_unchecked("project.yaml"),
+            RefCounted::new(TestLoader),
+        );
+
+        let target = ValidUse::Relative("./sections/".to_string());
+        let result = res.resolve_list(&target).await.unwrap();
+        assert_eq!(
+            paths(result),
+            vec!["sections/a.yaml", "sections/b.yaml", "sections/readme.md"]
+        );
+
+        let target = ValidUse::Relative("./sections/*.yaml".to_string());
+        let result = res.resolve_list(&target).await.unwrap();
+        assert_eq!(paths(result), vec!["sections/a.yaml", "sections/b.yaml"]);
+
+        let target = ValidUse::Absolute("/*.yaml".to_string());
+        let result = res.resolve_list(&target).await.unwrap();
+        assert_eq!(paths(result), vec!["project.yaml"]);
+
+        let target = ValidUse::Relative("./foo/".to_string());
+        let result = res.resolve_list(&target).await;
+        assert_eq!(
+            result.map(paths),
+            Err(ResError::FailToListDir("foo".to_string(), String::new()))
+        );
+    }
+}
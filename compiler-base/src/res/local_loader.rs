@@ -0,0 +1,235 @@
+//! Loader for local files on the file system, for native builds
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+use crate::env::RefCounted;
+use crate::macros::async_trait;
+use crate::util::Component;
+
+use super::{Loader, ResError, ResPath, ResResult};
+
+/// Default maximum size of a local file that can be loaded
+pub const MAX_LOCAL_RESOURCE_SIZE: u64 = 1024 * 1024 * 10; // 10 MB
+
+/// Loader that loads local paths from a project root directory on the file system
+///
+/// The loader is sandboxed to the root directory. Paths that escape the root,
+/// either with `..` or through symbolic links, are refused.
+/// Remote paths are not supported.
+#[derive(Debug)]
+pub struct LocalLoader {
+    /// Canonicalized root directory
+    root: PathBuf,
+    /// Maximum size of a file that can be loaded
+    max_size: u64,
+}
+
+impl LocalLoader {
+    /// Create a loader with the project root directory
+    pub fn new(root: impl AsRef<Path>) -> ResResult<Self> {
+        let root = root.as_ref();
+        let root = match root.canonicalize() {
+            Ok(root) => root,
+            Err(e) => {
+                return Err(ResError::Create(format!(
+                    "Cannot access project root `{}`: {e}",
+                    root.display()
+                )))
+            }
+        };
+        if !root.is_dir() {
+            return Err(ResError::Create(format!(
+                "Project root `{}` is not a directory",
+                root.display()
+            )));
+        }
+        Ok(Self {
+            root,
+            max_size: MAX_LOCAL_RESOURCE_SIZE,
+        })
+    }
+
+    /// Set the maximum size of a file that can be loaded
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Get the (canonicalized) project root directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve the path on the file system, making sure it's inside the root.
+    /// Returns the error as [`ResError`] created with `fail`, or [`ResError::NotFound`]
+    /// if the path does not exist
+    async fn resolve<F>(&self, path: &ResPath<'_>, fail: F) -> ResResult<PathBuf>
+    where
+        F: Fn(String) -> ResError,
+    {
+        let path = match path {
+            ResPath::Local(path) => path,
+            ResPath::Remote(_, _) => {
+                return Err(fail(
+                    "Remote paths are not supported by the local loader".to_string(),
+                ))
+            }
+        };
+        let mut fs_path = self.root.clone();
+        for c in path.components() {
+            match c {
+                Component::Normal(c) => fs_path.push(c),
+                Component::CurDir => {}
+                _ => return Err(fail("Path is outside of the project root".to_string())),
+            }
+        }
+        // symbolic links could point outside of the root
+        let fs_path = match fs::canonicalize(&fs_path).await {
+            Ok(fs_path) => fs_path,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(ResError::NotFound(path.to_string()))
+            }
+            Err(e) => return Err(fail(e.to_string())),
+        };
+        if !fs_path.starts_with(&self.root) {
+            return Err(fail("Path is outside of the project root".to_string()));
+        }
+        Ok(fs_path)
+    }
+}
+
+#[async_trait(auto)]
+impl Loader for LocalLoader {
+    async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+        let fail = |e: String| ResError::FailToLoadFile(path.to_string(), e);
+        let fs_path = self.resolve(path, fail).await?;
+        let metadata = fs::metadata(&fs_path)
+            .await
+            .map_err(|e| fail(e.to_string()))?;
+        if !metadata.is_file() {
+            return Err(fail("Path is not a file".to_string()));
+        }
+        let too_large = || fail(format!("File is too large (max {} bytes)", self.max_size));
+        if metadata.len() > self.max_size {
+            return Err(too_large());
+        }
+        // the file could grow after checking the size, so only read up to the limit
+        let file = fs::File::open(&fs_path)
+            .await
+            .map_err(|e| fail(e.to_string()))?;
+        let mut bytes = Vec::with_capacity(metadata.len() as usize);
+        file.take(self.max_size.saturating_add(1))
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|e| fail(e.to_string()))?;
+        if bytes.len() as u64 > self.max_size {
+            return Err(too_large());
+        }
+        Ok(RefCounted::from(bytes))
+    }
+
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        let fail = |e: String| ResError::FailToListDir(path.to_string(), e);
+        let fs_path = self.resolve(path, fail).await?;
+        let mut dir = fs::read_dir(&fs_path)
+            .await
+            .map_err(|e| fail(e.to_string()))?;
+        let mut entries = vec![];
+        while let Some(entry) = dir.next_entry().await.map_err(|e| fail(e.to_string()))? {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                // skip names that can't be used in a resource path
+                Err(_) => continue,
+            };
+            // follow symbolic links to see if the entry is a directory
+            match fs::metadata(entry.path()).await {
+                Ok(metadata) if metadata.is_dir() => entries.push(format!("{name}/")),
+                _ => entries.push(name),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Create an empty directory for the test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("celer-local-loader-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("root/sub")).unwrap();
+        std::fs::write(dir.join("root/project.yaml"), b"hello").unwrap();
+        std::fs::write(dir.join("root/sub/a.yaml"), b"a").unwrap();
+        std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let dir = test_dir("load");
+        let loader = LocalLoader::new(dir.join("root")).unwrap();
+        let path = ResPath::new_local_unchecked("project.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"hello");
+        let path = ResPath::new_local_unchecked("sub/a.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"a");
+        let path = ResPath::new_local_unchecked("sub/missing.yaml");
+        assert_eq!(
+            loader.load_raw(&path).await,
+            Err(ResError::NotFound("sub/missing.yaml".to_string()))
+        );
+        let path = ResPath::new_local_unchecked("sub");
+        assert!(loader.load_raw(&path).await.is_err());
+        let path = ResPath::new_remote_unchecked("https://example.com/", "a.yaml");
+        assert!(loader.load_raw(&path).await.is_err());
+
+        let mut entries = loader
+            .list_dir(&ResPath::new_local_unchecked(""))
+            .await
+            .unwrap();
+        entries.sort();
+        assert_eq!(entries, vec!["project.yaml", "sub/"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_escape_root() {
+        let dir = test_dir("escape");
+        let loader = LocalLoader::new(dir.join("root")).unwrap();
+        let path = ResPath::new_local_unchecked("../secret.txt");
+        assert!(loader.load_raw(&path).await.is_err());
+        let path = ResPath::new_local_unchecked("sub/../../secret.txt");
+        assert!(loader.load_raw(&path).await.is_err());
+        let path = ResPath::new_local_unchecked("..");
+        assert!(loader.list_dir(&path).await.is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("root/link.txt")).unwrap();
+            let path = ResPath::new_local_unchecked("link.txt");
+            assert!(loader.load_raw(&path).await.is_err());
+            std::os::unix::fs::symlink(dir.join("root/sub/a.yaml"), dir.join("root/inner.yaml"))
+                .unwrap();
+            let path = ResPath::new_local_unchecked("inner.yaml");
+            assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"a");
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_max_size() {
+        let dir = test_dir("size");
+        let loader = LocalLoader::new(dir.join("root")).unwrap().with_max_size(3);
+        let path = ResPath::new_local_unchecked("project.yaml");
+        assert!(loader.load_raw(&path).await.is_err());
+        let path = ResPath::new_local_unchecked("sub/a.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"a");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
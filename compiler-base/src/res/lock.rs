@@ -0,0 +1,326 @@
+//! Lock file for pinning the content of remote resources
+//!
+//! Remote `use`s resolve to whatever is on the branch at compile time. To make builds
+//! reproducible, the project can have a `celer.lock` file at the root that maps the URL
+//! of each remote resource to the hash of its content. Loading a remote resource
+//! with a different hash results in an error.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{Loader, ResError, ResPath, ResResult, Resource, ValidUse};
+
+/// Name of the lock file at the root of the project
+pub const LOCK_FILE: &str = "celer.lock";
+
+/// Current version of the lock file format
+pub const LOCK_FILE_VERSION: u32 = 1;
+
+/// Content of the lock file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResLockFile {
+    pub version: u32,
+    /// URL of the remote resource to the hash of its content
+    #[serde(default)]
+    pub resources: BTreeMap<String, String>,
+}
+
+impl Default for ResLockFile {
+    fn default() -> Self {
+        Self {
+            version: LOCK_FILE_VERSION,
+            resources: BTreeMap::new(),
+        }
+    }
+}
+
+impl ResLockFile {
+    /// Parse the lock file from bytes
+    pub fn from_slice(bytes: &[u8]) -> ResResult<Self> {
+        let lock_file: Self =
+            serde_json::from_slice(bytes).map_err(|e| ResError::InvalidLockFile(e.to_string()))?;
+        if lock_file.version != LOCK_FILE_VERSION {
+            return Err(ResError::InvalidLockFile(format!(
+                "unsupported version {}",
+                lock_file.version
+            )));
+        }
+        Ok(lock_file)
+    }
+
+    /// Serialize the lock file to be saved
+    pub fn to_string_pretty(&self) -> String {
+        // serializing string maps should never fail
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+/// Lock for the remote resources loaded by a project
+///
+/// It checks the remote resources against the pinned hashes in the lock file,
+/// and records the hashes of all remote resources loaded so the lock file can be
+/// (re)generated. Resources in the same repo as the project are not locked.
+#[derive(Debug, Default)]
+pub struct ResLock {
+    /// URL prefix of the project if it's remote
+    project_url: Option<String>,
+    /// Hashes from the lock file
+    locked: BTreeMap<String, String>,
+    /// Hashes of the resources loaded so far
+    resolved: Mutex<BTreeMap<String, String>>,
+}
+
+impl ResLock {
+    /// Create a lock for the project with the content of the lock file
+    pub fn new(project_path: &ResPath, lock_file: Option<ResLockFile>) -> Self {
+        let project_url = match project_path {
+            ResPath::Local(_) => None,
+            ResPath::Remote(url, _) => Some(url.to_string()),
+        };
+        Self {
+            project_url,
+            locked: lock_file.map(|x| x.resources).unwrap_or_default(),
+            resolved: Default::default(),
+        }
+    }
+
+    /// Load the lock file at the root of the project.
+    ///
+    /// The project is treated as not having a lock file only if the lock file does not exist.
+    /// For local projects, the root directory is listed first (if the loader supports it),
+    /// so the lock file is not loaded if it's not there. An error is returned if the lock file
+    /// cannot be loaded for other reasons (like network errors), or if it's invalid
+    pub async fn load<L>(project_res: &Resource<'_, L>) -> ResResult<Self>
+    where
+        L: Loader,
+    {
+        let lock_use = ValidUse::Absolute(format!("/{LOCK_FILE}"));
+        let lock_res = project_res.resolve(&lock_use)?;
+        if lock_res.path().is_local() {
+            let root = ResPath::new_local_unchecked("");
+            if let Ok(entries) = lock_res.loader.list_dir(&root).await {
+                if !entries.iter().any(|x| x == LOCK_FILE) {
+                    return Ok(Self::new(project_res.path(), None));
+                }
+            }
+        }
+        let lock_file = match lock_res.loader.load_raw(lock_res.path()).await {
+            Ok(bytes) => Some(ResLockFile::from_slice(&bytes)?),
+            Err(ResError::NotFound(_)) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Self::new(project_res.path(), lock_file))
+    }
+
+    /// Check the content of a loaded resource against the lock, and record its hash
+    pub fn check(&self, path: &ResPath, bytes: &[u8]) -> ResResult<()> {
+        let url = match path {
+            ResPath::Local(_) => return Ok(()),
+            ResPath::Remote(url, _) => url,
+        };
+        if self.project_url.as_deref() == Some(url) {
+            return Ok(());
+        }
+        let key = path.to_string();
+        let hash = hash_content(bytes);
+        if let Ok(mut resolved) = self.resolved.lock() {
+            resolved.insert(key.clone(), hash.clone());
+        }
+        match self.locked.get(&key) {
+            Some(expected) if expected != &hash => {
+                Err(ResError::LockMismatch(key, expected.clone(), hash))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get the lock file with the hashes of the resources loaded so far,
+    /// along with the pinned hashes that are not loaded
+    pub fn to_lock_file(&self) -> ResLockFile {
+        let mut resources = self.locked.clone();
+        if let Ok(resolved) = self.resolved.lock() {
+            for (url, hash) in resolved.iter() {
+                resources.insert(url.clone(), hash.clone());
+            }
+        }
+        ResLockFile {
+            version: LOCK_FILE_VERSION,
+            resources,
+        }
+    }
+}
+
+/// Compute the hash of the content as it's stored in the lock file
+pub fn hash_content(bytes: &[u8]) -> String {
+    format!("sha256-{:x}", Sha256::digest(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::env::RefCounted;
+    use crate::macros::async_trait;
+
+    const PROJECT_URL: &str = "https://project/";
+    const REMOTE_URL: &str = "https://remote/";
+
+    struct TestLoader(Option<&'static str>);
+    #[async_trait(auto)]
+    impl Loader for TestLoader {
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            match (path.to_string().as_str(), self.0) {
+                ("https://project/celer.lock", Some(lock)) => Ok(RefCounted::from(lock.as_bytes())),
+                ("https://project/celer.lock", None) => Err(ResError::NotFound(path.to_string())),
+                ("celer.lock", _) => panic!("should not load the lock file if it's not listed"),
+                _ => Err(ResError::FailToLoadUrl(
+                    path.to_string(),
+                    "timeout".to_string(),
+                )),
+            }
+        }
+
+        async fn list_dir(&self, _: &ResPath) -> ResResult<Vec<String>> {
+            Ok(vec!["project.yaml".to_string()])
+        }
+    }
+
+    /// Loader like the one in the editor, where the directory can't be listed
+    /// and missing files are reported as not found
+    struct NoListLoader(Option<&'static str>);
+    #[async_trait(auto)]
+    impl Loader for NoListLoader {
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            match (path.to_string().as_str(), self.0) {
+                ("celer.lock", Some(lock)) => Ok(RefCounted::from(lock.as_bytes())),
+                ("celer.lock", None) => Err(ResError::NotFound(path.to_string())),
+                _ => Err(ResError::FailToLoadFile(
+                    path.to_string(),
+                    "permission denied".to_string(),
+                )),
+            }
+        }
+
+        async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+            Err(ResError::FailToListDir(
+                path.to_string(),
+                "not supported".to_string(),
+            ))
+        }
+    }
+
+    fn remote_path(path: &str) -> ResPath<'static> {
+        ResPath::new_remote_unchecked(REMOTE_URL, path.to_string())
+    }
+
+    #[test]
+    fn test_hash_content() {
+        assert_eq!(
+            hash_content(b"hello"),
+            "sha256-2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn test_check_and_record() {
+        let project_path = ResPath::new_remote_unchecked(PROJECT_URL, "project.yaml");
+        let mut lock_file = ResLockFile::default();
+        lock_file
+            .resources
+            .insert("https://remote/a.yaml".to_string(), hash_content(b"a"));
+        lock_file
+            .resources
+            .insert("https://remote/c.yaml".to_string(), hash_content(b"c"));
+        let lock = ResLock::new(&project_path, Some(lock_file));
+
+        assert_eq!(lock.check(&remote_path("a.yaml"), b"a"), Ok(()));
+        assert_eq!(lock.check(&remote_path("b.yaml"), b"b"), Ok(()));
+        assert_eq!(
+            lock.check(&remote_path("a.yaml"), b"changed"),
+            Err(ResError::LockMismatch(
+                "https://remote/a.yaml".to_string(),
+                hash_content(b"a"),
+                hash_content(b"changed"),
+            ))
+        );
+        // project files and local files are not locked
+        let project_file = ResPath::new_remote_unchecked(PROJECT_URL, "foo.yaml");
+        assert_eq!(lock.check(&project_file, b"foo"), Ok(()));
+        let local_file = ResPath::new_local_unchecked("foo.yaml");
+        assert_eq!(lock.check(&local_file, b"foo"), Ok(()));
+
+        let lock_file = lock.to_lock_file();
+        assert_eq!(
+            lock_file.resources.keys().collect::<Vec<_>>(),
+            vec![
+                "https://remote/a.yaml",
+                "https://remote/b.yaml",
+                "https://remote/c.yaml"
+            ]
+        );
+        assert_eq!(
+            lock_file.resources["https://remote/a.yaml"],
+            hash_content(b"changed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load() {
+        let project_path = ResPath::new_remote_unchecked(PROJECT_URL, "project.yaml");
+
+        let resource = Resource::new(project_path.clone(), RefCounted::new(TestLoader(None)));
+        let lock = ResLock::load(&resource).await.unwrap();
+        assert!(lock.locked.is_empty());
+
+        let content = r#"{"version":1,"resources":{"https://remote/a.yaml":"sha256-1"}}"#;
+        let resource = Resource::new(
+            project_path.clone(),
+            RefCounted::new(TestLoader(Some(content))),
+        );
+        let lock = ResLock::load(&resource).await.unwrap();
+        assert_eq!(lock.locked.len(), 1);
+        assert_eq!(
+            ResLockFile::from_slice(lock.to_lock_file().to_string_pretty().as_bytes()),
+            Ok(lock.to_lock_file())
+        );
+
+        let resource = Resource::new(project_path, RefCounted::new(TestLoader(Some("{}"))));
+        assert!(matches!(
+            ResLock::load(&resource).await,
+            Err(ResError::InvalidLockFile(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_errors() {
+        // errors other than not found should not be treated as no lock file
+        let project_path = ResPath::new_remote_unchecked("https://other/", "project.yaml");
+        let resource = Resource::new(project_path, RefCounted::new(TestLoader(None)));
+        assert!(matches!(
+            ResLock::load(&resource).await,
+            Err(ResError::FailToLoadUrl(_, _))
+        ));
+
+        // local projects are listed first
+        let project_path = ResPath::new_local_unchecked("project.yaml");
+        let resource = Resource::new(project_path, RefCounted::new(TestLoader(None)));
+        let lock = ResLock::load(&resource).await.unwrap();
+        assert!(lock.locked.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_load_without_list_dir() {
+        let project_path = ResPath::new_local_unchecked("project.yaml");
+        let resource = Resource::new(project_path.clone(), RefCounted::new(NoListLoader(None)));
+        let lock = ResLock::load(&resource).await.unwrap();
+        assert!(lock.locked.is_empty());
+
+        let content = r#"{"version":1,"resources":{"https://remote/a.yaml":"sha256-1"}}"#;
+        let resource = Resource::new(project_path, RefCounted::new(NoListLoader(Some(content))));
+        let lock = ResLock::load(&resource).await.unwrap();
+        assert_eq!(lock.locked.len(), 1);
+    }
+}
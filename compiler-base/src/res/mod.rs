@@ -9,9 +9,22 @@ mod path;
 pub use path::*;
 mod res_use;
 pub use res_use::*;
+mod remote_host;
+pub use remote_host::*;
+mod lock;
+pub use lock::*;
+mod vendor;
+pub use vendor::*;
 mod res_type;
 pub use res_type::*;
+mod convert;
+mod list;
 mod resolve;
+pub use list::*;
+#[cfg(not(feature = "wasm"))]
+mod local_loader;
+#[cfg(not(feature = "wasm"))]
+pub use local_loader::*;
 
 pub mod test_utils;
 
@@ -30,23 +43,44 @@ pub enum ResError {
     #[error("Error when parsing YAML resource `{0}`: {1}")]
     InvalidYaml(String, serde_yaml::Error),
 
+    #[error("Error when parsing TOML resource `{0}`: {1}")]
+    InvalidToml(String, toml::de::Error),
+
+    #[error("Error when parsing CSV resource `{0}`: {1}")]
+    InvalidCsv(String, csv::Error),
+
     #[error("Cannot determine the data format for `{0}`.")]
     UnknownDataFormat(String),
 
     #[error("Cannot determine the image format for `{0}`.")]
     UnknownImageFormat(String),
 
+    #[error("Unknown remote host `{0}`.")]
+    UnknownRemoteHost(String),
+
     #[error("Cannot resolve resource `{1}` from `{0}`.")]
     CannotResolve(String, String),
 
+    #[error("Resource `{0}` does not exist.")]
+    NotFound(String),
+
     #[error("Cannot load file `{0}`: {1}")]
     FailToLoadFile(String, String),
 
     #[error("Cannot load url `{0}`: {1}")]
     FailToLoadUrl(String, String),
 
+    #[error("Cannot list directory `{0}`: {1}")]
+    FailToListDir(String, String),
+
     #[error("Failed to create resource: {0}")]
     Create(String),
+
+    #[error("The lock file is invalid: {0}")]
+    InvalidLockFile(String),
+
+    #[error("Resource `{0}` has changed since it was locked (expected {1}, got {2}). If the change is intended, update the lock file.")]
+    LockMismatch(String, String, String),
 }
 
 impl PartialEq for ResError {
@@ -55,9 +89,18 @@ impl PartialEq for ResError {
             (Self::InvalidUtf8(a), Self::InvalidUtf8(b)) => a == b,
             (Self::InvalidJson(a, _), Self::InvalidJson(b, _)) => a == b,
             (Self::InvalidYaml(a, _), Self::InvalidYaml(b, _)) => a == b,
+            (Self::InvalidToml(a, _), Self::InvalidToml(b, _)) => a == b,
+            (Self::InvalidCsv(a, _), Self::InvalidCsv(b, _)) => a == b,
             (Self::UnknownDataFormat(a), Self::UnknownDataFormat(b)) => a == b,
             (Self::UnknownImageFormat(a), Self::UnknownImageFormat(b)) => a == b,
             (Self::CannotResolve(a, b), Self::CannotResolve(c, d)) => a == c && b == d,
+            (Self::UnknownRemoteHost(a), Self::UnknownRemoteHost(b)) => a == b,
+            (Self::InvalidLockFile(a), Self::InvalidLockFile(b)) => a == b,
+            (Self::NotFound(a), Self::NotFound(b)) => a == b,
+            (Self::FailToListDir(a, _), Self::FailToListDir(b, _)) => a == b,
+            (Self::LockMismatch(a, b, c), Self::LockMismatch(d, e, f)) => {
+                a == d && b == e && c == f
+            }
             _ => false,
         }
     }
@@ -70,6 +113,22 @@ pub type ResResult<T> = Result<T, ResError>;
 pub trait Loader {
     /// Load a resource as raw bytes
     async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>>;
+
+    /// If remote images should be loaded and embedded as data URLs, instead of
+    /// being referenced by the URL. This is needed when loading images from the vendor directory
+    fn embed_remote_images(&self) -> bool {
+        false
+    }
+
+    /// List the entries in a directory. Names of sub-directories end with `/`
+    ///
+    /// Not all loaders can list directories (for example, remote resources cannot be listed)
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        Err(ResError::FailToListDir(
+            path.to_string(),
+            "Listing directories is not supported".to_string(),
+        ))
+    }
 }
 
 /// Factory for creating new loaders
@@ -89,6 +148,8 @@ where
 {
     pub path: ResPath<'a>,
     pub loader: RefCounted<L>,
+    /// Lock for checking the content of remote resources
+    pub lock: Option<RefCounted<ResLock>>,
 }
 
 impl<'a, L> Resource<'a, L>
@@ -97,14 +158,25 @@ where
 {
     /// Create a new resource
     pub fn new(path: ResPath<'a>, loader: RefCounted<L>) -> Self {
-        Self { path, loader }
+        Self {
+            path,
+            loader,
+            lock: None,
+        }
+    }
+
+    /// Set the lock used to check the remote resources loaded from this resource
+    pub fn with_lock(mut self, lock: RefCounted<ResLock>) -> Self {
+        self.lock = Some(lock);
+        self
     }
 
-    /// Create a new resource with the same loader as `self` but with a different path
+    /// Create a new resource with the same loader and lock as `self` but with a different path
     pub fn with_path(&self, path: ResPath<'a>) -> Self {
         Self {
             path,
             loader: RefCounted::clone(&self.loader),
+            lock: self.lock.as_ref().map(RefCounted::clone),
         }
     }
 
@@ -113,24 +185,32 @@ where
     }
 
     /// Load the resource as raw bytes
+    ///
+    /// If the resource has a lock, the content is checked against the lock
     pub async fn load_raw(&self) -> ResResult<RefCounted<[u8]>> {
-        self.loader.load_raw(&self.path).await
+        let bytes = self.loader.load_raw(&self.path).await?;
+        if let Some(lock) = &self.lock {
+            lock.check(&self.path, &bytes)?;
+        }
+        Ok(bytes)
     }
 
     /// Load the resource as UTF-8 string
     pub async fn load_utf8(&self) -> ResResult<String> {
-        let bytes = self.loader.load_raw(&self.path).await?;
+        let bytes = self.load_raw().await?;
         match std::str::from_utf8(&bytes) {
             Ok(v) => Ok(v.to_string()),
             Err(_) => Err(ResError::InvalidUtf8(self.path.to_string())),
         }
     }
 
-    /// Load the resource as structured value for supported formats (JSON, YAML)
+    /// Load the resource as structured value for supported formats (JSON, YAML, TOML, CSV)
+    ///
+    /// CSV is loaded as an array of objects, see [`ResType::Csv`]
     pub async fn load_structured(&self) -> ResResult<Value> {
         match self.path.get_type() {
             Some(ResType::Yaml) => {
-                let bytes = self.loader.load_raw(&self.path).await?;
+                let bytes = self.load_raw().await?;
                 if std::str::from_utf8(&bytes).is_err() {
                     return Err(ResError::InvalidUtf8(self.path.to_string()));
                 }
@@ -140,7 +220,7 @@ where
                 }
             }
             Some(ResType::Json) => {
-                let bytes = self.loader.load_raw(&self.path).await?;
+                let bytes = self.load_raw().await?;
                 if std::str::from_utf8(&bytes).is_err() {
                     return Err(ResError::InvalidUtf8(self.path.to_string()));
                 }
@@ -149,23 +229,45 @@ where
                     Err(e) => Err(ResError::InvalidJson(self.path.to_string(), e)),
                 }
             }
+            Some(ResType::Toml) => {
+                let text = self.load_utf8().await?;
+                match toml::from_str(&text) {
+                    Ok(v) => Ok(convert::toml_to_json(v)),
+                    Err(e) => Err(ResError::InvalidToml(self.path.to_string(), e)),
+                }
+            }
+            Some(ResType::Csv) => {
+                let bytes = self.load_raw().await?;
+                if std::str::from_utf8(&bytes).is_err() {
+                    return Err(ResError::InvalidUtf8(self.path.to_string()));
+                }
+                match convert::csv_to_json(&bytes) {
+                    Ok(v) => Ok(v),
+                    Err(e) => Err(ResError::InvalidCsv(self.path.to_string(), e)),
+                }
+            }
             _ => Err(ResError::UnknownDataFormat(self.path.to_string())),
         }
     }
 
     /// Load the image as either a remote URL or a data URL
     pub async fn load_image_url(&self) -> ResResult<String> {
+        let image_type = self.path.get_type();
         if !self.path.is_local() {
-            // if path is a URL, just return it
-            return Ok(self.path.to_string());
+            let embed =
+                self.loader.embed_remote_images() && matches!(image_type, Some(x) if x.is_image());
+            if !embed {
+                // if path is a URL, just return it
+                return Ok(self.path.to_string());
+            }
         }
-        let image_type = self.path.get_type();
-        let media_type = match image_type {
+        // load the bytes
+        let bytes = self.load_raw().await?;
+        // the content takes precedence, in case the extension is missing or wrong
+        let media_type = match ResType::sniff_image(&bytes).or(image_type) {
             Some(x) if x.is_image() => x.media_type(),
             _ => return Err(ResError::UnknownImageFormat(self.path.to_string())),
         };
-        // load the bytes
-        let bytes = self.loader.load_raw(&self.path).await?;
         // encode the bytes as a data url
         let data_url = util::to_data_url_base64(media_type, &bytes);
 
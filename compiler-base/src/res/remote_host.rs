@@ -0,0 +1,315 @@
+//! Hosts for loading remote resources
+//!
+//! A remote `use` like `owner/repo/path:ref` is loaded from a git host. The host
+//! can be specified with a prefix like `gitlab:owner/repo/path:ref`, and is looked up
+//! in a [`RemoteHostRegistry`] to build the URL for the raw files in the repo.
+
+use std::collections::BTreeMap;
+
+use crate::macros::late_global;
+
+/// Name of the host used when the remote `use` does not specify one
+pub const DEFAULT_REMOTE_HOST: &str = "github";
+
+/// Reference used when the remote `use` does not specify one
+pub const DEFAULT_REMOTE_REF: &str = "main";
+
+/// A git host to load remote resources from
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteHost {
+    /// Template for the base URL of the raw files in a repo.
+    ///
+    /// `{owner}`, `{repo}` and `{ref}` are replaced with the corresponding parts
+    /// of the remote `use`
+    url_template: String,
+}
+
+impl RemoteHost {
+    pub fn new<S: Into<String>>(url_template: S) -> Self {
+        let mut url_template = url_template.into();
+        if !url_template.ends_with('/') {
+            url_template.push('/');
+        }
+        Self { url_template }
+    }
+
+    /// Build the base URL for the repo at the reference
+    pub fn base_url(&self, owner: &str, repo: &str, reference: Option<&str>) -> String {
+        let reference = reference.unwrap_or(DEFAULT_REMOTE_REF);
+        self.url_template
+            .replace("{owner}", owner)
+            .replace("{repo}", repo)
+            .replace("{ref}", reference)
+    }
+
+    /// Get the owner and repo from a URL of a file on the host, by matching it
+    /// against the URL template. Returns `None` if the URL is not on the host, or the template
+    /// does not have both `{owner}` and `{repo}`
+    pub fn match_url(&self, url: &str) -> Option<(String, String)> {
+        let mut owner = None;
+        let mut repo = None;
+        let mut rest = url;
+        let mut template = self.url_template.as_str();
+        while !template.is_empty() {
+            let (literal, var) = match template.find('{') {
+                Some(i) => (&template[..i], &template[i..]),
+                None => (template, ""),
+            };
+            rest = rest.strip_prefix(literal)?;
+            if var.is_empty() {
+                break;
+            }
+            let end = var.find('}')?;
+            let name = &var[1..end];
+            template = &var[end + 1..];
+            // the next literal is needed to know where the value ends
+            let next_literal = match template.find('{') {
+                Some(i) => &template[..i],
+                None => template,
+            };
+            let value_end = if next_literal.is_empty() {
+                rest.len()
+            } else {
+                rest.find(next_literal)?
+            };
+            let value = &rest[..value_end];
+            if value.is_empty() || value.contains('/') {
+                return None;
+            }
+            match name {
+                "owner" => owner = Some(value.to_string()),
+                "repo" => repo = Some(value.to_string()),
+                _ => {}
+            }
+            rest = &rest[value_end..];
+        }
+        Some((owner?, repo?))
+    }
+}
+
+/// Registry of remote hosts by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteHostRegistry {
+    hosts: BTreeMap<String, RemoteHost>,
+}
+
+impl Default for RemoteHostRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(
+            DEFAULT_REMOTE_HOST,
+            RemoteHost::new("https://raw.githubusercontent.com/{owner}/{repo}/{ref}/"),
+        );
+        registry.register(
+            "gitlab",
+            RemoteHost::new("https://gitlab.com/{owner}/{repo}/-/raw/{ref}/"),
+        );
+        registry.register(
+            "codeberg",
+            RemoteHost::new("https://codeberg.org/{owner}/{repo}/raw/{ref}/"),
+        );
+        registry
+    }
+}
+
+impl RemoteHostRegistry {
+    /// Create a registry without any hosts
+    pub fn empty() -> Self {
+        Self {
+            hosts: BTreeMap::new(),
+        }
+    }
+
+    /// Add a host to the registry, replacing the existing one with the same name
+    pub fn register<S: Into<String>>(&mut self, name: S, host: RemoteHost) {
+        self.hosts.insert(name.into(), host);
+    }
+
+    /// Add hosts from a config string in the format of `name=url_template,name2=url_template2`
+    ///
+    /// Returns the entry that is malformed if the config is invalid
+    pub fn register_from_config(&mut self, config: &str) -> Result<(), String> {
+        for entry in config.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (name, url_template) = match entry.split_once('=') {
+                Some((name, url_template)) => (name.trim(), url_template.trim()),
+                None => return Err(entry.to_string()),
+            };
+            if !is_valid_host_name(name) || url_template.is_empty() {
+                return Err(entry.to_string());
+            }
+            self.register(name, RemoteHost::new(url_template));
+        }
+        Ok(())
+    }
+
+    /// Get a host by name, or the default host if the name is `None`
+    pub fn get(&self, name: Option<&str>) -> Option<&RemoteHost> {
+        self.hosts.get(name.unwrap_or(DEFAULT_REMOTE_HOST))
+    }
+
+    /// Get the name of the host, the owner and the repo from a URL of a file in a repo.
+    /// Returns `None` if the URL does not match any host
+    pub fn match_url(&self, url: &str) -> Option<(&str, String, String)> {
+        self.hosts.iter().find_map(|(name, host)| {
+            host.match_url(url)
+                .map(|(owner, repo)| (name.as_str(), owner, repo))
+        })
+    }
+
+    /// Build the base URL for the repo on the host. Returns `None` if the host is not registered
+    pub fn base_url(
+        &self,
+        host: Option<&str>,
+        owner: &str,
+        repo: &str,
+        reference: Option<&str>,
+    ) -> Option<String> {
+        self.get(host)
+            .map(|host| host.base_url(owner, repo, reference))
+    }
+}
+
+/// Check if the string can be used as the host prefix in a remote `use`
+pub fn is_valid_host_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Split the host prefix (i.e. `host:`) from the owner of a remote repo
+pub fn split_remote_host(owner: &str) -> (Option<&str>, &str) {
+    match owner.split_once(':') {
+        Some((host, owner)) if is_valid_host_name(host) => (Some(host), owner),
+        _ => (None, owner),
+    }
+}
+
+/// Remote hosts global configuration
+#[late_global(RemoteHostRegistry)]
+pub mod remote_hosts {
+    /// Get the registry, initializing it with the default hosts if not set
+    pub fn get_registry() -> RefCounted<RemoteHostRegistry> {
+        if let Some(registry) = get() {
+            return registry;
+        }
+        let _ = set(RefCounted::new(RemoteHostRegistry::default()));
+        get().unwrap_or_default()
+    }
+}
+
+/// Build the base URL for the repo using the global registry.
+/// Returns `None` if the host is not registered
+pub fn base_url(
+    host: Option<&str>,
+    owner: &str,
+    repo: &str,
+    reference: Option<&str>,
+) -> Option<String> {
+    remote_hosts::get_registry().base_url(host, owner, repo, reference)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_hosts() {
+        let registry = RemoteHostRegistry::default();
+        assert_eq!(
+            registry.base_url(None, "foo", "bar", None).unwrap(),
+            "https://raw.githubusercontent.com/foo/bar/main/"
+        );
+        assert_eq!(
+            registry
+                .base_url(Some("gitlab"), "foo", "bar", Some("v1"))
+                .unwrap(),
+            "https://gitlab.com/foo/bar/-/raw/v1/"
+        );
+        assert_eq!(
+            registry
+                .base_url(Some("codeberg"), "foo", "bar", None)
+                .unwrap(),
+            "https://codeberg.org/foo/bar/raw/main/"
+        );
+        assert_eq!(registry.base_url(Some("gitea"), "foo", "bar", None), None);
+    }
+
+    #[test]
+    fn test_register_from_config() {
+        let mut registry = RemoteHostRegistry::default();
+        registry
+            .register_from_config(
+                "gitea=https://git.example.com/{owner}/{repo}/raw/{ref}, github=https://gh/{repo}",
+            )
+            .unwrap();
+        assert_eq!(
+            registry
+                .base_url(Some("gitea"), "foo", "bar", Some("dev"))
+                .unwrap(),
+            "https://git.example.com/foo/bar/raw/dev/"
+        );
+        assert_eq!(
+            registry.base_url(None, "foo", "bar", None).unwrap(),
+            "https://gh/bar/"
+        );
+
+        assert_eq!(
+            registry.register_from_config("gitea"),
+            Err("gitea".to_string())
+        );
+        assert_eq!(
+            registry.register_from_config("a/b=https://x"),
+            Err("a/b=https://x".to_string())
+        );
+        assert_eq!(registry.register_from_config("x="), Err("x=".to_string()));
+    }
+
+    #[test]
+    fn test_match_url() {
+        let mut registry = RemoteHostRegistry::default();
+        assert_eq!(
+            registry.match_url("https://raw.githubusercontent.com/foo/bar/main/a/b.yaml"),
+            Some(("github", "foo".to_string(), "bar".to_string()))
+        );
+        assert_eq!(
+            registry.match_url("https://gitlab.com/foo/bar/-/raw/v1/a.yaml"),
+            Some(("gitlab", "foo".to_string(), "bar".to_string()))
+        );
+        assert_eq!(
+            registry.match_url("https://raw.githubusercontent.com/foo/bar"),
+            None
+        );
+        assert_eq!(
+            registry.match_url("https://raw.githubusercontent.com//bar/main/a.yaml"),
+            None
+        );
+        assert_eq!(
+            registry.match_url("https://example.com/foo/bar/main/a"),
+            None
+        );
+
+        registry
+            .register_from_config(
+                "gitea=https://git.example.com/{owner}-{repo}/{ref}, x=https://x/{repo}",
+            )
+            .unwrap();
+        assert_eq!(
+            registry.match_url("https://git.example.com/foo-bar/dev/a.yaml"),
+            Some(("gitea", "foo".to_string(), "bar".to_string()))
+        );
+        // the template does not have the owner
+        assert_eq!(registry.match_url("https://x/bar/a.yaml"), None);
+    }
+
+    #[test]
+    fn test_split_remote_host() {
+        assert_eq!(split_remote_host("foo"), (None, "foo"));
+        assert_eq!(split_remote_host("gitlab:foo"), (Some("gitlab"), "foo"));
+        assert_eq!(split_remote_host(":foo"), (None, ":foo"));
+    }
+}
@@ -2,13 +2,21 @@
 
 use super::ResPath;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ResType {
     Yaml,
     Json,
+    Toml,
+    /// Table with a header row, loaded as an array of objects keyed by the header.
+    /// All values are strings
+    Csv,
     Png,
     Jpeg,
     Gif,
     Webp,
+    Svg,
+    Avif,
+    Bmp,
 }
 
 impl ResType {
@@ -17,17 +25,76 @@ impl ResType {
         match self {
             Self::Yaml => "application/x-yaml", // unofficial
             Self::Json => "application/json",
+            Self::Toml => "application/toml",
+            Self::Csv => "text/csv",
             Self::Png => "image/png",
             Self::Jpeg => "image/jpeg",
             Self::Gif => "image/gif",
             Self::Webp => "image/webp",
+            Self::Svg => "image/svg+xml",
+            Self::Avif => "image/avif",
+            Self::Bmp => "image/bmp",
         }
     }
 
     /// Return if the resource is an image
     pub fn is_image(&self) -> bool {
-        matches!(self, Self::Png | Self::Jpeg | Self::Gif | Self::Webp)
+        matches!(
+            self,
+            Self::Png | Self::Jpeg | Self::Gif | Self::Webp | Self::Svg | Self::Avif | Self::Bmp
+        )
     }
+
+    /// Detect the image type from the content (magic bytes)
+    ///
+    /// This is used when the extension is missing or does not match the content
+    pub fn sniff_image(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Some(Self::Png);
+        }
+        if bytes.starts_with(b"\xff\xd8\xff") {
+            return Some(Self::Jpeg);
+        }
+        if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            return Some(Self::Gif);
+        }
+        if bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP" {
+            return Some(Self::Webp);
+        }
+        // ISO BMFF, the major brand is after the box size
+        if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+            let brand = &bytes[8..12];
+            if brand == b"avif" || brand == b"avis" {
+                return Some(Self::Avif);
+            }
+        }
+        if bytes.starts_with(b"BM") && bytes.len() >= 14 {
+            return Some(Self::Bmp);
+        }
+        if is_svg(bytes) {
+            return Some(Self::Svg);
+        }
+        None
+    }
+}
+
+/// Check if the content looks like an SVG document, by looking for the `<svg`
+/// tag near the start, after the optional XML declaration, comments and doctype
+fn is_svg(bytes: &[u8]) -> bool {
+    let head = &bytes[..bytes.len().min(1024)];
+    let head = match std::str::from_utf8(head) {
+        Ok(head) => head,
+        // the cut could be in the middle of a character
+        Err(e) => match std::str::from_utf8(&head[..e.valid_up_to()]) {
+            Ok(head) => head,
+            Err(_) => return false,
+        },
+    };
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    if !head.starts_with('<') {
+        return false;
+    }
+    head.contains("<svg")
 }
 
 impl<'a> ResPath<'a> {
@@ -43,6 +110,12 @@ impl<'a> ResPath<'a> {
         if ext.eq_ignore_ascii_case("json") {
             return Some(ResType::Json);
         }
+        if ext.eq_ignore_ascii_case("toml") {
+            return Some(ResType::Toml);
+        }
+        if ext.eq_ignore_ascii_case("csv") {
+            return Some(ResType::Csv);
+        }
         if ext.eq_ignore_ascii_case("png") {
             return Some(ResType::Png);
         }
@@ -55,6 +128,70 @@ impl<'a> ResPath<'a> {
         if ext.eq_ignore_ascii_case("webp") {
             return Some(ResType::Webp);
         }
+        if ext.eq_ignore_ascii_case("svg") {
+            return Some(ResType::Svg);
+        }
+        if ext.eq_ignore_ascii_case("avif") {
+            return Some(ResType::Avif);
+        }
+        if ext.eq_ignore_ascii_case("bmp") {
+            return Some(ResType::Bmp);
+        }
         None
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_type_images() {
+        let path = ResPath::new_local_unchecked("icons/shrine.svg");
+        assert_eq!(path.get_type(), Some(ResType::Svg));
+        let path = ResPath::new_remote_unchecked("https://example.com/", "a.AVIF");
+        assert_eq!(path.get_type(), Some(ResType::Avif));
+        let path = ResPath::new_local_unchecked("a.bmp");
+        assert_eq!(path.get_type(), Some(ResType::Bmp));
+        let path = ResPath::new_local_unchecked("a");
+        assert_eq!(path.get_type(), None);
+    }
+
+    #[test]
+    fn test_sniff_image() {
+        assert_eq!(
+            ResType::sniff_image(b"\x89PNG\r\n\x1a\n0000"),
+            Some(ResType::Png)
+        );
+        assert_eq!(
+            ResType::sniff_image(b"\xff\xd8\xff\xe0"),
+            Some(ResType::Jpeg)
+        );
+        assert_eq!(ResType::sniff_image(b"GIF89a"), Some(ResType::Gif));
+        assert_eq!(
+            ResType::sniff_image(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ResType::Webp)
+        );
+        assert_eq!(
+            ResType::sniff_image(b"\0\0\0\x1cftypavif\0\0\0\0"),
+            Some(ResType::Avif)
+        );
+        assert_eq!(
+            ResType::sniff_image(b"BM\0\0\0\0\0\0\0\0\0\0\0\0"),
+            Some(ResType::Bmp)
+        );
+        assert_eq!(
+            ResType::sniff_image(b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>"),
+            Some(ResType::Svg)
+        );
+        assert_eq!(
+            ResType::sniff_image(
+                b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<!-- icon -->\n<svg></svg>"
+            ),
+            Some(ResType::Svg)
+        );
+        assert_eq!(ResType::sniff_image(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(ResType::sniff_image(b"hello <svg"), None);
+        assert_eq!(ResType::sniff_image(b""), None);
+    }
+}
@@ -8,6 +8,8 @@ use serde_json::Value;
 use crate::json::Coerce;
 use crate::prop;
 
+use super::{base_url, is_valid_host_name};
+
 /// Result of parsing an object which could be loading a resource with
 /// the `use` property
 #[derive(Debug, PartialEq, Clone)]
@@ -22,11 +24,18 @@ pub enum Use {
 #[derive(Debug, PartialEq, Clone)]
 pub enum ValidUse {
     /// Loading a resource using relative path. The string must start with `./` or `../`
+    ///
+    /// The path can also be a directory (ending with `/`) or a glob (`*` in the file name),
+    /// see [`ValidUse::is_list`]
     Relative(String),
     /// Loading a resource using absolute path. The string must start with `/`
+    ///
+    /// The path can also be a directory or a glob, same as [`ValidUse::Relative`]
     Absolute(String),
     /// Loading a resource using remote path
     Remote {
+        /// Name of the remote host in the registry. `None` for the default host
+        host: Option<String>,
         owner: String,
         repo: String,
         path: String,
@@ -49,13 +58,15 @@ impl Display for ValidUse {
             ValidUse::Relative(v) => write!(f, "{}", v),
             ValidUse::Absolute(v) => write!(f, "{}", v),
             ValidUse::Remote {
+                host,
                 owner,
                 repo,
                 path,
                 reference,
             } => {
-                let reference = reference.as_ref().map(|s| s.as_str());
-                let v = format_remote_use(owner, repo, path, reference);
+                let host = host.as_deref();
+                let reference = reference.as_deref();
+                let v = format_remote_use(host, owner, repo, path, reference);
                 write!(f, "{}", v)
             }
         }
@@ -72,19 +83,31 @@ impl Use {
     {
         let v = v.into();
         if v.starts_with('/') {
-            if v.ends_with('/') {
+            if v == "/" || !is_valid_local_glob(&v) {
                 Self::Invalid(v.into_owned())
             } else {
                 Self::Valid(ValidUse::Absolute(v.into_owned()))
             }
         } else if v.starts_with("./") || v.starts_with("../") {
-            if v.ends_with('/') {
+            if v.split('/').all(|x| x.is_empty() || x == "." || x == "..")
+                || !is_valid_local_glob(&v)
+            {
+                // directory use must not be the current or parent directory itself
                 Self::Invalid(v.into_owned())
             } else {
                 Self::Valid(ValidUse::Relative(v.into_owned()))
             }
         } else {
-            let mut reference_split = v.splitn(2, ':');
+            let (host, rest) = match v.split_once(':') {
+                Some((host, rest)) if !host.contains('/') => {
+                    if !is_valid_host_name(host) {
+                        return Self::Invalid(v.into_owned());
+                    }
+                    (Some(host), rest)
+                }
+                _ => (None, v.as_ref()),
+            };
+            let mut reference_split = rest.splitn(2, ':');
             // unwrap is safe because we know there is at least one element
             let path = reference_split.next().unwrap();
             if path.ends_with('/') {
@@ -106,6 +129,7 @@ impl Use {
                 None => return Self::Invalid(v.into_owned()),
             };
             Self::Valid(ValidUse::Remote {
+                host: host.map(|s| s.to_string()),
                 owner: owner.to_string(),
                 repo: repo.to_string(),
                 path: path.to_string(),
@@ -144,34 +168,68 @@ impl ValidUse {
         }
     }
 
+    /// If the `use` refers to a directory, i.e. the path ends with `/`
+    pub fn is_dir(&self) -> bool {
+        self.path().ends_with('/')
+    }
+
+    /// If the `use` is a glob, i.e. the file name has a `*`
+    pub fn is_glob(&self) -> bool {
+        let path = self.path();
+        let file_name = match path.rfind('/') {
+            Some(i) => &path[i + 1..],
+            None => path,
+        };
+        file_name.contains('*')
+    }
+
+    /// If the `use` refers to a list of resources (a directory or a glob), which needs to be
+    /// resolved with [`Resource::resolve_list`](super::Resource::resolve_list)
+    pub fn is_list(&self) -> bool {
+        self.is_dir() || self.is_glob()
+    }
+
     /// Return the base URL if the variant is a Remote
+    ///
+    /// Returns `None` if the remote host is not registered
     pub fn base_url(&self) -> Option<String> {
         match self {
             ValidUse::Relative(_) => None,
             ValidUse::Absolute(_) => None,
             ValidUse::Remote {
+                host,
                 owner,
                 repo,
                 reference,
                 ..
-            } => {
-                let reference = reference.as_ref().map(|s| s.as_str());
-                Some(base_url(owner, repo, reference))
-            }
+            } => base_url(host.as_deref(), owner, repo, reference.as_deref()),
         }
     }
 }
 
-pub fn format_remote_use(owner: &str, repo: &str, path: &str, reference: Option<&str>) -> String {
-    match reference {
-        Some(reference) => format!("{owner}/{repo}/{path}:{reference}"),
-        None => format!("{owner}/{repo}/{path}"),
+/// Only the file name of a local path can have `*`
+fn is_valid_local_glob(path: &str) -> bool {
+    match path.rfind('/') {
+        Some(i) => !path[..i].contains('*'),
+        None => true,
     }
 }
 
-pub fn base_url(owner: &str, repo: &str, reference: Option<&str>) -> String {
-    let branch = reference.unwrap_or("main");
-    format!("https://raw.githubusercontent.com/{owner}/{repo}/{branch}/")
+pub fn format_remote_use(
+    host: Option<&str>,
+    owner: &str,
+    repo: &str,
+    path: &str,
+    reference: Option<&str>,
+) -> String {
+    let host = match host {
+        Some(host) => format!("{host}:"),
+        None => String::new(),
+    };
+    match reference {
+        Some(reference) => format!("{host}{owner}/{repo}/{path}:{reference}"),
+        None => format!("{host}{owner}/{repo}/{path}"),
+    }
 }
 
 #[cfg(test)]
@@ -260,6 +318,7 @@ mod test {
                 "use": "foo/hello/bar"
             })),
             Some(Use::Valid(ValidUse::Remote {
+                host: None,
                 owner: "foo".to_string(),
                 repo: "hello".to_string(),
                 path: "bar".to_string(),
@@ -271,6 +330,7 @@ mod test {
                 "use": "foo/hello/bar:test"
             })),
             Some(Use::Valid(ValidUse::Remote {
+                host: None,
                 owner: "foo".to_string(),
                 repo: "hello".to_string(),
                 path: "bar".to_string(),
@@ -282,6 +342,7 @@ mod test {
                 "use": ".foo/hello/bar/giz"
             })),
             Some(Use::Valid(ValidUse::Remote {
+                host: None,
                 owner: ".foo".to_string(),
                 repo: "hello".to_string(),
                 path: "bar/giz".to_string(),
@@ -293,6 +354,7 @@ mod test {
                 "use": "foo/hello/bar/giz/biz:test"
             })),
             Some(Use::Valid(ValidUse::Remote {
+                host: None,
                 owner: "foo".to_string(),
                 repo: "hello".to_string(),
                 path: "bar/giz/biz".to_string(),
@@ -304,6 +366,7 @@ mod test {
                 "use": "foo/hello/bar/giz/biz:"
             })),
             Some(Use::Valid(ValidUse::Remote {
+                host: None,
                 owner: "foo".to_string(),
                 repo: "hello".to_string(),
                 path: "bar/giz/biz".to_string(),
@@ -312,6 +375,74 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_use_remote_host() {
+        assert_eq!(
+            Use::from_value(&json!({
+                "use": "gitlab:foo/hello/bar/giz:test"
+            })),
+            Some(Use::Valid(ValidUse::Remote {
+                host: Some("gitlab".to_string()),
+                owner: "foo".to_string(),
+                repo: "hello".to_string(),
+                path: "bar/giz".to_string(),
+                reference: Some("test".to_string()),
+            }))
+        );
+        let v = Use::new("gitea:foo/hello/bar");
+        assert_eq!(
+            v,
+            Use::Valid(ValidUse::Remote {
+                host: Some("gitea".to_string()),
+                owner: "foo".to_string(),
+                repo: "hello".to_string(),
+                path: "bar".to_string(),
+                reference: None,
+            })
+        );
+        assert_eq!(v.to_string(), "gitea:foo/hello/bar");
+        let v = Use::new("codeberg:foo/hello/bar:v1");
+        assert_eq!(v.to_string(), "codeberg:foo/hello/bar:v1");
+        match v {
+            Use::Valid(v) => assert_eq!(
+                v.base_url().unwrap(),
+                "https://codeberg.org/foo/hello/raw/v1/"
+            ),
+            _ => panic!("expected valid use"),
+        }
+    }
+
+    #[test]
+    fn test_use_list() {
+        let v = Use::new("./foo/hello/");
+        assert_eq!(
+            v,
+            Use::Valid(ValidUse::Relative("./foo/hello/".to_string()))
+        );
+        match v {
+            Use::Valid(v) => {
+                assert!(v.is_dir());
+                assert!(!v.is_glob());
+                assert!(v.is_list());
+            }
+            _ => panic!("expected valid use"),
+        }
+        let v = Use::new("/foo/*.yaml");
+        assert_eq!(v, Use::Valid(ValidUse::Absolute("/foo/*.yaml".to_string())));
+        match v {
+            Use::Valid(v) => {
+                assert!(!v.is_dir());
+                assert!(v.is_glob());
+                assert!(v.is_list());
+            }
+            _ => panic!("expected valid use"),
+        }
+        match Use::new("../foo/bar.yaml") {
+            Use::Valid(v) => assert!(!v.is_list()),
+            _ => panic!("expected valid use"),
+        }
+    }
+
     fn make_use(s: &str) -> Value {
         json!({
         "use": s
@@ -328,9 +459,13 @@ mod test {
             ".../hello",
             "foo/hello",
             "foo/hello/",
-            "./foo/hello/",
-            "../foo/hello/",
+            "./.././",
+            "./*/hello",
+            "/foo*/hello/",
             "foo/hello/path/",
+            "gitlab:foo/hello",
+            "git lab:foo/hello/path",
+            ":foo/hello/path",
         ];
 
         for test in tests {
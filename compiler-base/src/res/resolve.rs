@@ -127,6 +127,7 @@ mod test {
     fn test_local_remote() {
         let resource = create_local_resource("foo");
         let target = ValidUse::Remote {
+            host: None,
             owner: "owner".into(),
             repo: "repo".into(),
             path: "bar".into(),
@@ -140,6 +141,7 @@ mod test {
 
         let resource = create_local_resource("foo/a");
         let target = ValidUse::Remote {
+            host: None,
             owner: "owner".into(),
             repo: "repo".into(),
             path: "bar/b".into(),
@@ -152,6 +154,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_remote_unknown_host() {
+        let resource = create_local_resource("foo");
+        let target = ValidUse::Remote {
+            host: Some("unknown".into()),
+            owner: "owner".into(),
+            repo: "repo".into(),
+            path: "bar".into(),
+            reference: None,
+        };
+        let result = resource.resolve(&target);
+        assert_eq!(
+            result.unwrap_err(),
+            ResError::CannotResolve("foo".into(), "unknown:owner/repo/bar".into())
+        );
+    }
+
     #[test]
     fn test_remote_relative_error() {
         let resource = create_remote_resource("foo");
@@ -223,6 +242,7 @@ mod test {
     fn test_remote_remote() {
         let resource = create_remote_resource("foo");
         let target = ValidUse::Remote {
+            host: None,
             owner: "owner".into(),
             repo: "repo".into(),
             path: "bar".into(),
@@ -236,6 +256,7 @@ mod test {
 
         let resource = create_remote_resource("foo/a");
         let target = ValidUse::Remote {
+            host: None,
             owner: "owner".into(),
             repo: "repo".into(),
             path: "bar/b".into(),
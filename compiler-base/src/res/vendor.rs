@@ -0,0 +1,266 @@
+//! Vendoring remote resources for offline compilation
+//!
+//! Remote resources can be downloaded into the `vendor` directory at the root of the
+//! project. The remote URL `https://host/path/to/file` is stored at
+//! `vendor/host/path/to/file`. [`VendorLoader`] serves remote resources from the
+//! vendor directory first if the project has one, so the project can be compiled without
+//! internet access.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::env::RefCounted;
+use crate::macros::async_trait;
+
+use super::{Loader, ResPath, ResResult};
+
+/// Name of the directory at the root of the project to store the vendored resources
+pub const VENDOR_DIR: &str = "vendor";
+
+/// Get the local path in the vendor directory for a remote resource
+///
+/// Returns `None` if the path is local or is not a http(s) URL (e.g. a data URL)
+pub fn vendor_path(path: &ResPath) -> Option<ResPath<'static>> {
+    if path.is_local() {
+        return None;
+    }
+    let url = path.to_string();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    ResPath::new_local(format!("{VENDOR_DIR}/{rest}"))
+}
+
+/// Check if the root of the project has the vendor directory
+pub async fn has_vendor_dir<L>(loader: &L) -> bool
+where
+    L: Loader,
+{
+    let root = ResPath::new_local_unchecked("");
+    match loader.list_dir(&root).await {
+        // names of directories end with `/`
+        Ok(entries) => entries
+            .iter()
+            .any(|x| x.strip_suffix('/') == Some(VENDOR_DIR)),
+        Err(_) => false,
+    }
+}
+
+/// Loader wrapper that loads remote resources from the vendor directory first,
+/// and only loads from the URL if the resource is not vendored
+///
+/// When vendoring is disabled, everything is loaded from the inner loader
+#[derive(Debug)]
+pub struct VendorLoader<L>
+where
+    L: Loader,
+{
+    inner: RefCounted<L>,
+    enabled: bool,
+}
+
+impl<L> VendorLoader<L>
+where
+    L: Loader,
+{
+    pub fn new(inner: RefCounted<L>, enabled: bool) -> Self {
+        Self { inner, enabled }
+    }
+
+    /// Create the loader with vendoring enabled only if the project has the vendor directory
+    pub async fn detect(inner: RefCounted<L>) -> Self {
+        let enabled = has_vendor_dir(inner.as_ref()).await;
+        Self::new(inner, enabled)
+    }
+
+    /// If remote resources are loaded from the vendor directory
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[async_trait(auto)]
+impl<L> Loader for VendorLoader<L>
+where
+    L: Loader,
+{
+    async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+        if !self.enabled {
+            return self.inner.load_raw(path).await;
+        }
+        if let Some(local_path) = vendor_path(path) {
+            if let Ok(bytes) = self.inner.load_raw(&local_path).await {
+                return Ok(bytes);
+            }
+        }
+        self.inner.load_raw(path).await
+    }
+
+    fn embed_remote_images(&self) -> bool {
+        // images are only embedded so the vendored ones can be displayed offline
+        self.enabled
+    }
+
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        self.inner.list_dir(path).await
+    }
+}
+
+/// Loader wrapper that records every remote resource loaded, so they can be
+/// written to the vendor directory
+#[derive(Debug)]
+pub struct VendorRecorder<L>
+where
+    L: Loader,
+{
+    inner: RefCounted<L>,
+    resources: Mutex<BTreeMap<String, RefCounted<[u8]>>>,
+}
+
+impl<L> VendorRecorder<L>
+where
+    L: Loader,
+{
+    pub fn new(inner: RefCounted<L>) -> Self {
+        Self {
+            inner,
+            resources: Default::default(),
+        }
+    }
+
+    /// Take the recorded resources, keyed by the path in the vendor directory
+    pub fn take_resources(&self) -> BTreeMap<String, RefCounted<[u8]>> {
+        match self.resources.lock() {
+            Ok(mut resources) => std::mem::take(&mut *resources),
+            Err(_) => Default::default(),
+        }
+    }
+}
+
+#[async_trait(auto)]
+impl<L> Loader for VendorRecorder<L>
+where
+    L: Loader,
+{
+    async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+        let bytes = self.inner.load_raw(path).await?;
+        if let Some(local_path) = vendor_path(path) {
+            if let Ok(mut resources) = self.resources.lock() {
+                resources.insert(local_path.to_string(), RefCounted::clone(&bytes));
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn embed_remote_images(&self) -> bool {
+        true
+    }
+
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        self.inner.list_dir(path).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::res::ResError;
+
+    /// Loader that only has the vendored file and one remote file
+    struct TestLoader;
+    #[async_trait(auto)]
+    impl Loader for TestLoader {
+        async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+            match path.to_string().as_str() {
+                "" => Ok(vec!["project.yaml".to_string(), "vendor/".to_string()]),
+                _ => Err(ResError::FailToListDir(path.to_string(), "404".to_string())),
+            }
+        }
+
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            match path.to_string().as_str() {
+                "vendor/remote/vendored.yaml" => Ok(RefCounted::from(b"vendored".as_slice())),
+                "https://remote/online.yaml" => Ok(RefCounted::from(b"online".as_slice())),
+                _ => Err(ResError::FailToLoadFile(
+                    path.to_string(),
+                    "404".to_string(),
+                )),
+            }
+        }
+    }
+
+    #[test]
+    fn test_vendor_path() {
+        let path = ResPath::new_remote_unchecked("https://remote/", "a/b.yaml");
+        assert_eq!(
+            vendor_path(&path),
+            Some(ResPath::new_local_unchecked("vendor/remote/a/b.yaml"))
+        );
+        let path = ResPath::new_remote_unchecked("http://remote/x/", "../b.yaml");
+        assert_eq!(
+            vendor_path(&path),
+            Some(ResPath::new_local_unchecked("vendor/remote/b.yaml"))
+        );
+        let path = ResPath::new_remote_unchecked("", "data:image/png;base64,AAAA");
+        assert_eq!(vendor_path(&path), None);
+        let path = ResPath::new_local_unchecked("a/b.yaml");
+        assert_eq!(vendor_path(&path), None);
+    }
+
+    /// Loader without the vendor directory
+    struct NoVendorLoader;
+    #[async_trait(auto)]
+    impl Loader for NoVendorLoader {
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            TestLoader.load_raw(path).await
+        }
+
+        async fn list_dir(&self, _: &ResPath) -> ResResult<Vec<String>> {
+            Ok(vec!["project.yaml".to_string()])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vendor_loader() {
+        let loader = VendorLoader::detect(RefCounted::new(TestLoader)).await;
+        assert!(loader.is_enabled());
+        assert!(loader.embed_remote_images());
+        let path = ResPath::new_remote_unchecked("https://remote/", "vendored.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"vendored");
+        let path = ResPath::new_remote_unchecked("https://remote/", "online.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"online");
+        let path = ResPath::new_remote_unchecked("https://remote/", "missing.yaml");
+        assert!(loader.load_raw(&path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_vendor_loader_disabled() {
+        let loader = VendorLoader::detect(RefCounted::new(NoVendorLoader)).await;
+        assert!(!loader.is_enabled());
+        assert!(!loader.embed_remote_images());
+        let path = ResPath::new_remote_unchecked("https://remote/", "vendored.yaml");
+        assert!(loader.load_raw(&path).await.is_err());
+        let path = ResPath::new_remote_unchecked("https://remote/", "online.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"online");
+
+        // vendoring can also be enabled explicitly
+        let loader = VendorLoader::new(RefCounted::new(NoVendorLoader), true);
+        let path = ResPath::new_remote_unchecked("https://remote/", "vendored.yaml");
+        assert_eq!(&*loader.load_raw(&path).await.unwrap(), b"vendored");
+    }
+
+    #[tokio::test]
+    async fn test_vendor_recorder() {
+        let recorder = VendorRecorder::new(RefCounted::new(TestLoader));
+        let path = ResPath::new_remote_unchecked("https://remote/", "online.yaml");
+        recorder.load_raw(&path).await.unwrap();
+        let path = ResPath::new_local_unchecked("vendor/remote/vendored.yaml");
+        recorder.load_raw(&path).await.unwrap();
+
+        let resources = recorder.take_resources();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(&*resources["vendor/remote/online.yaml"], b"online");
+        assert!(recorder.take_resources().is_empty());
+    }
+}
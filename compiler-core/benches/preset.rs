@@ -0,0 +1,139 @@
+//! Benchmark for compiling a large route that uses nested presets heavily
+//!
+//! Run with `cargo bench -p compiler-core`
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use instant::Instant;
+use serde_json::{json, Value};
+
+use celerc::comp::PresetCache;
+use celerc::json::RouteBlob;
+use celerc::lang::{optimize_presets, Preset};
+use celerc::prep::{CompilerMetadata, RouteConfig, Setting};
+use celerc::{CompileContext, Compiler};
+
+const SECTIONS: usize = 30;
+const LINES_PER_SECTION: usize = 100;
+
+fn create_presets() -> BTreeMap<String, Preset> {
+    let presets = json!({
+        "_Base": {
+            "color": "#ff0000",
+            "notes": [{"type": "text", "content": "base note"}],
+            "vars": {"count": 1},
+        },
+        "_Item": {
+            "presets": ["_Base"],
+            "text": "Get $(0)",
+            "icon": "item-$(0|kebab)",
+            "comment": "$(1|default:nothing)",
+        },
+        "_Chest": {
+            "extends": "_Base",
+            "presets": ["_Item<Chest>"],
+            "vars": {"chest": 1},
+            "counter": "chest",
+        },
+        "_Shrine": {
+            "presets": ["_Base", "_Chest"],
+            "text": "Shrine",
+            "icon": "shrine",
+            "coord": [100, 200],
+        },
+        "_Warp": {
+            "presets": ["_Shrine"],
+            "movements": [{"to": [1, 2], "warp": true}, [3, 4]],
+        },
+    });
+    match presets {
+        Value::Object(obj) => obj
+            .into_iter()
+            .map(|(k, v)| (k, Preset::compile(v).unwrap()))
+            .collect(),
+        _ => unreachable!(),
+    }
+}
+
+fn create_route() -> RouteBlob {
+    let mut sections = vec![];
+    for s in 0..SECTIONS {
+        let mut lines = vec![];
+        for i in 0..LINES_PER_SECTION {
+            let line = match i % 5 {
+                0 => json!("_Chest"),
+                1 => json!({"_Item<Korok Seed>": {"coord": [i, s]}}),
+                2 => json!({"_Shrine": {"comment": "shrine"}}),
+                3 => json!({"Go to Warp": {"presets": ["_Warp", "_Item<Map>"]}}),
+                _ => json!("plain line"),
+            };
+            lines.push(line);
+        }
+        sections.push(json!({ format!("Section {s}"): lines }));
+    }
+    RouteBlob::Prim(Value::Array(sections))
+}
+
+fn create_compiler<'p>(
+    presets: BTreeMap<String, Preset>,
+    route: &'p RouteBlob,
+    setting: &'p Setting,
+) -> Compiler<'p> {
+    Compiler {
+        ctx: CompileContext {
+            start_time: Instant::now(),
+            config: Cow::Owned(RouteConfig::default()),
+            meta: Cow::Owned(CompilerMetadata {
+                presets,
+                default_icon_priority: 2,
+            }),
+            plugins: vec![],
+            plugin_meta: vec![],
+            setting,
+        },
+        route: Cow::Borrowed(route),
+        plugin_runtimes: vec![],
+    }
+}
+
+fn bench_comp(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let setting = Setting::default();
+    let route = create_route();
+    let presets = create_presets();
+    let optimized_presets = runtime.block_on(optimize_presets(presets.clone()));
+
+    // all arms run the full comp phase, and the compilers are created outside of the measurement
+    let mut group = c.benchmark_group("comp");
+    group.sample_size(10);
+    group.bench_function("unoptimized", |b| {
+        b.iter_batched(
+            || create_compiler(presets.clone(), &route, &setting),
+            |compiler| {
+                runtime.block_on(compiler.compile_with_preset_cache(PresetCache::disabled()))
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("cached", |b| {
+        b.iter_batched(
+            || create_compiler(presets.clone(), &route, &setting),
+            |compiler| runtime.block_on(compiler.compile()),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("optimized", |b| {
+        b.iter_batched(
+            || create_compiler(optimized_presets.clone(), &route, &setting),
+            |compiler| runtime.block_on(compiler.compile()),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_comp);
+criterion_main!(benches);
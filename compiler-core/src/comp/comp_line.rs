@@ -7,7 +7,7 @@ use crate::lang::{DocDiagnostic, DocRichText, DocRichTextBlock};
 use crate::pack::{Compiler, PackError};
 use crate::util::StringMap;
 
-use super::{CompError, CompMarker, CompMovement, DocNote};
+use super::{CompError, CompMarker, CompMovement, DocNote, PresetCache};
 
 #[derive(PartialEq, Default, Serialize, Deserialize, Debug, Clone)]
 pub struct CompLine {
@@ -52,12 +52,24 @@ pub struct LineContext<'c, 'p> {
     pub compiler: &'c Compiler<'p>,
     pub line: CompLine,
     pub errors: Vec<CompError>,
+    /// Cache of applied presets, shared between lines
+    pub preset_cache: PresetCache<'c>,
 }
 
 impl<'p> Compiler<'p> {
     /// Parse the line (parallel pass)
     pub fn parse_line(&self, value: RouteBlobRef<'p>) -> CompLine {
+        self.parse_line_with_cache(value, &mut PresetCache::default())
+    }
+
+    /// Parse the line, using and updating the preset cache shared between lines
+    pub fn parse_line_with_cache<'c>(
+        &'c self,
+        value: RouteBlobRef<'p>,
+        preset_cache: &mut PresetCache<'c>,
+    ) -> CompLine {
         let mut ctx = self.create_line_context();
+        std::mem::swap(&mut ctx.preset_cache, preset_cache);
         match value.checked() {
             Ok(value) => {
                 ctx.parse_line(value);
@@ -92,6 +104,7 @@ impl<'p> Compiler<'p> {
             }
         };
 
+        std::mem::swap(&mut ctx.preset_cache, preset_cache);
         ctx.line
     }
 
@@ -104,6 +117,7 @@ impl<'p> Compiler<'p> {
             compiler: self,
             line,
             errors: vec![],
+            preset_cache: Default::default(),
         }
     }
 }
@@ -7,7 +7,7 @@ use crate::json::{
 use crate::lang::{self, DocRichText, IntoDiagnostic};
 use crate::pack::PackError;
 
-use super::{CompError, CompLine, Compiler};
+use super::{CompError, CompLine, Compiler, PresetCache};
 
 /// Compiled Section
 #[derive(Default, Serialize, Deserialize, Debug, Clone)]
@@ -43,11 +43,12 @@ impl<'p> Compiler<'p> {
     }
     /// Compile a blob into a section
     ///
-    /// If value is a preface, returns `None`
-    pub async fn compile_section(
-        &self,
+    /// If value is a preface, returns `None`. The preset cache is shared between sections
+    pub async fn compile_section<'c>(
+        &'c self,
         value: RouteBlobRef<'p>,
         route: &[CompSection],
+        preset_cache: &mut PresetCache<'c>,
     ) -> Option<CompSection> {
         let result = match value.try_as_single_key_object() {
             RouteBlobSingleKeyObjectResult::Ok(key, value) => Ok((key, value)),
@@ -96,7 +97,7 @@ impl<'p> Compiler<'p> {
         let mut lines = vec![];
         for line in array {
             yield_budget(64).await;
-            lines.push(self.parse_line(line));
+            lines.push(self.parse_line_with_cache(line, preset_cache));
         }
 
         let section = CompSection {
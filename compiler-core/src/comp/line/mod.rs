@@ -2,7 +2,7 @@ use serde_json::Value;
 
 use crate::json::{Cast, Coerce, IntoSafeRouteBlob, SafeRouteBlob};
 use crate::lang;
-use crate::lang::{HydrateTarget, PresetInst};
+use crate::lang::HydrateTarget;
 use crate::prop;
 
 use super::{validate_not_array_or_object, CompError, LineContext};
@@ -16,6 +16,8 @@ mod note;
 pub use note::*;
 mod desugar;
 mod preset;
+mod preset_cache;
+pub use preset_cache::*;
 mod prop_map;
 use prop_map::*;
 
@@ -65,7 +67,7 @@ impl<'c, 'p> LineContext<'c, 'p> {
 
         // process the preset in the text
         if text.starts_with('_') {
-            let preset_inst = PresetInst::try_parse(&text);
+            let preset_inst = self.preset_cache.parse_inst(&text);
             if let Some(inst) = preset_inst {
                 // At this level, we will only process the preset if it exists
                 // otherwise treat the string as a regular string
@@ -22,10 +22,23 @@ impl<'c, 'p> LineContext<'c, 'p> {
             }
             Some(preset) => preset,
         };
+        if let Some(properties) = self.preset_cache.get(inst, depth) {
+            output.extend(properties.clone());
+            return;
+        }
+
+        let error_count = self.errors.len();
+        let mut result = LinePropMap::new();
         let mut properties = LinePropMap::new();
         preset.hydrate(&inst.args, &mut properties);
+        // apply the base preset first, so the presets and properties of this preset are on top
+        let extends = properties.remove(prop::EXTENDS);
+        let has_extends = extends.is_some();
+        if let Some(extends) = extends {
+            self.process_one_preset(prop::EXTENDS, None, depth, extends, &mut result);
+        }
         if let Some(presets) = properties.remove(prop::PRESETS) {
-            self.process_presets(depth, presets, output);
+            self.process_presets(depth, presets, &mut result);
         }
 
         if let Some(movements) = properties.remove(prop::MOVEMENTS) {
@@ -35,7 +48,19 @@ impl<'c, 'p> LineContext<'c, 'p> {
             );
         }
 
-        output.extend(properties.evaluate());
+        if has_extends {
+            // deep merge the properties on top of the extended preset
+            result.deep_merge(properties);
+        } else {
+            result.extend(properties.evaluate());
+        }
+
+        let result = result.evaluate();
+        // only cache the result if it's the same when applied from any line
+        if self.errors.len() == error_count {
+            self.preset_cache.insert(inst, depth, result.clone());
+        }
+        output.extend(result);
     }
 
     /// Process the "presets" property in the line object
@@ -50,17 +75,18 @@ impl<'c, 'p> LineContext<'c, 'p> {
         match presets.try_into_array() {
             Ok(arr) => {
                 for (i, preset) in arr.into_iter().enumerate() {
-                    self.process_one_preset(Some(i), depth, preset, output);
+                    self.process_one_preset(prop::PRESETS, Some(i), depth, preset, output);
                 }
             }
             Err(preset) => {
-                self.process_one_preset(None, depth, preset, output);
+                self.process_one_preset(prop::PRESETS, None, depth, preset, output);
             }
         }
     }
 
     fn process_one_preset(
         &mut self,
+        prop_name: &str,
         index: Option<usize>,
         depth: usize,
         preset: SafeRouteBlob<'c>,
@@ -70,8 +96,8 @@ impl<'c, 'p> LineContext<'c, 'p> {
             &preset,
             &mut self.errors,
             match index {
-                Some(i) => format!("{p}[{i}]", p = prop::PRESETS, i = i),
-                None => prop::PRESETS.to_string(),
+                Some(i) => format!("{p}[{i}]", p = prop_name, i = i),
+                None => prop_name.to_string(),
             }
         ) {
             return;
@@ -84,7 +110,7 @@ impl<'c, 'p> LineContext<'c, 'p> {
             return;
         }
 
-        let preset_inst = PresetInst::try_parse(&preset_string);
+        let preset_inst = self.preset_cache.parse_inst(&preset_string);
         match preset_inst {
             None => {
                 self.errors
@@ -116,7 +142,7 @@ impl<'c, 'p> LineContext<'c, 'p> {
                     continue;
                 }
             };
-            let preset_inst = match PresetInst::try_parse(preset_str) {
+            let preset_inst = match self.preset_cache.parse_inst(preset_str) {
                 None => {
                     self.errors
                         .push(CompError::InvalidPresetString(preset_str.to_string()));
@@ -151,7 +177,8 @@ mod test {
     use serde_json::{json, Value};
 
     use crate::comp::test_utils::CompilerBuilder;
-    use crate::lang::Preset;
+    use crate::lang::{optimize_presets, Preset};
+    use crate::prep::Setting;
 
     use super::*;
 
@@ -627,4 +654,369 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_extends() {
+        let mut builder = CompilerBuilder::default();
+        builder
+            .add_preset(
+                "_base",
+                Preset::compile(json!({
+                    "text": "base $(0)",
+                    "comment": "base comment",
+                    "vars": {
+                        "a": 1,
+                        "b": "$(0)",
+                    },
+                    "movements": [[1, 2]],
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_base::child",
+                Preset::compile(json!({
+                    "extends": "_base<$(1)>",
+                    "text": "child $(0)",
+                    "vars": {
+                        "c": 3,
+                    },
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_base::grandchild",
+                Preset::compile(json!({
+                    "extends": "_base::child<grand, $(0)>",
+                    "vars": {
+                        "a": 4,
+                    },
+                    "movements": [[3, 4]],
+                }))
+                .unwrap(),
+            );
+        let compiler = builder.build();
+        let mut ctx = LineContext::with_compiler(&compiler);
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(
+            0,
+            &PresetInst {
+                name: "_base::child".to_string(),
+                args: vec!["foo".to_string(), "bar".to_string()],
+            },
+            &mut output,
+        );
+        assert_eq!(
+            evaluate(output),
+            [
+                ("text".to_string(), json!("child foo")),
+                ("comment".to_string(), json!("base comment")),
+                ("vars".to_string(), json!({"a": 1, "b": "bar", "c": 3})),
+                ("movements".to_string(), json!([[1, 2]])),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(ctx.errors, vec![]);
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(
+            0,
+            &PresetInst {
+                name: "_base::grandchild".to_string(),
+                args: vec!["x".to_string()],
+            },
+            &mut output,
+        );
+        assert_eq!(
+            evaluate(output),
+            [
+                ("text".to_string(), json!("child grand")),
+                ("comment".to_string(), json!("base comment")),
+                ("vars".to_string(), json!({"a": 4, "b": " x", "c": 3})),
+                ("movements".to_string(), json!([[3, 4]])),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(ctx.errors, vec![]);
+    }
+
+    #[test]
+    fn test_extends_with_presets() {
+        let mut builder = CompilerBuilder::default();
+        builder
+            .add_preset(
+                "_base",
+                Preset::compile(json!({
+                    "text": "base",
+                    "key": "base",
+                    "comment": "base comment",
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_key",
+                Preset::compile(json!({
+                    "key": "preset",
+                    "comment": "preset comment",
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_child",
+                Preset::compile(json!({
+                    "extends": "_base",
+                    "presets": ["_key"],
+                    "comment": "child comment",
+                }))
+                .unwrap(),
+            );
+        let compiler = builder.build();
+        let mut ctx = LineContext::with_compiler(&compiler);
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(
+            0,
+            &PresetInst {
+                name: "_child".to_string(),
+                args: vec![],
+            },
+            &mut output,
+        );
+        assert_eq!(
+            evaluate(output),
+            [
+                ("text".to_string(), json!("base")),
+                ("key".to_string(), json!("preset")),
+                ("comment".to_string(), json!("child comment")),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(ctx.errors, vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_max_depth_flattened() {
+        // a chain of static presets deeper than the max depth
+        let depth = Setting::default().max_preset_ref_depth + 2;
+        let mut presets = BTreeMap::new();
+        for i in 0..depth {
+            let preset = if i + 1 < depth {
+                json!({ "extends": format!("_p{}", i + 1), "counter": format!("p{i}") })
+            } else {
+                json!({ "text": "last" })
+            };
+            presets.insert(format!("_p{i}"), Preset::compile(preset).unwrap());
+        }
+        let inst = PresetInst {
+            name: "_p0".to_string(),
+            args: vec![],
+        };
+
+        let mut builder = CompilerBuilder::default();
+        for (name, preset) in presets.clone() {
+            builder.add_preset(&name, preset);
+        }
+        let compiler = builder.build();
+        let mut ctx = LineContext::with_compiler(&compiler);
+        ctx.apply_preset(0, &inst, &mut LinePropMap::new());
+        assert!(ctx
+            .errors
+            .iter()
+            .any(|e| matches!(e, CompError::MaxPresetDepthExceeded(_))));
+
+        // the chain is flattened when optimized, so it no longer exceeds the max depth
+        let mut builder = CompilerBuilder::default();
+        for (name, preset) in optimize_presets(presets).await {
+            builder.add_preset(&name, preset);
+        }
+        let compiler = builder.build();
+        let mut ctx = LineContext::with_compiler(&compiler);
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(0, &inst, &mut output);
+        assert_eq!(ctx.errors, vec![]);
+        assert_eq!(
+            evaluate(output),
+            [
+                ("text".to_string(), json!("last")),
+                ("counter".to_string(), json!("p0")),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn test_extends_invalid() {
+        let mut builder = CompilerBuilder::default();
+        builder
+            .add_preset(
+                "_cycle::one",
+                Preset::compile(json!({
+                    "extends": "_cycle::two",
+                    "text": "one",
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_cycle::two",
+                Preset::compile(json!({
+                    "extends": "_cycle::one",
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_invalid",
+                Preset::compile(json!({
+                    "extends": ["_cycle::one"],
+                    "text": "invalid",
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_notfound",
+                Preset::compile(json!({
+                    "extends": "_foo",
+                    "text": "not found",
+                }))
+                .unwrap(),
+            );
+        let compiler = builder.build();
+        let mut ctx = LineContext::with_compiler(&compiler);
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(
+            0,
+            &PresetInst {
+                name: "_cycle::one".to_string(),
+                args: vec![],
+            },
+            &mut output,
+        );
+        assert_eq!(
+            evaluate(output),
+            [("text".to_string(), json!("one"))].into_iter().collect()
+        );
+        assert_eq!(
+            ctx.errors,
+            vec![CompError::MaxPresetDepthExceeded("_cycle::two".to_string())]
+        );
+        ctx.errors.clear();
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(
+            0,
+            &PresetInst {
+                name: "_invalid".to_string(),
+                args: vec![],
+            },
+            &mut output,
+        );
+        assert_eq!(
+            evaluate(output),
+            [("text".to_string(), json!("invalid"))]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            ctx.errors,
+            vec![CompError::InvalidLinePropertyType("extends".to_string())]
+        );
+        ctx.errors.clear();
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(
+            0,
+            &PresetInst {
+                name: "_notfound".to_string(),
+                args: vec![],
+            },
+            &mut output,
+        );
+        assert_eq!(
+            evaluate(output),
+            [("text".to_string(), json!("not found"))]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            ctx.errors,
+            vec![CompError::PresetNotFound("_foo".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut builder = CompilerBuilder::default();
+        builder
+            .add_preset(
+                "_one",
+                Preset::compile(json!({
+                    "text": "one $(0)",
+                }))
+                .unwrap(),
+            )
+            .add_preset(
+                "_two",
+                Preset::compile(json!({
+                    "presets": ["_one<$(0)>", "_notfound"],
+                    "comment": "two",
+                }))
+                .unwrap(),
+            );
+        let compiler = builder.build();
+        let mut ctx = LineContext::with_compiler(&compiler);
+        let one = PresetInst {
+            name: "_one".to_string(),
+            args: vec!["a".to_string()],
+        };
+        let two = PresetInst {
+            name: "_two".to_string(),
+            args: vec!["a".to_string()],
+        };
+
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(1, &one, &mut output);
+        assert_eq!(
+            ctx.preset_cache.get(&one, 1).cloned(),
+            Some(output.evaluate())
+        );
+        assert!(ctx.preset_cache.get(&one, 0).is_some());
+        // cannot use cache at deeper depth
+        assert!(ctx.preset_cache.get(&one, 2).is_none());
+
+        // deeper depth can still exceed max depth
+        let mut output = LinePropMap::new();
+        ctx.apply_preset(compiler.setting.max_preset_ref_depth + 1, &one, &mut output);
+        assert_eq!(evaluate(output), BTreeMap::new());
+        assert_eq!(
+            ctx.errors,
+            vec![CompError::MaxPresetDepthExceeded("_one".to_string())]
+        );
+        ctx.errors.clear();
+
+        // not cached if there are errors
+        for _ in 0..2 {
+            let mut output = LinePropMap::new();
+            ctx.apply_preset(0, &two, &mut output);
+            assert_eq!(
+                evaluate(output),
+                [
+                    ("text".to_string(), json!("one a")),
+                    ("comment".to_string(), json!("two")),
+                ]
+                .into_iter()
+                .collect()
+            );
+            assert_eq!(
+                ctx.errors,
+                vec![CompError::PresetNotFound("_notfound".to_string())]
+            );
+            ctx.errors.clear();
+        }
+        assert!(ctx.preset_cache.get(&two, 0).is_none());
+        assert!(ctx.preset_cache.get(&one, 1).is_some());
+    }
 }
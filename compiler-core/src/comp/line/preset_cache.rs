@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::json::SafeRouteBlob;
+use crate::lang::PresetInst;
+
+/// Cache of the properties from applying presets, keyed by the preset instantiation
+///
+/// The same preset is usually applied with the same args in many lines, so
+/// the hydration result is cached and shared for the whole document.
+/// Parsing the preset strings is also cached for the same reason.
+#[derive(Debug, Default)]
+pub struct PresetCache<'c> {
+    entries: HashMap<PresetInst, PresetCacheEntry<'c>>,
+    insts: HashMap<String, Option<PresetInst>>,
+    /// If true, nothing is cached
+    disabled: bool,
+}
+
+#[derive(Debug)]
+struct PresetCacheEntry<'c> {
+    /// The maximum depth the preset was applied at
+    depth: usize,
+    /// The evaluated properties
+    properties: BTreeMap<String, SafeRouteBlob<'c>>,
+}
+
+impl<'c> PresetCache<'c> {
+    /// Create a cache that doesn't cache anything, for comparing the performance
+    pub fn disabled() -> Self {
+        Self {
+            disabled: true,
+            ..Default::default()
+        }
+    }
+
+    /// Parse a string to a preset instantiation, see [`PresetInst::try_parse`]
+    pub fn parse_inst(&mut self, s: &str) -> Option<PresetInst> {
+        if let Some(inst) = self.insts.get(s) {
+            return inst.clone();
+        }
+        let inst = PresetInst::try_parse(s);
+        if self.disabled {
+            return inst;
+        }
+        self.insts.insert(s.to_string(), inst.clone());
+        inst
+    }
+
+    /// Get the cached properties of applying the preset at the depth
+    ///
+    /// The cached result can only be used if the preset was applied without errors
+    /// at the same or deeper depth, since it could exceed the max depth otherwise
+    pub fn get(
+        &self,
+        inst: &PresetInst,
+        depth: usize,
+    ) -> Option<&BTreeMap<String, SafeRouteBlob<'c>>> {
+        self.entries
+            .get(inst)
+            .filter(|entry| depth <= entry.depth)
+            .map(|entry| &entry.properties)
+    }
+
+    /// Cache the properties of applying the preset without errors at the depth
+    pub fn insert(
+        &mut self,
+        inst: &PresetInst,
+        depth: usize,
+        properties: BTreeMap<String, SafeRouteBlob<'c>>,
+    ) {
+        if self.disabled {
+            return;
+        }
+        match self.entries.get_mut(inst) {
+            Some(entry) => {
+                if depth > entry.depth {
+                    entry.depth = depth;
+                }
+            }
+            None => {
+                self.entries
+                    .insert(inst.clone(), PresetCacheEntry { depth, properties });
+            }
+        }
+    }
+}
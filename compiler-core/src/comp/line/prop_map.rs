@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 
-use crate::json::{IntoSafeRouteBlob, SafeRouteBlob};
+use crate::json::{Cast, IntoSafeRouteBlob, SafeRouteBlob};
 use crate::lang::HydrateTarget;
 use crate::prop;
 
@@ -62,6 +62,46 @@ impl<'a> LinePropMap<'a> {
     {
         self.inner.extend(other);
     }
+
+    /// Deep merge the properties in `other` into self.
+    ///
+    /// Objects are merged recursively. Other values in `other` replace
+    /// the values in self
+    pub fn deep_merge(&mut self, other: LinePropMap<'a>) {
+        for (key, value) in other.inner {
+            let value = match self.inner.remove(&key) {
+                Some(base) => deep_merge_blob(base, value),
+                None => value,
+            };
+            self.inner.insert(key, value);
+        }
+    }
+}
+
+fn deep_merge_blob<'a>(base: SafeRouteBlob<'a>, other: SafeRouteBlob<'a>) -> SafeRouteBlob<'a> {
+    if base.as_object().is_none() {
+        return other;
+    }
+    let other = match other.try_into_object() {
+        Ok(other) => other,
+        Err(other) => return other,
+    };
+    let mut out = match base.try_into_object() {
+        Ok(base) => base
+            .into_iter()
+            .map(|(k, v)| (k.into_owned(), v))
+            .collect::<BTreeMap<_, _>>(),
+        Err(_) => BTreeMap::new(),
+    };
+    for (key, value) in other {
+        let key = key.into_owned();
+        let value = match out.remove(&key) {
+            Some(base) => deep_merge_blob(base, value),
+            None => value,
+        };
+        out.insert(key, value);
+    }
+    SafeRouteBlob::OwnedObject(out)
 }
 
 #[cfg(test)]
@@ -100,4 +140,31 @@ mod test {
             .into();
         assert_eq!(value, json!([1, 2]));
     }
+
+    #[test]
+    fn test_deep_merge() {
+        let mut base = LinePropMap::new();
+        base.insert("text".to_string(), json!("base"));
+        base.insert("comment".to_string(), json!("base comment"));
+        base.insert("vars".to_string(), json!({"a": 1, "b": {"c": 2, "d": 3}}));
+        base.insert("movements".to_string(), json!([[1, 2]]));
+        base.insert("notes".to_string(), json!({"x": 1}));
+        let mut other = LinePropMap::new();
+        other.insert("text".to_string(), json!("other"));
+        other.insert("vars".to_string(), json!({"b": {"c": 4}, "e": 5}));
+        other.insert("movements".to_string(), json!([[3, 4]]));
+        other.insert("notes".to_string(), json!("not an object"));
+        base.deep_merge(other);
+        let value: Value = SafeRouteBlob::OwnedObject(base.evaluate()).into();
+        assert_eq!(
+            value,
+            json!({
+                "text": "other",
+                "comment": "base comment",
+                "vars": {"a": 1, "b": {"c": 4, "d": 3}, "e": 5},
+                "movements": [[3, 4]],
+                "notes": "not an object",
+            })
+        );
+    }
 }
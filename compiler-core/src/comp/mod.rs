@@ -55,14 +55,19 @@ static DEFAULT_SETTING: Setting = Setting::const_default();
 
 impl<'p> Compiler<'p> {
     /// Entry point for the comp phase
-    pub async fn compile(mut self) -> CompDoc<'p> {
+    pub async fn compile(self) -> CompDoc<'p> {
+        self.compile_with_preset_cache(PresetCache::default()).await
+    }
+
+    /// Run the comp phase with the preset cache, which is shared by all lines
+    pub async fn compile_with_preset_cache(mut self, preset_cache: PresetCache<'_>) -> CompDoc<'p> {
         for plugin in &mut self.plugin_runtimes {
             if let Err(e) = plugin.on_before_compile(&mut self.ctx).await {
                 return CompDoc::from_diagnostic(CompError::PluginBeforeCompileError(e), self.ctx);
             }
         }
         let mut plugins = std::mem::take(&mut self.plugin_runtimes);
-        let mut comp_doc = self.compile_document().await;
+        let mut comp_doc = self.compile_document(preset_cache).await;
         for plugin in &mut plugins {
             if let Err(e) = plugin.on_after_compile(&mut comp_doc).await {
                 let diag = CompError::PluginAfterCompileError(e).into_diagnostic();
@@ -74,7 +79,7 @@ impl<'p> Compiler<'p> {
         comp_doc
     }
 
-    async fn compile_document(self) -> CompDoc<'p> {
+    async fn compile_document(self, mut preset_cache: PresetCache<'_>) -> CompDoc<'p> {
         let route_blob = RouteBlobRef::Blob(self.route.as_ref());
 
         let mut preface = vec![];
@@ -91,6 +96,7 @@ impl<'p> Compiler<'p> {
                         &mut route,
                         &mut preface,
                         &mut diagnostics,
+                        &mut preset_cache,
                     )
                     .await;
                 }
@@ -115,14 +121,18 @@ impl<'p> Compiler<'p> {
         }
     }
 
-    async fn compile_section_or_preface(
-        &self,
+    async fn compile_section_or_preface<'c>(
+        &'c self,
         section_ref: RouteBlobRef<'p>,
         route: &mut Vec<CompSection>,
         prefaces: &mut Vec<DocRichText>,
         diagnostics: &mut Vec<DocDiagnostic>,
+        preset_cache: &mut PresetCache<'c>,
     ) {
-        match self.compile_section(section_ref.clone(), route).await {
+        match self
+            .compile_section(section_ref.clone(), route, preset_cache)
+            .await
+        {
             Some(section) => route.push(section),
             None => {
                 match self.compile_preface(section_ref) {
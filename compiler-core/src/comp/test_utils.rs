@@ -36,6 +36,7 @@ impl<'c, 'p> LineContext<'c, 'p> {
             compiler,
             line: Default::default(),
             errors: Default::default(),
+            preset_cache: Default::default(),
         }
     }
 }
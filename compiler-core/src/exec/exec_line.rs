@@ -2,6 +2,7 @@ use crate::comp::{CompLine, CompMovement, DocNote};
 use crate::lang::{DocDiagnostic, DocRichText, DocRichTextBlock, IntoDiagnostic};
 use crate::macros::derive_wasm;
 use crate::prep::{GameCoord, RouteConfig};
+use crate::prop;
 
 use super::{ExecError, MapBuilder, MapIcon, MapMarker};
 
@@ -44,6 +45,7 @@ impl CompLine {
         line_number: usize,
         map_builder: &mut MapBuilder,
     ) -> ExecLine {
+        self.resolve_inline_icons(project);
         let line_color = match self.line_color {
             Some(color) => {
                 map_builder.change_color(color.clone());
@@ -143,6 +145,25 @@ impl CompLine {
             map_coords,
         }
     }
+
+    /// Resolve the inline icons like `.icon(korok)` in the rich texts of the line
+    ///
+    /// The icon is only set on the block if it exists in the config
+    fn resolve_inline_icons(&mut self, project: &RouteConfig) {
+        let mut diagnostics = vec![];
+        for block in self.rich_texts_mut().with_counter() {
+            if block.tag.as_deref() != Some(prop::ICON) {
+                continue;
+            }
+            let icon = block.text.trim();
+            if project.icons.contains_key(icon) {
+                block.icon = Some(icon.to_string());
+            } else {
+                diagnostics.push(ExecError::IconNotFound(icon.to_string()).into_diagnostic());
+            }
+        }
+        self.diagnostics.extend(diagnostics);
+    }
 }
 
 #[cfg(test)]
@@ -152,7 +173,7 @@ mod test {
     use crate::comp::{CompMarker, CompMovement, DocNote};
     use crate::exec::MapLine;
     use crate::lang::{
-        DocDiagnostic, DocPoorText, DocPoorTextBlock, DocRichText, DocRichTextBlock,
+        self, DocDiagnostic, DocPoorText, DocPoorTextBlock, DocRichText, DocRichTextBlock,
     };
     use crate::prep::GameCoord;
 
@@ -215,13 +236,17 @@ mod test {
         let test_text = DocRichText(vec![
             DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: "test1".to_string(),
                 link: None,
+                icon: None,
             },
             DocRichTextBlock {
                 tag: Some("test tag".to_string()),
+                outer_tags: vec![],
                 text: "test2".to_string(),
                 link: Some("test link".to_string()),
+                icon: None,
             },
         ]);
         let test_color = "test color".to_string();
@@ -237,32 +262,42 @@ mod test {
         let test_secondary_text = DocRichText(vec![
             DocRichTextBlock {
                 tag: None,
+                outer_tags: vec![],
                 text: "secondary test1".to_string(),
                 link: None,
+                icon: None,
             },
             DocRichTextBlock {
                 tag: Some("secondary test tag".to_string()),
+                outer_tags: vec![],
                 text: "secondary test2".to_string(),
                 link: Some("secondary test link".to_string()),
+                icon: None,
             },
         ]);
         let test_counter_text = Some(DocRichTextBlock {
             tag: Some("counter test tag".to_string()),
+            outer_tags: vec![],
             text: "counter test".to_string(),
             link: None,
+            icon: None,
         });
         let test_notes = vec![
             DocNote::Text {
                 content: DocRichText(vec![
                     DocRichTextBlock {
                         tag: None,
+                        outer_tags: vec![],
                         text: "note test1".to_string(),
                         link: None,
+                        icon: None,
                     },
                     DocRichTextBlock {
                         tag: Some("note test tag".to_string()),
+                        outer_tags: vec![],
                         text: "note test2".to_string(),
                         link: Some("note test link".to_string()),
+                        icon: None,
                     },
                 ]),
             },
@@ -481,4 +516,50 @@ mod test {
         assert_eq!(exec_line.diagnostics[2].msg_type, "warning");
         assert_eq!(exec_line.diagnostics[2].source, "celerc/exec");
     }
+
+    #[test]
+    fn test_inline_icons() {
+        let test_line = CompLine {
+            text: lang::parse_rich("get .icon(korok) seed"),
+            secondary_text: lang::parse_rich(".icon( shrine )"),
+            notes: vec![DocNote::text("missing .icon(chest)")],
+            counter_text: Some(DocRichTextBlock::with_tag("icon", "korok")),
+            ..Default::default()
+        };
+        let project = RouteConfig {
+            icons: btree_map! {
+                "korok".to_string() => "korok.png".to_string(),
+                "shrine".to_string() => "shrine.png".to_string(),
+            }
+            .into(),
+            ..Default::default()
+        };
+        let mut builder = Default::default();
+        let exec_line = test_line.exec(&project, 0, 0, &mut builder);
+        assert_eq!(
+            exec_line.text.0[1],
+            DocRichTextBlock {
+                tag: Some("icon".to_string()),
+                outer_tags: vec![],
+                text: "korok".to_string(),
+                link: None,
+                icon: Some("korok".to_string()),
+            }
+        );
+        assert_eq!(
+            exec_line.secondary_text.0[0].icon,
+            Some("shrine".to_string())
+        );
+        assert_eq!(
+            exec_line.counter_text.unwrap().icon,
+            Some("korok".to_string())
+        );
+        match &exec_line.notes[0] {
+            DocNote::Text { content } => assert_eq!(content.0[1].icon, None),
+            _ => panic!("expected text note"),
+        }
+        assert_eq!(exec_line.diagnostics.len(), 1);
+        assert_eq!(exec_line.diagnostics[0].msg_type, "warning");
+        assert_eq!(exec_line.diagnostics[0].source, "celerc/exec");
+    }
 }
@@ -95,7 +95,14 @@ impl Instance {
                     plugin = Some(PluginUse::parse(res, value).await?);
                 }
                 prop::WITH => {
-                    props = value;
+                    // props can be loaded from a file with `use`
+                    props = match Use::from_value(&value) {
+                        Some(Use::Valid(valid_use)) => {
+                            res.resolve(&valid_use)?.load_structured().await?
+                        }
+                        Some(Use::Invalid(path)) => Err(ResError::InvalidUse(path))?,
+                        None => value,
+                    };
                 }
                 prop::ALLOW_DUPLICATE => {
                     allow_duplicate = value.coerce_truthy();
@@ -12,4 +12,5 @@ mod runtime;
 pub use runtime::*;
 
 mod native;
+pub use native::METRICS_STATS;
 mod script;
@@ -98,8 +98,10 @@ mod test {
     fn test_ignore_link() {
         let mut text = DocRichTextBlock {
             tag: Some("link".to_string()),
+            outer_tags: vec![],
             text: "hello world".to_string(),
             link: Some("https://example.com".to_string()),
+            icon: None,
         };
         let expected = text.clone();
         transform_link_tag(&mut text);
@@ -110,8 +112,10 @@ mod test {
     fn test_ignore_non_link_tag() {
         let mut text = DocRichTextBlock {
             tag: Some("test".to_string()),
+            outer_tags: vec![],
             text: "hello world".to_string(),
             link: None,
+            icon: None,
         };
         let expected = text.clone();
         transform_link_tag(&mut text);
@@ -122,16 +126,20 @@ mod test {
     fn test_transform_link_tag() {
         let mut text = DocRichTextBlock {
             tag: Some(prop::LINK.to_string()),
+            outer_tags: vec![],
             text: "hello world".to_string(),
             link: None,
+            icon: None,
         };
         transform_link_tag(&mut text);
         assert_eq!(
             text,
             DocRichTextBlock {
                 tag: Some(prop::LINK.to_string()),
+                outer_tags: vec![],
                 text: "hello world".to_string(),
                 link: Some("hello world".to_string()),
+                icon: None,
             }
         );
     }
@@ -140,17 +148,21 @@ mod test {
     fn test_transform_link_tag_with_text() {
         let mut text = DocRichTextBlock {
             tag: Some(prop::LINK.to_string()),
+            outer_tags: vec![],
             text: "[hello world] i am link".to_string(),
             link: None,
+            icon: None,
         };
         transform_link_tag(&mut text);
         assert_eq!(
             text,
             DocRichTextBlock {
                 tag: Some(prop::LINK.to_string()),
+                outer_tags: vec![],
                 text: "hello world".to_string(),
                 // link should be trimmed
                 link: Some("i am link".to_string()),
+                icon: None,
             }
         );
     }
@@ -159,46 +171,58 @@ mod test {
     fn test_transform_partial_bracket() {
         let mut text = DocRichTextBlock {
             tag: Some(prop::LINK.to_string()),
+            outer_tags: vec![],
             text: "[hello world i am link".to_string(),
             link: None,
+            icon: None,
         };
         transform_link_tag(&mut text);
         assert_eq!(
             text,
             DocRichTextBlock {
                 tag: Some(prop::LINK.to_string()),
+                outer_tags: vec![],
                 text: "[hello world i am link".to_string(),
                 link: Some("[hello world i am link".to_string()),
+                icon: None,
             }
         );
 
         let mut text = DocRichTextBlock {
             tag: Some(prop::LINK.to_string()),
+            outer_tags: vec![],
             text: "abc[hello world] i am link".to_string(),
             link: None,
+            icon: None,
         };
         transform_link_tag(&mut text);
         assert_eq!(
             text,
             DocRichTextBlock {
                 tag: Some(prop::LINK.to_string()),
+                outer_tags: vec![],
                 text: "abc[hello world] i am link".to_string(),
                 link: Some("abc[hello world] i am link".to_string()),
+                icon: None,
             }
         );
 
         let mut text = DocRichTextBlock {
             tag: Some(prop::LINK.to_string()),
+            outer_tags: vec![],
             text: "abchello world] i am link".to_string(),
             link: None,
+            icon: None,
         };
         transform_link_tag(&mut text);
         assert_eq!(
             text,
             DocRichTextBlock {
                 tag: Some(prop::LINK.to_string()),
+                outer_tags: vec![],
                 text: "abchello world] i am link".to_string(),
                 link: Some("abchello world] i am link".to_string()),
+                icon: None,
             }
         );
     }
@@ -11,6 +11,16 @@ use crate::prop;
 
 use crate::plugin::{PluginResult, Runtime};
 
+const PREP_TIME: &str = "Prep Time";
+const COMP_TIME: &str = "Comp Time";
+const EXEC_TIME: &str = "Exec Time";
+const COMPILED_IN: &str = "Compiled In";
+
+/// Keys of the project stats added by the metrics plugin.
+///
+/// The values are different every time the project is compiled
+pub const METRICS_STATS: &[&str] = &[PREP_TIME, COMP_TIME, EXEC_TIME, COMPILED_IN];
+
 /// Metrics collected during compilation
 pub struct Metrics {
     /// If detailed metrics of each phase should be given
@@ -69,23 +79,23 @@ impl Runtime for Metrics {
         // add time to statistics
         if self.detailed {
             project.stats.insert(
-                "Prep Time".to_string(),
+                PREP_TIME.to_string(),
                 format!("{}ms", self.before_comp_time_ms),
             );
             project
                 .stats
-                .insert("Comp Time".to_string(), format!("{}ms", self.comp_time_ms));
+                .insert(COMP_TIME.to_string(), format!("{}ms", self.comp_time_ms));
             project
                 .stats
-                .insert("Exec Time".to_string(), format!("{exec_time_ms}ms"));
+                .insert(EXEC_TIME.to_string(), format!("{exec_time_ms}ms"));
             let total_ms = self.before_comp_time_ms + self.comp_time_ms + exec_time_ms;
             project
                 .stats
-                .insert("Compiled In".to_string(), format!("{total_ms}ms"));
+                .insert(COMPILED_IN.to_string(), format!("{total_ms}ms"));
         } else {
             project
                 .stats
-                .insert("Compiled In".to_string(), format!("{exec_time_ms}ms"));
+                .insert(COMPILED_IN.to_string(), format!("{exec_time_ms}ms"));
         }
         Ok(())
     }
@@ -17,6 +17,7 @@ mod export_livesplit;
 mod export_mist;
 mod link;
 mod metrics;
+pub use metrics::METRICS_STATS;
 mod split_format;
 mod variables;
 
@@ -6,16 +6,23 @@ use crate::env::yield_budget;
 use crate::json::Coerce;
 use crate::prep::{PrepError, PrepResult, PreparedConfig};
 use crate::prop;
-use crate::res::{Loader, ResError, Resource, Use};
+use crate::res::{Loader, ResError, Resource, Use, ValidUse};
 
 impl<'a> PreparedConfig<'a> {
     /// Process the `icons` property
     ///
-    /// `use`'s are resolved in the context of `res`
+    /// `use`'s are resolved in the context of `res`.
+    /// The property can also be a directory or glob `use` (e.g. `use: ./icons/`),
+    /// which adds every image in it with the file name (without extension) as the icon id
     pub async fn load_icons<L>(&mut self, res: &Resource<'_, L>, icons: Value) -> PrepResult<()>
     where
         L: Loader,
     {
+        if let Some(Use::Valid(valid_use)) = Use::from_value(&icons) {
+            if valid_use.is_list() {
+                return self.load_icons_from_list(res, &valid_use).await;
+            }
+        }
         let icons = super::check_map!(self, icons, prop::ICONS)?;
 
         for (key, v) in icons.into_iter() {
@@ -42,4 +49,30 @@ impl<'a> PreparedConfig<'a> {
 
         Ok(())
     }
+
+    /// Add the images from a directory or glob `use` as icons
+    async fn load_icons_from_list<L>(
+        &mut self,
+        res: &Resource<'_, L>,
+        valid_use: &ValidUse,
+    ) -> PrepResult<()>
+    where
+        L: Loader,
+    {
+        for icon_res in res.resolve_list(valid_use).await? {
+            yield_budget(16).await;
+            // skip files that are not images, like a README in the icons directory
+            if !matches!(icon_res.path().get_type(), Some(t) if t.is_image()) {
+                continue;
+            }
+            let key = match icon_res.path().as_path().file_stem() {
+                Some(stem) => stem.to_string(),
+                None => continue,
+            };
+            let url = icon_res.load_image_url().await?;
+            self.icons.insert(key, url);
+        }
+
+        Ok(())
+    }
 }
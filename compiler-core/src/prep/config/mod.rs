@@ -43,6 +43,7 @@ impl<'a> PreparedConfig<'a> {
     pub fn new(setting: &'a Setting) -> Self {
         Self {
             setting: Cow::Borrowed(setting),
+            tags: builtin_tags(),
             ..Default::default()
         }
     }
@@ -131,7 +132,7 @@ impl<'a> PreparedConfig<'a> {
                     }
                 }
                 prop::PRESETS => {
-                    self.load_presets(value).await?;
+                    self.load_presets(res, value).await?;
                 }
                 prop::DEFAULT_ICON_PRIORITY => {
                     let priority = value.try_coerce_to_i64().ok_or_else(|| {
@@ -6,29 +6,59 @@ use crate::lang::Preset;
 use crate::macros::async_recursion;
 use crate::prep::{PrepError, PrepResult};
 use crate::prop;
+use crate::res::{Loader, ResError, Resource, Use};
 
 use super::PreparedConfig;
 
 impl<'a> PreparedConfig<'a> {
     /// Load the `presets` property
-    pub async fn load_presets(&mut self, value: Value) -> PrepResult<()> {
-        self.load_presets_internal("", value, 0).await
+    ///
+    /// The presets and namespaces can be loaded from other files with `use`,
+    /// which are resolved in the context of `res`
+    pub async fn load_presets<L>(&mut self, res: &Resource<'_, L>, value: Value) -> PrepResult<()>
+    where
+        L: Loader,
+    {
+        self.load_presets_internal(res, "", value, 0).await
     }
 
     /// Recursively load presets helper
     #[async_recursion(auto)]
-    async fn load_presets_internal(
+    async fn load_presets_internal<L>(
         &mut self,
+        res: &Resource<'_, L>,
         preset_name: &str,
         value: Value,
         depth: usize,
-    ) -> PrepResult<()> {
+    ) -> PrepResult<()>
+    where
+        L: Loader,
+    {
         if depth > self.setting.max_preset_namespace_depth {
             return Err(PrepError::MaxPresetNamespaceDepthExceeded(
                 self.setting.max_preset_namespace_depth,
             ));
         }
 
+        // the namespace is loaded from another file
+        match Use::from_value(&value) {
+            Some(Use::Valid(valid_use)) => {
+                let inner_res = res.resolve(&valid_use)?;
+                let value = inner_res.load_structured().await?;
+                return self
+                    .load_presets_internal(&inner_res, preset_name, value, depth + 1)
+                    .await;
+            }
+            Some(Use::Invalid(path)) => Err(ResError::InvalidUse(path))?,
+            None => {}
+        }
+
+        // a table (like a CSV file) defines one preset per row
+        let value = match value {
+            Value::Array(rows) => self.table_to_presets(preset_name, rows)?,
+            value => value,
+        };
+
         let obj = value.try_into_object().map_err(|_| {
             if preset_name.is_empty() {
                 PrepError::InvalidConfigPropertyType(
@@ -46,11 +76,18 @@ impl<'a> PreparedConfig<'a> {
             if let Some(namespace) = key.strip_prefix('_') {
                 // sub namespace
                 let full_key = format_preset_str(preset_name, namespace);
-                self.load_presets_internal(&full_key, value, depth + 1)
+                self.load_presets_internal(res, &full_key, value, depth + 1)
                     .await?;
             } else {
                 // preset
                 let full_key = format_preset_str(preset_name, &key);
+                let value = match Use::from_value(&value) {
+                    Some(Use::Valid(valid_use)) => {
+                        res.resolve(&valid_use)?.load_structured().await?
+                    }
+                    Some(Use::Invalid(path)) => Err(ResError::InvalidUse(path))?,
+                    None => value,
+                };
                 let preset = Preset::compile(value).ok_or_else(|| {
                     PrepError::InvalidPreset(self.trace.clone(), full_key.clone())
                 })?;
@@ -60,8 +97,43 @@ impl<'a> PreparedConfig<'a> {
 
         Ok(())
     }
+
+    /// Convert a table to presets, keyed by the `name` column of each row.
+    /// The other columns are the properties of the preset. Empty cells are skipped.
+    fn table_to_presets(&self, preset_name: &str, rows: Vec<Value>) -> PrepResult<Value> {
+        let mut presets = serde_json::Map::new();
+        for (i, row) in rows.into_iter().enumerate() {
+            let mut row = match row {
+                Value::Object(row) => row,
+                _ => return Err(self.invalid_table_row(preset_name, i)),
+            };
+            let name = match row.remove(TABLE_NAME_COLUMN) {
+                Some(Value::String(name)) if !name.is_empty() && !name.starts_with('_') => name,
+                _ => return Err(self.invalid_table_row(preset_name, i)),
+            };
+            row.retain(|_, value| value.as_str() != Some(""));
+            presets.insert(name, Value::Object(row));
+        }
+        Ok(Value::Object(presets))
+    }
+
+    fn invalid_table_row(&self, preset_name: &str, i: usize) -> PrepError {
+        let namespace = if preset_name.is_empty() {
+            prop::PRESETS.to_string()
+        } else {
+            preset_name.to_string()
+        };
+        PrepError::InvalidConfigPropertyType(
+            self.trace.clone(),
+            format!("{namespace}[{i}]").into(),
+            format!("row with a preset name in the `{TABLE_NAME_COLUMN}` column").into(),
+        )
+    }
 }
 
+/// Column for the preset names when loading presets from a table
+const TABLE_NAME_COLUMN: &str = "name";
+
 #[inline]
 fn format_preset_str(namespace: &str, key: &str) -> String {
     if namespace.is_empty() {
@@ -70,3 +142,87 @@ fn format_preset_str(namespace: &str, key: &str) -> String {
         format!("{namespace}::{key}")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::env::RefCounted;
+    use crate::macros::async_trait;
+    use crate::prep::Setting;
+    use crate::res::{ResPath, ResResult};
+
+    struct TestLoader;
+    #[async_trait(auto)]
+    impl Loader for TestLoader {
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            let content: &[u8] = match path.to_string().as_str() {
+                "presets/shops.toml" => {
+                    b"[Apple]\ntext = \"Buy apple\"\n\n[_Fruit]\nuse = \"./fruit.yaml\"\n"
+                }
+                "presets/fruit.yaml" => b"Banana:\n  text: Buy banana\n",
+                "presets/cherry.json" => b"{\"text\": \"Buy cherry\"}",
+                "presets/prices.csv" => {
+                    b"name,text,comment\nApple,Buy apple,10 rupees\nBanana,Buy banana,\n"
+                }
+                "presets/invalid.csv" => b"text\nBuy apple\n",
+                _ => return Err(ResError::FailToLoadFile(path.to_string(), "404".into())),
+            };
+            Ok(RefCounted::from(content))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_presets_use() {
+        let res = Resource::new(
+            ResPath::new_local_unchecked("project.yaml"),
+            RefCounted::new(TestLoader),
+        );
+        let setting = Setting::default();
+        let mut config = PreparedConfig::new(&setting);
+        let presets = json!({
+            "_Shop": { "use": "./presets/shops.toml" },
+            "Cherry": { "use": "./presets/cherry.json" },
+        });
+        config.load_presets(&res, presets).await.unwrap();
+
+        assert_eq!(
+            config.presets.keys().collect::<Vec<_>>(),
+            vec!["_Cherry", "_Shop::Apple", "_Shop::Fruit::Banana"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_presets_use_csv() {
+        let res = Resource::new(
+            ResPath::new_local_unchecked("project.yaml"),
+            RefCounted::new(TestLoader),
+        );
+        let setting = Setting::default();
+        let mut config = PreparedConfig::new(&setting);
+        let presets = json!({
+            "_Shop": { "use": "./presets/prices.csv" },
+        });
+        config.load_presets(&res, presets).await.unwrap();
+
+        assert_eq!(
+            config.presets.get("_Shop::Apple"),
+            Preset::compile(json!({"text": "Buy apple", "comment": "10 rupees"})).as_ref()
+        );
+        assert_eq!(
+            config.presets.get("_Shop::Banana"),
+            Preset::compile(json!({"text": "Buy banana"})).as_ref()
+        );
+
+        let mut config = PreparedConfig::new(&setting);
+        let presets = json!({
+            "_Shop": { "use": "./presets/invalid.csv" },
+        });
+        assert!(matches!(
+            config.load_presets(&res, presets).await,
+            Err(PrepError::InvalidConfigPropertyType(_, _, _))
+        ));
+    }
+}
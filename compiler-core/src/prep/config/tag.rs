@@ -1,5 +1,7 @@
 //! Process the `tags` property
 
+use std::collections::BTreeMap;
+
 use serde_json::Value;
 
 use crate::env::yield_budget;
@@ -8,6 +10,15 @@ use crate::macros::derive_wasm;
 use crate::prep::{PrepError, PrepResult, PreparedConfig};
 use crate::prop;
 
+/// Built-in tag for bold text
+pub const TAG_BOLD: &str = "bold";
+/// Built-in tag for italic text
+pub const TAG_ITALIC: &str = "italic";
+/// Built-in tag for strikethrough text
+pub const TAG_STRIKETHROUGH: &str = "strikethrough";
+/// Built-in tag for inline code
+pub const TAG_CODE: &str = "code";
+
 impl<'a> PreparedConfig<'a> {
     /// Process the `tags` property
     pub async fn load_tags(&mut self, tags: Value) -> PrepResult<()> {
@@ -76,6 +87,8 @@ pub struct DocTag {
     pub underline: Option<bool>,
     /// Strikethrough style
     pub strikethrough: Option<bool>,
+    /// Monospace font, for code
+    pub monospace: Option<bool>,
     /// Color of the text (light, dark)
     pub color: Option<DocTagColor>,
     /// Background color of the text (light, dark)
@@ -107,6 +120,7 @@ impl DocTag {
         apply_tag_prop!(self, other, italic);
         apply_tag_prop!(self, other, underline);
         apply_tag_prop!(self, other, strikethrough);
+        apply_tag_prop!(self, other, monospace);
         apply_tag_prop!(self, other, color);
         apply_tag_prop!(self, other, background);
         apply_tag_prop!(self, other, split_type);
@@ -118,12 +132,50 @@ impl DocTag {
         apply_tag_if_none!(self, other, italic);
         apply_tag_if_none!(self, other, underline);
         apply_tag_if_none!(self, other, strikethrough);
+        apply_tag_if_none!(self, other, monospace);
         apply_tag_if_none!(self, other, color);
         apply_tag_if_none!(self, other, background);
         apply_tag_if_none!(self, other, split_type);
     }
 }
 
+/// Get the built-in tags for basic text formatting
+///
+/// These are available without being defined in the config. A tag in the config with the
+/// same name replaces the built-in one, or extends it by including itself.
+pub fn builtin_tags() -> BTreeMap<String, DocTag> {
+    let mut tags = BTreeMap::new();
+    tags.insert(
+        TAG_BOLD.to_string(),
+        DocTag {
+            bold: Some(true),
+            ..Default::default()
+        },
+    );
+    tags.insert(
+        TAG_ITALIC.to_string(),
+        DocTag {
+            italic: Some(true),
+            ..Default::default()
+        },
+    );
+    tags.insert(
+        TAG_STRIKETHROUGH.to_string(),
+        DocTag {
+            strikethrough: Some(true),
+            ..Default::default()
+        },
+    );
+    tags.insert(
+        TAG_CODE.to_string(),
+        DocTag {
+            monospace: Some(true),
+            ..Default::default()
+        },
+    );
+    tags
+}
+
 /// Used to specify color for [`DocTag`]s.
 #[derive(PartialEq, Debug, Clone)]
 #[derive_wasm]
@@ -139,3 +191,60 @@ pub enum DocTagColor {
         dark: Option<String>,
     },
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::prep::Setting;
+
+    #[test]
+    fn test_builtin_tags() {
+        let tags = builtin_tags();
+        assert_eq!(tags.len(), 4);
+        assert_eq!(tags.get(TAG_BOLD).unwrap().bold, Some(true));
+        assert_eq!(tags.get(TAG_ITALIC).unwrap().italic, Some(true));
+        assert_eq!(
+            tags.get(TAG_STRIKETHROUGH).unwrap().strikethrough,
+            Some(true)
+        );
+        assert_eq!(tags.get(TAG_CODE).unwrap().monospace, Some(true));
+
+        let setting = Setting::default();
+        let config = PreparedConfig::new(&setting);
+        assert_eq!(config.tags, tags);
+    }
+
+    #[tokio::test]
+    async fn test_override_builtin_tags() {
+        let setting = Setting::default();
+        let mut config = PreparedConfig::new(&setting);
+        let tags = json!({
+            "bold": { "color": "red" },
+            "italic": { "includes": "italic", "color": "blue" },
+        });
+        config.load_tags(tags).await.unwrap();
+
+        // replaced
+        assert_eq!(
+            config.tags.get(TAG_BOLD),
+            Some(&DocTag {
+                color: Some(DocTagColor::Single("red".to_string())),
+                ..Default::default()
+            })
+        );
+        // extended by including itself
+        assert_eq!(
+            config.tags.get(TAG_ITALIC),
+            Some(&DocTag {
+                italic: Some(true),
+                color: Some(DocTagColor::Single("blue".to_string())),
+                ..Default::default()
+            })
+        );
+        // not affected
+        assert_eq!(config.tags.get(TAG_CODE).unwrap().monospace, Some(true));
+    }
+}
@@ -9,10 +9,12 @@
 //! as well as the entry point
 //!
 //! # Work
-//! 1. Loading the entry point config (project.yaml). If the entry point contains redirection
+//! 1. Loading the lock file (celer.lock) if the project has one, which is used
+//!    to check the remote resources loaded by the project
+//! 2. Loading the entry point config (project.yaml). If the entry point contains redirection
 //!    through `entrypoints` property, it finds the correct entry point config to load.
-//! 2. Build the configuration object
-//! 3. Optimize configuration and compile plugins to be cached
+//! 3. Build the configuration object
+//! 4. Optimize configuration and compile plugins to be cached
 //!
 //! # Output
 //! The output of this phase is a [`PreparedContext`] object that can be used to create
@@ -27,11 +29,11 @@ use serde_json::{Map, Value};
 
 use crate::env::{join_futures, RefCounted};
 use crate::json::{Cast, Coerce, RouteBlob};
-use crate::lang::Preset;
+use crate::lang::{optimize_presets, Preset};
 use crate::macros::derive_wasm;
 use crate::plugin;
 use crate::prop;
-use crate::res::{Loader, ResPath, Resource, Use, ValidUse};
+use crate::res::{Loader, ResLock, ResPath, Resource, Use, ValidUse};
 use crate::util::StringMap;
 
 mod error;
@@ -42,6 +44,8 @@ mod config;
 pub use config::*;
 mod route;
 pub use route::*;
+mod vendor;
+pub use vendor::*;
 
 /// Output of the prep phase
 #[derive(Debug, Clone)]
@@ -71,7 +75,8 @@ where
     /// Hydrate a context from its data with a loader
     pub fn from_data(data: RefCounted<PrepCtxData>, loader: RefCounted<L>) -> Self {
         Self {
-            project_res: Resource::new(data.res_path.clone(), loader),
+            project_res: Resource::new(data.res_path.clone(), loader)
+                .with_lock(RefCounted::clone(&data.lock)),
             data,
         }
     }
@@ -95,6 +100,9 @@ pub struct PrepCtxData {
     pub start_time: Instant,
     pub setting: Setting,
     pub plugins: Vec<plugin::Instance>,
+    /// Lock for the remote resources. It also records the hashes of the
+    /// remote resources loaded, so the lock file can be generated
+    pub lock: RefCounted<ResLock>,
     // TODO #173: add a option to make ContextBuilder output a dependency list for PreparedContext
 }
 
@@ -201,6 +209,8 @@ where
     /// Load the project and parse config and (optionally) route
     pub async fn build_context(mut self) -> PrepResult<PrepCtx<L>> {
         let start_time = Instant::now();
+        let lock = RefCounted::new(ResLock::load(&self.project_res).await?);
+        self.project_res = self.project_res.with_lock(RefCounted::clone(&lock));
         let mut project = self.resolve_entry_point().await?;
         let metadata = self.load_metadata(&mut project)?;
 
@@ -237,18 +247,8 @@ where
                 splits: prep_config.splits,
                 stats: Default::default(),
             };
-            // optimize presets
-            let mut unoptimized_presets = prep_config.presets;
-            let mut optimized_presets = BTreeMap::new();
-            while let Some((name, mut preset)) = unoptimized_presets.pop_first() {
-                preset
-                    .optimize(&mut unoptimized_presets, &mut optimized_presets)
-                    .await;
-                optimized_presets.insert(name, preset);
-            }
-
             let meta = CompilerMetadata {
-                presets: optimized_presets,
+                presets: optimize_presets(prep_config.presets).await,
                 default_icon_priority: prep_config.default_icon_priority,
             };
 
@@ -268,6 +268,7 @@ where
                 start_time,
                 setting: self.setting,
                 plugins,
+                lock,
             }),
             project_res: self.project_res,
         })
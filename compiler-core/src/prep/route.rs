@@ -17,6 +17,8 @@ use super::Setting;
 /// - If the `use` is inside an array, and the `use` resolves to an inner array, the inner array is injected
 ///   into the outer array
 /// - Otherwise, the resolved value replaces the `use`
+/// - A directory or glob `use` resolves to an array of the values of the files, sorted by path,
+///   with the inner arrays injected the same way
 ///
 /// If a `use` cannot be resolved, the error is placed inside the RouteBlob to defer it to the
 /// compiler
@@ -149,6 +151,9 @@ async fn build_route_from_use<L>(
 where
     L: Loader,
 {
+    if use_prop.is_list() {
+        return build_route_from_list_use(resource, use_prop, use_depth, setting).await;
+    }
     // Resolve the resource
     let inner_resource = match resource.resolve(&use_prop) {
         Ok(r) => r,
@@ -169,3 +174,91 @@ where
     )
     .await
 }
+
+/// Resolve a directory or glob `use` in the route, which becomes an array of the values
+/// of all the files
+async fn build_route_from_list_use<L>(
+    // The resource that contains the `use`
+    resource: &Resource<'_, L>,
+    use_prop: ValidUse,
+    use_depth: usize,
+    setting: &Setting,
+) -> RouteBlob
+where
+    L: Loader,
+{
+    let inner_resources = match resource.resolve_list(&use_prop).await {
+        Ok(r) => r,
+        Err(e) => return e.into(),
+    };
+    let mut output = vec![];
+    for inner_resource in inner_resources {
+        let result = match inner_resource.load_structured().await {
+            Ok(data) => {
+                build_route_internal(&inner_resource, data, use_depth + 1, 0, setting).await
+            }
+            Err(e) => e.into(),
+        };
+        match result {
+            RouteBlob::Array(arr) => output.extend(arr),
+            other => output.push(other),
+        }
+    }
+
+    RouteBlob::Array(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::env::RefCounted;
+    use crate::macros::async_trait;
+    use crate::res::{ResPath, ResResult};
+
+    struct TestLoader;
+    #[async_trait(auto)]
+    impl Loader for TestLoader {
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            let content: &[u8] = match path.to_string().as_str() {
+                "sections/a.yaml" => b"- a1\n- a2\n",
+                "sections/b.yaml" => b"b\n",
+                _ => return Err(ResError::FailToLoadFile(path.to_string(), "404".into())),
+            };
+            Ok(RefCounted::from(content))
+        }
+
+        async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+            match path.to_string().as_str() {
+                "sections" => Ok(vec![
+                    "b.yaml".to_string(),
+                    "notes.txt".to_string(),
+                    "a.yaml".to_string(),
+                ]),
+                _ => Err(ResError::FailToListDir(path.to_string(), "404".into())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_glob_use_spliced_sorted() {
+        let project_res = Resource::new(
+            ResPath::new_local_unchecked("project.yaml"),
+            RefCounted::new(TestLoader),
+        );
+        let route = json!(["start", { "use": "./sections/*.yaml" }, "end"]);
+        let result = build_route(&project_res, route, &Setting::default()).await;
+        assert_eq!(
+            result,
+            RouteBlob::Array(vec![
+                RouteBlob::Prim(json!("start")),
+                RouteBlob::Prim(json!("a1")),
+                RouteBlob::Prim(json!("a2")),
+                RouteBlob::Prim(json!("b")),
+                RouteBlob::Prim(json!("end")),
+            ])
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Vendoring the remote dependencies of a project
+
+use std::collections::BTreeMap;
+
+use crate::env::RefCounted;
+use crate::res::{Loader, Resource, VendorRecorder};
+
+use super::{ContextBuilder, PrepResult};
+
+/// Remote resources to be written to the vendor directory, keyed by the path in the project
+pub type VendorFiles = BTreeMap<String, RefCounted<[u8]>>;
+
+impl<L> ContextBuilder<L>
+where
+    L: Loader,
+{
+    /// Load the project and record every remote resource needed to compile it,
+    /// which can be written to the vendor directory for offline compilation.
+    ///
+    /// This goes through the same steps as building the context with the route built,
+    /// so the configs, route, icons and plugin scripts are all included.
+    pub async fn vendor(self) -> PrepResult<VendorFiles> {
+        let recorder = RefCounted::new(VendorRecorder::new(RefCounted::clone(
+            &self.project_res.loader,
+        )));
+        let project_res = Resource::new(
+            self.project_res.path().clone(),
+            RefCounted::clone(&recorder),
+        );
+        ContextBuilder::new(self.source, project_res)
+            .setting(self.setting)
+            .entry_point(self.entry_point)
+            .with_route_built()
+            .build_context()
+            .await?;
+
+        Ok(recorder.take_resources())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::macros::async_trait;
+    use crate::res::{ResError, ResPath, ResResult};
+
+    struct TestLoader;
+    #[async_trait(auto)]
+    impl Loader for TestLoader {
+        async fn load_raw(&self, path: &ResPath) -> ResResult<RefCounted<[u8]>> {
+            let content: &[u8] = match path.to_string().as_str() {
+                "project.yaml" => {
+                    b"config:\n- use: foo/bar/config.yaml\nroute:\n  use: ./route.yaml\n"
+                }
+                "route.yaml" => b"- use: foo/bar/route.yaml\n",
+                "https://raw.githubusercontent.com/foo/bar/main/config.yaml" => {
+                    b"icons:\n  a:\n    use: ./a.png\n"
+                }
+                "https://raw.githubusercontent.com/foo/bar/main/route.yaml" => b"- section\n",
+                "https://raw.githubusercontent.com/foo/bar/main/a.png" => b"png",
+                _ => return Err(ResError::NotFound(path.to_string())),
+            };
+            Ok(RefCounted::from(content))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vendor() {
+        let project_res = Resource::new(
+            ResPath::new_local_unchecked("project.yaml"),
+            RefCounted::new(TestLoader),
+        );
+        let files = ContextBuilder::new("test".to_string(), project_res)
+            .vendor()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec![
+                "vendor/raw.githubusercontent.com/foo/bar/main/a.png",
+                "vendor/raw.githubusercontent.com/foo/bar/main/config.yaml",
+                "vendor/raw.githubusercontent.com/foo/bar/main/route.yaml",
+            ]
+        );
+    }
+}
@@ -2,6 +2,7 @@ async function __initWorker(HANDLERS) {
     await wasm_bindgen("/celerc/celercwasm_bg.wasm");
 
     const pendingFiles = {};
+    const pendingDirs = {};
 
     function workerOnReady() {
         wasm_bindgen.init(
@@ -20,6 +21,12 @@ async function __initWorker(HANDLERS) {
             async (url) => {
                 for (let i = 0; i < 3; i++) {
                     const response = await fetch(url);
+                    if (response.status === 404) {
+                        // not retrying, since the resource does not exist
+                        const error = new Error(`not found: ${url}`);
+                        error.notFound = true;
+                        throw error;
+                    }
                     if (!response.ok) {
                         await new Promise((resolve) => {
                             setTimeout(resolve, 1000);
@@ -30,6 +37,14 @@ async function __initWorker(HANDLERS) {
                     return new Uint8Array(data);
                 }
                 throw new Error(`failed to fetch ${url}`);
+            },
+            (path) => {
+                return new Promise((resolve, reject) => {
+                    setTimeout(() => {
+                        pendingDirs[path] = [resolve, reject];
+                        self.postMessage(["list_dir", undefined, [path]]);
+                    }, 0);
+                });
             }
         );
     }
@@ -55,6 +70,17 @@ async function __initWorker(HANDLERS) {
             setTimeout(() => handler(event.data[3]), 0);
             return;
         }
+        if (msgId === "dir") {
+            // ["dir", 0, path, entries]
+            // ["dir", 1, path, FsError]
+            if (!pendingDirs[args]) {
+                return;
+            }
+            const handler = pendingDirs[args][funcId];
+            delete pendingDirs[args];
+            setTimeout(() => handler(event.data[3]), 0);
+            return;
+        }
         try {
             const handler = HANDLERS[funcId];
             const result = await handler(...args);
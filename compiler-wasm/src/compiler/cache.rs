@@ -4,14 +4,23 @@ use log::info;
 
 use celerc::PrepCtx;
 
-use crate::loader::{self, LoadFileOutput, LoaderInWasm};
+use crate::loader::{self, LoadFileOutput, ProjectLoader};
 
 thread_local! {
-    static CACHED_COMPILER_CONTEXT: RefCell<Option<PrepCtx<LoaderInWasm>>> = const { RefCell::new(None) };
+    static CACHED_COMPILER_CONTEXT: RefCell<Option<PrepCtx<ProjectLoader>>> = const { RefCell::new(None) };
+}
+
+/// Get the lock file generated from the cached context, with the hashes
+/// of the remote resources loaded
+pub fn get_lock_file() -> Option<String> {
+    CACHED_COMPILER_CONTEXT.with_borrow(|x| {
+        x.as_ref()
+            .map(|prep_ctx| prep_ctx.lock.to_lock_file().to_string_pretty())
+    })
 }
 
 /// Guard for acquiring the cached context and takes care of releasing it
-pub struct CachedContextGuard(Option<PrepCtx<LoaderInWasm>>);
+pub struct CachedContextGuard(Option<PrepCtx<ProjectLoader>>);
 impl CachedContextGuard {
     /// Acquire the cached context if it's valid
     pub async fn acquire(entry_path: Option<&String>) -> Option<Self> {
@@ -46,7 +55,7 @@ impl CachedContextGuard {
     }
 
     /// Put a new context into the cache upon drop
-    pub fn new(prep_ctx: PrepCtx<LoaderInWasm>) -> Self {
+    pub fn new(prep_ctx: PrepCtx<ProjectLoader>) -> Self {
         CachedContextGuard(Some(prep_ctx))
     }
 }
@@ -55,8 +64,8 @@ impl Drop for CachedContextGuard {
         CACHED_COMPILER_CONTEXT.with_borrow_mut(|x| *x = self.0.take());
     }
 }
-impl AsRef<PrepCtx<LoaderInWasm>> for CachedContextGuard {
-    fn as_ref(&self) -> &PrepCtx<LoaderInWasm> {
+impl AsRef<PrepCtx<ProjectLoader>> for CachedContextGuard {
+    fn as_ref(&self) -> &PrepCtx<ProjectLoader> {
         self.0.as_ref().unwrap()
     }
 }
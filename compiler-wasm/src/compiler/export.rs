@@ -5,7 +5,7 @@ use celerc::pack::PackError;
 use celerc::plugin;
 use celerc::{Compiler, ExpoDoc, ExportRequest, PrepCtx};
 
-use crate::loader::LoaderInWasm;
+use crate::loader::ProjectLoader;
 
 use super::CachedContextGuard;
 
@@ -46,7 +46,7 @@ pub async fn export_document(
 }
 
 async fn export_in_context(
-    prep_ctx: &PrepCtx<LoaderInWasm>,
+    prep_ctx: &PrepCtx<ProjectLoader>,
     start_time: Option<Instant>,
     plugin_options: Option<plugin::Options>,
     req: ExportRequest,
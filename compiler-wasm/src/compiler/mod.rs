@@ -1,6 +1,7 @@
 use celerc::lang::DocDiagnostic;
 use celerc::prep::PrepResult;
 use instant::Instant;
+use js_sys::{Map, Uint8Array};
 use log::{error, info};
 use wasm_bindgen::prelude::*;
 
@@ -9,9 +10,10 @@ use celerc::plugin;
 use celerc::{CompDoc, CompileContext, Compiler, ContextBuilder, ExecContext, PrepCtx};
 
 use crate::interop::OpaqueExpoContext;
-use crate::loader::LoaderInWasm;
+use crate::loader::ProjectLoader;
 
 mod cache;
+pub use cache::get_lock_file;
 use cache::CachedContextGuard;
 mod export;
 pub use export::export_document;
@@ -57,8 +59,8 @@ pub async fn compile_document(
     compile_in_context(guard.as_ref(), None, plugin_options).await
 }
 
-pub async fn new_context(entry_path: Option<String>) -> PrepResult<PrepCtx<LoaderInWasm>> {
-    let mut context_builder = new_context_builder();
+pub async fn new_context(entry_path: Option<String>) -> PrepResult<PrepCtx<ProjectLoader>> {
+    let mut context_builder = new_context_builder().await;
     if entry_path.is_some() {
         context_builder = context_builder.entry_point(entry_path);
     }
@@ -66,7 +68,7 @@ pub async fn new_context(entry_path: Option<String>) -> PrepResult<PrepCtx<Loade
 }
 
 async fn compile_in_context(
-    prep_ctx: &PrepCtx<LoaderInWasm>,
+    prep_ctx: &PrepCtx<ProjectLoader>,
     start_time: Option<Instant>,
     plugin_options: Option<plugin::Options>,
 ) -> Result<OpaqueExpoContext, JsValue> {
@@ -95,9 +97,31 @@ async fn compile_with_compiler(compiler: Compiler<'_>) -> Result<OpaqueExpoConte
     OpaqueExpoContext::try_from(exec_ctx.prepare_exports().await)
 }
 
+/// Load the remote resources needed to compile the document
+pub async fn vendor_document(entry_path: Option<String>) -> Result<Map, JsValue> {
+    let mut context_builder = new_context_builder().await;
+    if entry_path.is_some() {
+        context_builder = context_builder.entry_point(entry_path);
+    }
+    let files = match context_builder.vendor().await {
+        Ok(x) => x,
+        Err(e) => {
+            let message = format!("Failed to vendor the project: {e}");
+            error!("{message}");
+            return Err(JsValue::from(message));
+        }
+    };
+    info!("vendored {} files", files.len());
+    let map = Map::new();
+    for (path, bytes) in files {
+        map.set(&JsValue::from(path), &Uint8Array::from(bytes.as_ref()));
+    }
+    Ok(map)
+}
+
 /// Create a context builder that corresponds to the root project.yaml
-pub fn new_context_builder() -> ContextBuilder<LoaderInWasm> {
+pub async fn new_context_builder() -> ContextBuilder<ProjectLoader> {
     let source = "Web Editor".to_string();
-    let project_res = super::get_root_project_resource();
+    let project_res = super::get_root_project_resource().await;
     ContextBuilder::new(source, project_res)
 }
@@ -1,18 +1,18 @@
-use js_sys::Function;
+use js_sys::{Function, Map};
 use log::info;
 use wasm_bindgen::prelude::*;
 
 use celerc::env::RefCounted;
 use celerc::plugin::OptionsRaw as PluginOptionsRaw;
 use celerc::prep::EntryPointsSorted;
-use celerc::res::{ResPath, Resource};
+use celerc::res::{ResPath, Resource, VendorLoader};
 use celerc::{ExpoDoc, ExportRequest};
 
 mod interop;
 use interop::OpaqueExpoContext;
 mod compiler;
 mod loader;
-use loader::LoaderInWasm;
+use loader::{LoaderInWasm, ProjectLoader};
 mod logger;
 mod plugin;
 
@@ -25,10 +25,11 @@ pub fn init(
     error_fn: Function,
     load_file: Function,
     load_url: Function,
+    list_dir: Function,
 ) {
     let _ = logger::bind(info_fn, warn_fn, error_fn);
     info!("initializing compiler...");
-    loader::bind(load_file, load_url);
+    loader::bind(load_file, load_url, list_dir);
     celerc::env::site::set_origin(&site_origin);
     let _ = celerc::env::global_loader_factory::set(RefCounted::new(LoaderInWasm));
 
@@ -40,7 +41,7 @@ pub fn init(
 /// If there is any error, this returns 0 entry points
 #[wasm_bindgen]
 pub async fn get_entry_points() -> Result<EntryPointsSorted, JsValue> {
-    let context_builder = compiler::new_context_builder();
+    let context_builder = compiler::new_context_builder().await;
     let entry_points = match context_builder.get_entry_points().await {
         Ok(x) => x.path_only().into(),
         Err(_) => Default::default(),
@@ -69,6 +70,26 @@ pub async fn export_document(
     Ok(compiler::export_document(entry_path, use_cache, req).await)
 }
 
+/// Load the remote resources needed to compile the document, so they
+/// can be saved to the vendor directory for offline compilation
+///
+/// Returns a map of the path in the project to the content of the file
+#[wasm_bindgen]
+#[inline]
+pub async fn vendor_document(entry_path: Option<String>) -> Result<Map, JsValue> {
+    compiler::vendor_document(entry_path).await
+}
+
+/// Get the content of the lock file (celer.lock) for the last compiled document,
+/// which pins the content of the remote resources loaded
+///
+/// Returns `None` if there is no compiled document
+#[wasm_bindgen]
+#[inline]
+pub async fn get_lock_file() -> Option<String> {
+    compiler::get_lock_file()
+}
+
 /// Set user plugin options
 #[wasm_bindgen]
 #[inline]
@@ -76,9 +97,16 @@ pub async fn set_plugin_options(options: Option<PluginOptionsRaw>) {
     plugin::set_plugin_options(options).await
 }
 
-pub fn get_root_project_resource() -> Resource<'static, LoaderInWasm> {
+/// Get the resource of the root project.yaml
+///
+/// Remote resources are loaded from the vendor directory first if the project has one
+pub async fn get_root_project_resource() -> Resource<'static, ProjectLoader> {
+    let loader = VendorLoader::detect(RefCounted::new(LoaderInWasm)).await;
+    if loader.is_enabled() {
+        info!("loading remote resources from the vendor directory");
+    }
     Resource::new(
         ResPath::Local("project.yaml".into()),
-        RefCounted::new(LoaderInWasm),
+        RefCounted::new(loader),
     )
 }
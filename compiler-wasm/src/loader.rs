@@ -12,7 +12,7 @@ use wasm_bindgen::prelude::*;
 
 use celerc::env::{yield_budget, RefCounted};
 use celerc::macros::async_trait;
-use celerc::res::{Loader, LoaderFactory, ResError, ResPath, ResResult};
+use celerc::res::{Loader, LoaderFactory, ResError, ResPath, ResResult, VendorLoader};
 
 use crate::interop::{self, JsIntoFuture};
 use crate::logger;
@@ -28,22 +28,37 @@ thread_local! {
     /// - [false] if the file was not modified
     ///
     /// The promise is rejected if the file could not be loaded.
+    /// The error has `notFound: true` if the file does not exist
     static LOAD_FILE: RefCell<Function> = RefCell::new(interop::stub_function());
 
     /// Callback function to ask JS to load resource from an URL
     ///
     /// Takes in a string (url) as argument.
-    /// Returns a promise that resolves to a Uint8Array that could throw
+    /// Returns a promise that resolves to a Uint8Array that could throw.
+    /// The error has `notFound: true` if the server responded with 404
     static LOAD_URL: RefCell<Function> = RefCell::new(interop::stub_function());
+
+    /// Callback function to ask JS to list the entries in a directory
+    ///
+    /// Takes in a string (path) as argument.
+    /// Returns a promise that resolves to an array of entry names,
+    /// where directory names end with `/`
+    ///
+    /// The promise is rejected if the directory could not be listed.
+    static LIST_DIR: RefCell<Function> = RefCell::new(interop::stub_function());
 }
 
-pub fn bind(load_file: Function, load_url: Function) {
+pub fn bind(load_file: Function, load_url: Function, list_dir: Function) {
     LOAD_FILE.replace(load_file);
     LOAD_URL.replace(load_url);
+    LIST_DIR.replace(list_dir);
 }
 
 pub struct LoaderInWasm;
 
+/// Loader for the project, which loads remote resources from the vendor directory first
+pub type ProjectLoader = VendorLoader<LoaderInWasm>;
+
 impl LoaderFactory for LoaderInWasm {
     fn create_loader(&self) -> ResResult<RefCounted<dyn Loader>> {
         Ok(RefCounted::from(Self))
@@ -66,6 +81,19 @@ impl Loader for LoaderInWasm {
         yield_budget(1).await;
         result.map(RefCounted::from)
     }
+
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        match path {
+            ResPath::Local(path) => {
+                info!("listing local {path}");
+                list_dir(path.as_ref()).await
+            }
+            ResPath::Remote(prefix, path) => Err(ResError::FailToListDir(
+                format!("{prefix}{path}"),
+                "Remote directories cannot be listed".to_string(),
+            )),
+        }
+    }
 }
 
 pub async fn load_url(url: &str) -> ResResult<Vec<u8>> {
@@ -94,6 +122,7 @@ pub async fn load_url(url: &str) -> ResResult<Vec<u8>> {
 
     match result {
         Ok(bytes) => Ok(bytes.to_vec()),
+        Err(e) if is_not_found(&e) => Err(ResError::NotFound(url.to_string())),
         Err(e) => {
             logger::raw_error(&e);
             Err(ResError::FailToLoadUrl(
@@ -118,6 +147,35 @@ pub async fn load_file(path: &str) -> ResResult<Vec<u8>> {
     }
 }
 
+/// List a directory using JS binding
+pub async fn list_dir(path: &str) -> ResResult<Vec<String>> {
+    // this is essentially try { Ok(await list_dir(path)) } catch (e) { Err(e) }
+    let result = async {
+        LIST_DIR
+            .with_borrow(|f| f.call1(&JsValue::UNDEFINED, &JsValue::from(path)))?
+            .into_future()
+            .await?
+            .dyn_into::<Array>()
+    }
+    .await;
+
+    match result {
+        Ok(entries) => Ok(entries.iter().filter_map(|x| x.as_string()).collect()),
+        Err(e) => {
+            if let Ok(value) = Reflect::get(&e, &JsValue::from("message")) {
+                if let Some(s) = value.as_string() {
+                    return Err(ResError::FailToListDir(path.to_string(), s));
+                }
+            }
+            logger::raw_error(&e);
+            Err(ResError::FailToListDir(
+                path.to_string(),
+                "JavaScript Error".to_string(),
+            ))
+        }
+    }
+}
+
 #[inline]
 pub async fn load_file_check_changed(path: &str) -> ResResult<LoadFileOutput> {
     load_file_internal(path, true).await
@@ -158,6 +216,7 @@ async fn load_file_internal(path: &str, check_changed: bool) -> ResResult<LoadFi
 
     match result {
         Ok(output) => Ok(output),
+        Err(e) if is_not_found(&e) => Err(ResError::NotFound(path.to_string())),
         Err(e) => {
             if let Ok(value) = Reflect::get(&e, &JsValue::from("message")) {
                 if let Some(s) = value.as_string() {
@@ -172,3 +231,11 @@ async fn load_file_internal(path: &str, check_changed: bool) -> ResResult<LoadFi
         }
     }
 }
+
+/// Check if the error from JS is because the file or URL does not exist,
+/// which is marked with the `notFound` property
+fn is_not_found(error: &JsValue) -> bool {
+    Reflect::get(error, &JsValue::from("notFound"))
+        .map(|x| x.is_truthy())
+        .unwrap_or_default()
+}
@@ -19,7 +19,10 @@ pub async fn set_plugin_options(options: Option<plugin::OptionsRaw>) {
     };
 
     info!("setting plugin options");
-    match options.parse(&super::get_root_project_resource()).await {
+    match options
+        .parse(&super::get_root_project_resource().await)
+        .await
+    {
         Ok(options) => {
             let _ = PLUGIN_OPTIONS.with(|x| x.replace(Ok(Some(options))));
             info!("plugin options set successfully");
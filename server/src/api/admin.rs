@@ -0,0 +1,98 @@
+//! The `/admin` API endpoints, authenticated with the admin token
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{routing, Json, Router};
+use serde::Deserialize;
+use tracing::{error, warn};
+
+use celerc::res;
+
+use crate::compiler;
+
+pub fn init_api(token: &str) -> Router {
+    // compare the hashes so the time taken does not depend on the token
+    let token_hash: Arc<str> = Arc::from(res::hash_content(token.as_bytes()));
+    Router::new()
+        .route("/purge", routing::post(purge))
+        .with_state(token_hash)
+}
+
+/// Request body for purging the cache of one project
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PurgeRequest {
+    owner: String,
+    repo: String,
+    reference: String,
+}
+
+async fn purge(State(token_hash): State<Arc<str>>, headers: HeaderMap, body: Bytes) -> Response {
+    if !is_authorized(&token_hash, &headers) {
+        warn!("Unauthorized request to admin endpoint");
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    // purge everything if there is no body
+    if body.is_empty() {
+        return Json(compiler::purge_all().await).into_response();
+    }
+    let req: PurgeRequest = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Failed to parse purge request: {e}");
+            return (StatusCode::BAD_REQUEST, "Invalid request body").into_response();
+        }
+    };
+    match compiler::purge_project(&req.owner, &req.repo, &req.reference).await {
+        Ok(result) => Json(result).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    }
+}
+
+/// Check the `Authorization: Bearer <token>` header against the hash of the admin token
+pub fn is_authorized(token_hash: &str, headers: &HeaderMap) -> bool {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match token {
+        Some(token) => res::hash_content(token.trim().as_bytes()) == token_hash,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use axum::http::HeaderValue;
+
+    fn auth_headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let token_hash = res::hash_content(b"token");
+        assert!(is_authorized(&token_hash, &auth_headers("Bearer token")));
+        assert!(is_authorized(&token_hash, &auth_headers("Bearer token ")));
+    }
+
+    #[test]
+    fn test_is_authorized_rejected() {
+        let token_hash = res::hash_content(b"token");
+        assert!(!is_authorized(&token_hash, &HeaderMap::new()));
+        assert!(!is_authorized(&token_hash, &auth_headers("Bearer other")));
+        assert!(!is_authorized(&token_hash, &auth_headers("Bearer ")));
+        assert!(!is_authorized(&token_hash, &auth_headers("token")));
+        assert!(!is_authorized(&token_hash, &auth_headers("Basic token")));
+        assert!(!is_authorized(&token_hash, &auth_headers("bearer token")));
+        assert!(!is_authorized(&token_hash, &auth_headers("Bearer token2")));
+    }
+}
@@ -0,0 +1,41 @@
+//! Utilities for the endpoints that accept an uploaded project bundle
+
+use std::sync::Arc;
+
+use base64::Engine;
+use tracing::error;
+
+use crate::compiler::{self, BundleLoader, CompileBudget, Credential};
+
+/// Maximum size of the request body with the bundle. The bundle is base64 encoded,
+/// so the maximum size of the bundle itself is about 3/4 of this
+pub const MAX_BUNDLE_REQUEST_SIZE: usize = 1024 * 1024 * 28; // 28 MB
+
+/// Decode the base64 encoded bundle and create a loader for it
+///
+/// Decoding and extracting are blocking, so they run on the blocking thread pool.
+/// This should be run with [`compiler::run_with_budget`], so extracting is stopped
+/// if it takes too long
+pub async fn load_bundle(
+    bundle: String,
+    credential: Option<Credential>,
+    budget: Arc<CompileBudget>,
+) -> Result<BundleLoader, String> {
+    let task = tokio::task::spawn_blocking(move || {
+        let bytes = match base64::engine::general_purpose::STANDARD.decode(bundle) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to decode bundle from base64: {e}");
+                return Err("Invalid bundle encoding".to_string());
+            }
+        };
+        compiler::BundleLoader::new(&bytes, credential, Some(budget))
+    });
+    match task.await {
+        Ok(result) => result,
+        Err(e) => {
+            error!("Failed to join the task extracting the bundle: {e}");
+            Err("Failed to extract bundle".to_string())
+        }
+    }
+}
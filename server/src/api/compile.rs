@@ -1,9 +1,16 @@
 //! The `/compile` API endpoint.
 
-use axum::extract::Path;
-use axum::http::HeaderMap;
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, Path};
+use axum::http::{header as http_header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing;
 use axum::{Json, Router};
+use celerc::plugin;
+use celerc::res::{self, Loader};
+use celerc::PrepCtx;
 use instant::Instant;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -12,10 +19,15 @@ use tower_http::compression::CompressionLayer;
 
 use crate::compiler;
 
-use super::header;
+use super::{bundle, header};
 
 pub fn init_api() -> Router {
     Router::new()
+        .route(
+            "/",
+            routing::post(compile_bundle)
+                .layer(DefaultBodyLimit::max(bundle::MAX_BUNDLE_REQUEST_SIZE)),
+        )
         .route(
             "/:owner/:repo/:reference",
             routing::get(compile_owner_repo_ref),
@@ -34,53 +46,233 @@ pub enum CompileResponse {
     Failure(String),
 }
 
+/// Request body for compiling an uploaded project bundle
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileBundleRequest {
+    /// Base64 encoded zip, tar or gzipped tar archive of the project
+    bundle: String,
+    /// Path to the entry point in the project, same as the `path` for the GET endpoint
+    #[serde(default)]
+    entry_path: Option<String>,
+    #[serde(default)]
+    plugin_options: Option<plugin::OptionsRaw>,
+}
+
 async fn compile_owner_repo_ref(
     Path((owner, repo, reference)): Path<(String, String, String)>,
     headers: HeaderMap,
-) -> Json<CompileResponse> {
+) -> Response {
     let plugin_options = match header::get_plugin_options(&headers) {
         Ok(v) => v,
-        Err(e) => return Json(CompileResponse::Failure(e)),
+        Err(e) => return Json(CompileResponse::Failure(e)).into_response(),
+    };
+    let token = match header::get_github_token(&headers) {
+        Ok(v) => v,
+        Err(e) => return Json(CompileResponse::Failure(e)).into_response(),
     };
-    let response = compile_internal(&owner, &repo, None, &reference, &plugin_options).await;
-    Json(response)
+    let credential = compiler::get_credential(token.as_deref());
+    let response =
+        compile_internal(&owner, &repo, None, &reference, &plugin_options, credential).await;
+    into_cached_response(response, &headers, token.is_some())
 }
 
 async fn compile_owner_repo_ref_path(
     Path((owner, repo, reference, path)): Path<(String, String, String, String)>,
     headers: HeaderMap,
-) -> Json<CompileResponse> {
+) -> Response {
     let plugin_options = match header::get_plugin_options(&headers) {
         Ok(v) => v,
+        Err(e) => return Json(CompileResponse::Failure(e)).into_response(),
+    };
+    let token = match header::get_github_token(&headers) {
+        Ok(v) => v,
+        Err(e) => return Json(CompileResponse::Failure(e)).into_response(),
+    };
+    let credential = compiler::get_credential(token.as_deref());
+    let response = compile_internal(
+        &owner,
+        &repo,
+        Some(&path),
+        &reference,
+        &plugin_options,
+        credential,
+    )
+    .await;
+    into_cached_response(response, &headers, token.is_some())
+}
+
+/// Create the response with an ETag from the hash of the output, so clients that
+/// already have the same output get `304 Not Modified` instead of downloading it again
+///
+/// Responses for requests with their own token are only cached by the client
+fn into_cached_response(response: CompileResponse, headers: &HeaderMap, private: bool) -> Response {
+    let output = match &response {
+        CompileResponse::Success(output) => output,
+        CompileResponse::Failure(_) => return Json(response).into_response(),
+    };
+    let hash = res::hash_content(&output_for_etag(output));
+    let body = match serde_json::to_vec(&response) {
+        Ok(body) => body,
+        Err(e) => return Json(CompileResponse::Failure(e.to_string())).into_response(),
+    };
+    let etag = format!("\"{}\"", hash.strip_prefix("sha256-").unwrap_or(&hash));
+    let etag = match HeaderValue::from_str(&etag) {
+        Ok(etag) => etag,
+        Err(_) => return Json(response).into_response(),
+    };
+    let cache_control = HeaderValue::from_static(if private {
+        "private, no-cache"
+    } else {
+        "no-cache"
+    });
+    let vary = HeaderValue::from_static("Celer-Plugin-Options, Celer-GitHub-Token");
+    let response_headers = [
+        (http_header::ETAG, etag.clone()),
+        (http_header::CACHE_CONTROL, cache_control),
+        (http_header::VARY, vary),
+    ];
+    if is_etag_match(headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, response_headers).into_response();
+    }
+    let content_type = HeaderValue::from_static("application/json");
+    (
+        response_headers,
+        [(http_header::CONTENT_TYPE, content_type)],
+        body,
+    )
+        .into_response()
+}
+
+/// Serialize the output for computing the ETag, without the stats added by the metrics plugin,
+/// since the compile time is different every time even if the output is the same
+fn output_for_etag(output: &Value) -> Vec<u8> {
+    let mut output = output.clone();
+    if let Some(stats) = output
+        .pointer_mut("/execDoc/project/stats")
+        .and_then(Value::as_object_mut)
+    {
+        for key in plugin::METRICS_STATS {
+            stats.remove(*key);
+        }
+    }
+    serde_json::to_vec(&output).unwrap_or_default()
+}
+
+/// Check if the If-None-Match header in the request matches the ETag
+fn is_etag_match(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let if_none_match = match headers
+        .get(http_header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(v) => v,
+        None => return false,
+    };
+    let etag = etag.to_str().unwrap_or_default();
+    // weak comparison, as the response could be compressed
+    if_none_match
+        .split(',')
+        .map(|x| x.trim())
+        .any(|x| x == "*" || x.strip_prefix("W/").unwrap_or(x) == etag)
+}
+
+async fn compile_bundle(
+    headers: HeaderMap,
+    body: Result<Json<CompileBundleRequest>, JsonRejection>,
+) -> Json<CompileResponse> {
+    let start_time = Instant::now();
+    let req = match body {
+        Ok(Json(req)) => req,
+        Err(e) => return Json(CompileResponse::Failure(e.body_text())),
+    };
+    let credential = match header::get_github_token(&headers) {
+        Ok(v) => compiler::get_credential(v.as_deref()),
         Err(e) => return Json(CompileResponse::Failure(e)),
     };
-    let response = compile_internal(&owner, &repo, Some(&path), &reference, &plugin_options).await;
-    Json(response)
+    let budget = compiler::CompileBudget::new();
+    let response = compiler::run_with_budget(&budget, async {
+        let loader = match bundle::load_bundle(req.bundle, credential, Arc::clone(&budget)).await {
+            Ok(loader) => loader,
+            Err(e) => return CompileResponse::Failure(e),
+        };
+        let prep_ctx = match compiler::new_bundle_context(loader, req.entry_path).await {
+            Ok(ctx) => ctx,
+            Err(e) => return CompileResponse::Failure(e.to_string()),
+        };
+
+        let plugin_options = match req.plugin_options {
+            None => None,
+            Some(options) => {
+                match compiler::parse_plugin_options_raw(options, &prep_ctx.project_res).await {
+                    Ok(options) => Some(options),
+                    Err(e) => return CompileResponse::Failure(e),
+                }
+            }
+        };
+
+        compile_with_context(&prep_ctx, start_time, plugin_options, &budget).await
+    })
+    .await;
+    Json(response.unwrap_or_else(CompileResponse::Failure))
 }
 
-async fn compile_internal(
+pub(super) async fn compile_internal(
     owner: &str,
     repo: &str,
     path: Option<&str>,
     reference: &str,
     plugin_options_json: &str,
+    credential: Option<compiler::Credential>,
 ) -> CompileResponse {
     let start_time = Instant::now();
-    let prep_ctx = match compiler::get_context(owner, repo, path, reference).await {
-        Ok(ctx) => ctx,
-        Err(e) => return CompileResponse::Failure(e.to_string()),
-    };
+    if let Err(e) = compiler::check_repo_access(owner, repo) {
+        return CompileResponse::Failure(e);
+    }
+    let budget = compiler::CompileBudget::new();
+    let response = compiler::run_with_budget(&budget, async {
+        let prep_ctx = match compiler::get_context(
+            owner,
+            repo,
+            path,
+            reference,
+            credential,
+            Some(Arc::clone(&budget)),
+        )
+        .await
+        {
+            Ok(ctx) => ctx,
+            Err(e) => return CompileResponse::Failure(e.to_string()),
+        };
 
-    let plugin_options = if plugin_options_json.is_empty() {
-        None
-    } else {
-        match compiler::parse_plugin_options(plugin_options_json, &prep_ctx.project_res).await {
-            Ok(options) => Some(options),
-            Err(e) => return CompileResponse::Failure(e),
-        }
-    };
+        let plugin_options = if plugin_options_json.is_empty() {
+            None
+        } else {
+            match compiler::parse_plugin_options(plugin_options_json, &prep_ctx.project_res).await {
+                Ok(options) => Some(options),
+                Err(e) => return CompileResponse::Failure(e),
+            }
+        };
+
+        compile_with_context(&prep_ctx, start_time, plugin_options, &budget).await
+    })
+    .await;
+    response.unwrap_or_else(CompileResponse::Failure)
+}
 
-    let expo_ctx = compiler::compile(&prep_ctx, Some(start_time), plugin_options).await;
+pub(super) async fn compile_with_context<L>(
+    prep_ctx: &PrepCtx<L>,
+    start_time: Instant,
+    plugin_options: Option<plugin::Options>,
+    budget: &compiler::CompileBudget,
+) -> CompileResponse
+where
+    L: Loader,
+{
+    let expo_ctx = match compiler::compile(prep_ctx, Some(start_time), plugin_options, budget).await
+    {
+        Ok(expo_ctx) => expo_ctx,
+        Err(e) => return CompileResponse::Failure(e),
+    };
     let expo_ctx_json = match serde_json::to_value(expo_ctx) {
         Ok(v) => v,
         Err(e) => return CompileResponse::Failure(e.to_string()),
@@ -88,3 +280,32 @@ async fn compile_internal(
 
     CompileResponse::Success(expo_ctx_json)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_output_for_etag_ignores_metrics() {
+        let output = |compiled_in: &str| {
+            json!({
+                "execDoc": {
+                    "project": {
+                        "title": "Test",
+                        "stats": { "Compiled In": compiled_in, "Lines": "10" },
+                    },
+                },
+            })
+        };
+        let a = output_for_etag(&output("10ms"));
+        let b = output_for_etag(&output("25ms"));
+        assert_eq!(a, b);
+        let stats: Value = serde_json::from_slice(&a).unwrap();
+        assert_eq!(
+            stats.pointer("/execDoc/project/stats"),
+            Some(&json!({ "Lines": "10" }))
+        );
+    }
+}
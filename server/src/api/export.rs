@@ -1,18 +1,28 @@
-use axum::extract::Path;
+use std::sync::Arc;
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, Path};
 use axum::http::HeaderMap;
 use axum::routing;
 use axum::{Json, Router};
+use celerc::plugin;
 use celerc::{ExpoDoc, ExportRequest};
 use instant::Instant;
+use serde::Deserialize;
 use tower::ServiceBuilder;
 use tower_http::compression::CompressionLayer;
 
 use crate::compiler;
 
-use super::header;
+use super::{bundle, header};
 
 pub fn init_api() -> Router {
     Router::new()
+        .route(
+            "/",
+            routing::post(export_bundle)
+                .layer(DefaultBodyLimit::max(bundle::MAX_BUNDLE_REQUEST_SIZE)),
+        )
         .route(
             "/:owner/:repo/:reference",
             routing::get(export_owner_repo_ref),
@@ -24,6 +34,20 @@ pub fn init_api() -> Router {
         .layer(ServiceBuilder::new().layer(CompressionLayer::new()))
 }
 
+/// Request body for exporting an uploaded project bundle
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportBundleRequest {
+    /// Base64 encoded zip, tar or gzipped tar archive of the project
+    bundle: String,
+    /// Path to the entry point in the project, same as the `path` for the GET endpoint
+    #[serde(default)]
+    entry_path: Option<String>,
+    #[serde(default)]
+    plugin_options: Option<plugin::OptionsRaw>,
+    export_request: ExportRequest,
+}
+
 async fn export_owner_repo_ref(
     Path((owner, repo, reference)): Path<(String, String, String)>,
     headers: HeaderMap,
@@ -32,11 +56,24 @@ async fn export_owner_repo_ref(
         Ok(v) => v,
         Err(e) => return Json(ExpoDoc::Error(e)),
     };
+    let credential = match header::get_github_token(&headers) {
+        Ok(v) => compiler::get_credential(v.as_deref()),
+        Err(e) => return Json(ExpoDoc::Error(e)),
+    };
     let req = match header::get_export_request(&headers) {
         Ok(v) => v,
         Err(e) => return Json(ExpoDoc::Error(e)),
     };
-    let response = export_internal(&owner, &repo, None, &reference, &plugin_options, req).await;
+    let response = export_internal(
+        &owner,
+        &repo,
+        None,
+        &reference,
+        &plugin_options,
+        req,
+        credential,
+    )
+    .await;
     Json(response)
 }
 
@@ -48,14 +85,74 @@ async fn export_owner_repo_ref_path(
         Ok(v) => v,
         Err(e) => return Json(ExpoDoc::Error(e)),
     };
+    let credential = match header::get_github_token(&headers) {
+        Ok(v) => compiler::get_credential(v.as_deref()),
+        Err(e) => return Json(ExpoDoc::Error(e)),
+    };
     let req = match header::get_export_request(&headers) {
         Ok(v) => v,
         Err(e) => return Json(ExpoDoc::Error(e)),
     };
-    let response =
-        export_internal(&owner, &repo, Some(&path), &reference, &plugin_options, req).await;
+    let response = export_internal(
+        &owner,
+        &repo,
+        Some(&path),
+        &reference,
+        &plugin_options,
+        req,
+        credential,
+    )
+    .await;
     Json(response)
 }
+
+async fn export_bundle(
+    headers: HeaderMap,
+    body: Result<Json<ExportBundleRequest>, JsonRejection>,
+) -> Json<ExpoDoc> {
+    let start_time = Instant::now();
+    let req = match body {
+        Ok(Json(req)) => req,
+        Err(e) => return Json(ExpoDoc::Error(e.body_text())),
+    };
+    let credential = match header::get_github_token(&headers) {
+        Ok(v) => compiler::get_credential(v.as_deref()),
+        Err(e) => return Json(ExpoDoc::Error(e)),
+    };
+    let budget = compiler::CompileBudget::new();
+    let response = compiler::run_with_budget(&budget, async {
+        let loader = match bundle::load_bundle(req.bundle, credential, Arc::clone(&budget)).await {
+            Ok(loader) => loader,
+            Err(e) => return ExpoDoc::Error(e),
+        };
+        let prep_ctx = match compiler::new_bundle_context(loader, req.entry_path).await {
+            Ok(ctx) => ctx,
+            Err(e) => return ExpoDoc::Error(e.to_string()),
+        };
+
+        let plugin_options = match req.plugin_options {
+            None => None,
+            Some(options) => {
+                match compiler::parse_plugin_options_raw(options, &prep_ctx.project_res).await {
+                    Ok(options) => Some(options),
+                    Err(e) => return ExpoDoc::Error(e),
+                }
+            }
+        };
+
+        compiler::export(
+            &prep_ctx,
+            Some(start_time),
+            plugin_options,
+            req.export_request,
+            &budget,
+        )
+        .await
+    })
+    .await;
+    Json(response.unwrap_or_else(ExpoDoc::Error))
+}
+
 async fn export_internal(
     owner: &str,
     repo: &str,
@@ -63,21 +160,39 @@ async fn export_internal(
     reference: &str,
     plugin_options_json: &str,
     req: ExportRequest,
+    credential: Option<compiler::Credential>,
 ) -> ExpoDoc {
     let start_time = Instant::now();
-    let prep_ctx = match compiler::get_context(owner, repo, path, reference).await {
-        Ok(ctx) => ctx,
-        Err(e) => return ExpoDoc::Error(e.to_string()),
-    };
+    if let Err(e) = compiler::check_repo_access(owner, repo) {
+        return ExpoDoc::Error(e);
+    }
+    let budget = compiler::CompileBudget::new();
+    let response = compiler::run_with_budget(&budget, async {
+        let prep_ctx = match compiler::get_context(
+            owner,
+            repo,
+            path,
+            reference,
+            credential,
+            Some(Arc::clone(&budget)),
+        )
+        .await
+        {
+            Ok(ctx) => ctx,
+            Err(e) => return ExpoDoc::Error(e.to_string()),
+        };
 
-    let plugin_options = if plugin_options_json.is_empty() {
-        None
-    } else {
-        match compiler::parse_plugin_options(plugin_options_json, &prep_ctx.project_res).await {
-            Ok(options) => Some(options),
-            Err(e) => return ExpoDoc::Error(e),
-        }
-    };
+        let plugin_options = if plugin_options_json.is_empty() {
+            None
+        } else {
+            match compiler::parse_plugin_options(plugin_options_json, &prep_ctx.project_res).await {
+                Ok(options) => Some(options),
+                Err(e) => return ExpoDoc::Error(e),
+            }
+        };
 
-    compiler::export(&prep_ctx, Some(start_time), plugin_options, req).await
+        compiler::export(&prep_ctx, Some(start_time), plugin_options, req, &budget).await
+    })
+    .await;
+    response.unwrap_or_else(ExpoDoc::Error)
 }
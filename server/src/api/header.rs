@@ -19,6 +19,28 @@ pub fn get_plugin_options(headers: &HeaderMap) -> Result<String, String> {
     Ok(header_str)
 }
 
+/// Get the Celer-GitHub-Token header as a string, for accessing private repositories
+/// Returns None if header is not present or empty
+pub fn get_github_token(headers: &HeaderMap) -> Result<Option<String>, String> {
+    let header_value = match headers.get("Celer-GitHub-Token") {
+        None => return Ok(None),
+        Some(v) => v,
+    };
+
+    let token = match header_value.to_str() {
+        Ok(s) => s.trim(),
+        Err(e) => {
+            error!("Raw header value is not valid UTF-8: {e}");
+            return Err("Invalid header encoding".to_string());
+        }
+    };
+    if token.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(token.to_string()))
+}
+
 pub fn get_export_request(headers: &HeaderMap) -> Result<ExportRequest, String> {
     let header_value = match headers.get("Celer-Export-Request") {
         None => {
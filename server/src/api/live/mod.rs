@@ -0,0 +1,493 @@
+//! The `/live` API endpoint for subscribing to a route over WebSocket
+//!
+//! The route is compiled when the client connects, and compiled again whenever the source
+//! changes, either when the project is purged (for example, by the webhook) or when
+//! files in the local project are modified. Each output is sent as a text message with the same
+//! JSON as the `/compile` endpoint. Sessions subscribed to the same route share
+//! the compilation, so a change that affects many sessions only compiles each route once.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Request, State};
+use axum::http::{HeaderName, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing;
+use axum::Router;
+use instant::Instant;
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::{self, Duration};
+use tracing::{debug, error, info};
+
+use crate::compiler::{self, Credential, ProjectChange};
+use crate::env::Environment;
+use crate::services;
+
+use super::compile::{self, CompileResponse};
+use super::header;
+
+/// Max size of a message from the client. Clients don't need to send anything,
+/// so this is kept small
+const MAX_MESSAGE_SIZE: usize = 1024 * 64; // 64 KB
+/// How often to ping the client to keep the connection alive through proxies
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long to wait for more changes before compiling, so a burst of changes
+/// (like saving multiple files) only compiles once
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(300);
+
+static LIVE_SESSIONS: AtomicUsize = AtomicUsize::new(0);
+/// Number of live sessions of each client IP
+static CLIENT_SESSIONS: Lazy<std::sync::Mutex<HashMap<IpAddr, usize>>> =
+    Lazy::new(Default::default);
+/// When the last live session ended, for checking if the server is idle
+static LAST_SESSION_END: std::sync::Mutex<Option<time::Instant>> = std::sync::Mutex::new(None);
+/// Compilations in progress, keyed by the target
+static IN_FLIGHT: Lazy<Mutex<HashMap<String, SharedCompile>>> = Lazy::new(Default::default);
+
+/// Get the last time the server is active because of live sessions.
+/// This is the current time if there are active sessions
+pub fn last_session_activity() -> Option<time::Instant> {
+    if LIVE_SESSIONS.load(Ordering::SeqCst) > 0 {
+        return Some(time::Instant::now());
+    }
+    match LAST_SESSION_END.lock() {
+        Ok(x) => *x,
+        Err(_) => None,
+    }
+}
+
+pub fn init_api(env: &Environment) -> Router {
+    Router::new()
+        .route(
+            "/:owner/:repo/:reference",
+            routing::get(live_owner_repo_ref),
+        )
+        .route(
+            "/:owner/:repo/:reference/*path",
+            routing::get(live_owner_repo_ref_path),
+        )
+        .with_state(SessionLimits::from_env(env))
+}
+
+/// Routes for subscribing to the local project, when the server is in local dev mode
+pub fn init_local_api(env: &Environment) -> Router {
+    Router::new()
+        .route("/", routing::get(live_local))
+        .route("/*path", routing::get(live_local_path))
+        .with_state(SessionLimits::from_env(env))
+}
+
+/// Limits on the number of live sessions
+#[derive(Debug, Clone)]
+struct SessionLimits {
+    /// Max number of sessions on the server
+    max_sessions: usize,
+    /// Max number of sessions from the same client IP
+    max_sessions_per_client: usize,
+    /// Header to get the client IP from, same as rate limiting
+    ip_header: Option<HeaderName>,
+}
+
+impl SessionLimits {
+    fn from_env(env: &Environment) -> Arc<Self> {
+        Arc::new(Self {
+            max_sessions: env.max_live_sessions,
+            max_sessions_per_client: env.max_live_sessions_per_client,
+            ip_header: env.client_ip_header(),
+        })
+    }
+}
+
+async fn live_owner_repo_ref(
+    Path((owner, repo, reference)): Path<(String, String, String)>,
+    State(limits): State<Arc<SessionLimits>>,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> Response {
+    start_remote_session(owner, repo, reference, None, &limits, ws, req)
+}
+
+async fn live_owner_repo_ref_path(
+    Path((owner, repo, reference, path)): Path<(String, String, String, String)>,
+    State(limits): State<Arc<SessionLimits>>,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> Response {
+    start_remote_session(owner, repo, reference, Some(path), &limits, ws, req)
+}
+
+async fn live_local(
+    State(limits): State<Arc<SessionLimits>>,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> Response {
+    start_session(LiveTarget::Local { path: None }, &limits, ws, &req)
+}
+
+async fn live_local_path(
+    Path(path): Path<String>,
+    State(limits): State<Arc<SessionLimits>>,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> Response {
+    start_session(LiveTarget::Local { path: Some(path) }, &limits, ws, &req)
+}
+
+fn start_remote_session(
+    owner: String,
+    repo: String,
+    reference: String,
+    path: Option<String>,
+    limits: &SessionLimits,
+    ws: WebSocketUpgrade,
+    req: Request,
+) -> Response {
+    if let Err(e) = compiler::check_repo_access(&owner, &repo) {
+        return (StatusCode::FORBIDDEN, e).into_response();
+    }
+    let credential = match header::get_github_token(req.headers()) {
+        Ok(token) => compiler::get_credential(token.as_deref()),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let target = LiveTarget::Remote {
+        owner,
+        repo,
+        reference,
+        path,
+        credential,
+    };
+    start_session(target, limits, ws, &req)
+}
+
+/// Accept the WebSocket handshake and run the session after the connection is upgraded
+fn start_session(
+    target: LiveTarget,
+    limits: &SessionLimits,
+    ws: WebSocketUpgrade,
+    req: &Request,
+) -> Response {
+    let ip = services::get_client_ip(req, limits.ip_header.as_ref());
+    let guard = match SessionGuard::acquire(limits, ip) {
+        Ok(guard) => guard,
+        Err(e) => return e.into_response(),
+    };
+    ws.max_message_size(MAX_MESSAGE_SIZE)
+        .on_failed_upgrade(|e| error!("failed to upgrade live session: {e}"))
+        .on_upgrade(move |socket| async move {
+            let _guard = guard;
+            run_session(socket, target).await;
+        })
+}
+
+/// The route a live session is subscribed to
+enum LiveTarget {
+    Remote {
+        owner: String,
+        repo: String,
+        reference: String,
+        path: Option<String>,
+        credential: Option<Credential>,
+    },
+    Local {
+        path: Option<String>,
+    },
+}
+
+impl LiveTarget {
+    /// Key for sharing the compilation with other sessions. Sessions with different
+    /// credentials don't share, since the output could be different
+    fn key(&self) -> String {
+        match self {
+            Self::Remote {
+                owner,
+                repo,
+                reference,
+                path,
+                credential,
+            } => {
+                let credential = credential.as_ref().map(|c| c.id()).unwrap_or_default();
+                let path = path.as_deref().unwrap_or_default();
+                format!("remote:{credential}:{owner}/{repo}/{reference}:{path}")
+            }
+            Self::Local { path } => {
+                let path = path.as_deref().unwrap_or_default();
+                format!("local:{path}")
+            }
+        }
+    }
+
+    fn is_affected_by(&self, change: &ProjectChange) -> bool {
+        match self {
+            Self::Remote {
+                owner,
+                repo,
+                reference,
+                ..
+            } => change.affects_remote(owner, repo, reference),
+            Self::Local { .. } => change.affects_local(),
+        }
+    }
+
+    async fn compile(&self) -> CompileResponse {
+        match self {
+            Self::Remote {
+                owner,
+                repo,
+                reference,
+                path,
+                credential,
+            } => {
+                compile::compile_internal(
+                    owner,
+                    repo,
+                    path.as_deref(),
+                    reference,
+                    "",
+                    credential.clone(),
+                )
+                .await
+            }
+            Self::Local { path } => {
+                let start_time = Instant::now();
+                let budget = compiler::CompileBudget::new();
+                let response = compiler::run_with_budget(&budget, async {
+                    let prep_ctx =
+                        match compiler::new_local_context(path.clone(), Some(Arc::clone(&budget)))
+                            .await
+                        {
+                            Ok(ctx) => ctx,
+                            Err(e) => return CompileResponse::Failure(e.to_string()),
+                        };
+                    compile::compile_with_context(&prep_ctx, start_time, None, &budget).await
+                })
+                .await;
+                response.unwrap_or_else(CompileResponse::Failure)
+            }
+        }
+    }
+}
+
+async fn run_session(mut socket: WebSocket, target: LiveTarget) {
+    let mut changes = compiler::subscribe_changes();
+
+    info!("live session started");
+    let mut keep_alive = time::interval(KEEP_ALIVE_INTERVAL);
+    // the first tick completes immediately
+    keep_alive.tick().await;
+    // compile when the session starts
+    let mut changed_at = Some(time::Instant::now());
+    // when to compile after a change, so the session keeps serving the client while waiting
+    let mut debounce_deadline = None;
+    // the last change waiting to be compiled
+    let mut pending_change = None;
+    loop {
+        if let Some(since) = changed_at.take() {
+            let json = match compile_shared(&target, since).await {
+                Some(json) => json,
+                None => break,
+            };
+            if socket.send(Message::Text(json.to_string())).await.is_err() {
+                break;
+            }
+        }
+        tokio::select! {
+            // pings from the client are answered automatically
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) => {
+                    // flush the close reply to the client
+                    let _ = socket.send(Message::Close(None)).await;
+                    break;
+                }
+                // other messages from the client are ignored
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    debug!("live session read error: {e}");
+                    break;
+                }
+                None => break,
+            },
+            change = changes.recv() => {
+                let should_compile = match change {
+                    Ok(change) => target.is_affected_by(&change),
+                    // some changes are missed, so compile to be safe
+                    Err(RecvError::Lagged(_)) => true,
+                    Err(RecvError::Closed) => break,
+                };
+                // later changes don't extend the wait, so the route is not
+                // stale for long when the changes keep coming
+                if should_compile {
+                    let now = time::Instant::now();
+                    pending_change = Some(now);
+                    debounce_deadline.get_or_insert(now + DEBOUNCE_DURATION);
+                }
+            }
+            _ = sleep_until(debounce_deadline) => {
+                debounce_deadline = None;
+                changed_at = pending_change.take();
+            }
+            _ = keep_alive.tick() => {
+                if socket.send(Message::Ping(vec![])).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+    info!("live session ended");
+}
+
+/// Sleep until the deadline, or forever if there is no deadline
+async fn sleep_until(deadline: Option<time::Instant>) {
+    match deadline {
+        Some(deadline) => time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Output of a compilation shared by the sessions, serialized as JSON.
+/// None if the output cannot be serialized
+struct SharedCompile {
+    started_at: time::Instant,
+    output: Arc<OnceCell<Option<Arc<str>>>>,
+}
+
+/// Compile the target, or wait for the compilation of the same target by another session
+/// if it started after the change
+async fn compile_shared(target: &LiveTarget, since: time::Instant) -> Option<Arc<str>> {
+    let key = target.key();
+    let output = {
+        let mut in_flight = IN_FLIGHT.lock().await;
+        match in_flight.get(&key) {
+            // the compilation could load the source before the change, so it can't be shared
+            Some(shared) if shared.started_at >= since => Arc::clone(&shared.output),
+            _ => {
+                let output = Arc::new(OnceCell::new());
+                let shared = SharedCompile {
+                    started_at: time::Instant::now(),
+                    output: Arc::clone(&output),
+                };
+                in_flight.insert(key.clone(), shared);
+                output
+            }
+        }
+    };
+    let result = output
+        .get_or_init(|| async {
+            let response = target.compile().await;
+            match serde_json::to_string(&response) {
+                Ok(json) => Some(Arc::from(json)),
+                Err(e) => {
+                    error!("failed to serialize live session output: {e}");
+                    None
+                }
+            }
+        })
+        .await
+        .clone();
+    {
+        let mut in_flight = IN_FLIGHT.lock().await;
+        // the entry could be replaced by a newer compilation
+        if in_flight
+            .get(&key)
+            .is_some_and(|shared| Arc::ptr_eq(&shared.output, &output))
+        {
+            in_flight.remove(&key);
+        }
+    }
+    result
+}
+
+/// Keep track of the number of live sessions, in total and for each client
+struct SessionGuard {
+    ip: IpAddr,
+}
+
+impl SessionGuard {
+    fn acquire(limits: &SessionLimits, ip: IpAddr) -> Result<Self, (StatusCode, &'static str)> {
+        let mut client_sessions = match CLIENT_SESSIONS.lock() {
+            Ok(x) => x,
+            // the data is still usable if another thread panicked
+            Err(e) => e.into_inner(),
+        };
+        let client_count = client_sessions.entry(ip).or_default();
+        if *client_count >= limits.max_sessions_per_client {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many live sessions from this client, please close some and try again",
+            ));
+        }
+        let count = LIVE_SESSIONS.fetch_add(1, Ordering::SeqCst);
+        if count >= limits.max_sessions {
+            LIVE_SESSIONS.fetch_sub(1, Ordering::SeqCst);
+            if *client_count == 0 {
+                client_sessions.remove(&ip);
+            }
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Too many live sessions, please try again later",
+            ));
+        }
+        *client_count += 1;
+        Ok(Self { ip })
+    }
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        if let Ok(mut x) = LAST_SESSION_END.lock() {
+            *x = Some(time::Instant::now());
+        }
+        let mut client_sessions = match CLIENT_SESSIONS.lock() {
+            Ok(x) => x,
+            Err(e) => e.into_inner(),
+        };
+        if let Some(count) = client_sessions.get_mut(&self.ip) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                client_sessions.remove(&self.ip);
+            }
+        }
+        LIVE_SESSIONS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_session_limits() {
+        let limits = SessionLimits {
+            max_sessions: 3,
+            max_sessions_per_client: 2,
+            ip_header: None,
+        };
+        let a = IpAddr::from([10, 0, 0, 1]);
+        let b = IpAddr::from([10, 0, 0, 2]);
+        let c = IpAddr::from([10, 0, 0, 3]);
+        let a1 = SessionGuard::acquire(&limits, a).unwrap();
+        let a2 = SessionGuard::acquire(&limits, a).unwrap();
+        // one client cannot take all the sessions
+        assert_eq!(
+            SessionGuard::acquire(&limits, a).err().map(|e| e.0),
+            Some(StatusCode::TOO_MANY_REQUESTS)
+        );
+        let b1 = SessionGuard::acquire(&limits, b).unwrap();
+        assert_eq!(
+            SessionGuard::acquire(&limits, c).err().map(|e| e.0),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert!(!CLIENT_SESSIONS.lock().unwrap().contains_key(&c));
+
+        // the slots are released when the sessions end
+        drop(a1);
+        let a3 = SessionGuard::acquire(&limits, a).unwrap();
+        drop((a2, a3, b1));
+        assert_eq!(LIVE_SESSIONS.load(Ordering::SeqCst), 0);
+        assert!(CLIENT_SESSIONS.lock().unwrap().is_empty());
+    }
+}
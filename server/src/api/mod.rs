@@ -3,28 +3,67 @@ use std::io;
 use axum::{routing, Router};
 use tracing::info;
 
+use crate::compiler;
 use crate::env::Environment;
 
+mod admin;
+pub use admin::is_authorized as is_admin_authorized;
+mod bundle;
 mod compile;
 mod export;
 mod header;
+mod live;
+pub use live::last_session_activity;
+mod preview;
+mod render;
 mod view;
+mod webhook;
 
 pub fn init_api(router: Router, env: &Environment) -> Result<Router, io::Error> {
     info!("initializing api routes");
-    let router = router
-        .nest("/api/v1", init_api_v1(env)?)
-        .nest("/view", view::init_api(&env.app_dir)?);
+    let api_v1 = init_api_v1(env)?;
+    let view = view::init_api(&env.app_dir, env.view_ssr)?;
+    // /view is limited together with the API, since it could compile the route
+    let (api_v1, view) = match env.rate_limit_layer() {
+        Some(layer) => (api_v1.layer(layer.clone()), view.layer(layer)),
+        None => {
+            info!("rate limiting is disabled");
+            (api_v1, view)
+        }
+    };
+    // webhook and admin requests are authenticated, so they are not limited
+    let api_v1 = api_v1.merge(init_api_v1_authenticated(env));
+    let router = router.nest("/api/v1", api_v1).nest("/view", view);
 
     Ok(router)
 }
 
 pub fn init_api_v1(env: &Environment) -> Result<Router, io::Error> {
     let version = env.version.clone();
-    let router = Router::new()
+    let mut router = Router::new()
         .route("/version", routing::get(move || async { version }))
         .nest("/compile", compile::init_api())
-        .nest("/export", export::init_api());
+        .nest("/export", export::init_api())
+        .nest("/preview", preview::init_api())
+        .nest("/live", live::init_api(env));
+
+    if compiler::has_local_project() {
+        router = router.nest("/live-local", live::init_local_api(env));
+    }
 
     Ok(router)
 }
+
+/// Routes that require a secret or token configured for the server
+fn init_api_v1_authenticated(env: &Environment) -> Router {
+    let mut router = Router::new();
+    match env.webhook_secret.as_deref().filter(|x| !x.is_empty()) {
+        Some(secret) => router = router.nest("/webhook", webhook::init_api(secret)),
+        None => info!("webhook secret not set, webhook endpoint is disabled"),
+    }
+    match env.admin_token.as_deref().filter(|x| !x.is_empty()) {
+        Some(token) => router = router.nest("/admin", admin::init_api(token)),
+        None => info!("admin token not set, admin endpoints are disabled"),
+    }
+    router
+}
@@ -0,0 +1,425 @@
+//! Drawing the preview card with the route information and the map thumbnail
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use celerc::exec::MapLine;
+use celerc::plugin::METRICS_STATS;
+use celerc::prep::MapTilesetTransform;
+use celerc::ExecDoc;
+use tiny_skia::{Color, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+use super::font;
+
+/// Size of the card, which is the recommended size for OpenGraph images
+pub const CARD_WIDTH: u32 = 1200;
+pub const CARD_HEIGHT: u32 = 630;
+
+const MARGIN: u32 = 60;
+/// Width of the accent bar on the left
+const ACCENT_WIDTH: u32 = 12;
+/// Left edge of the map thumbnail
+const MAP_LEFT: u32 = 660;
+/// Max number of stats to show
+const MAX_STATS: usize = 8;
+
+type Rgb = (u8, u8, u8);
+/// Color and points of a line on the map
+pub type MapPath = (Rgb, Vec<(f32, f32)>);
+
+const BACKGROUND: Rgb = (32, 32, 32);
+const MAP_BACKGROUND: Rgb = (48, 48, 48);
+/// Same as the theme color of the viewer
+const ACCENT: Rgb = (0xFF, 0xE1, 0x99);
+const TEXT: Rgb = (240, 240, 240);
+const TEXT_DIM: Rgb = (170, 170, 170);
+/// Color for map lines that can't be parsed
+const DEFAULT_LINE_COLOR: Rgb = (0x33, 0x88, 0xFF);
+
+/// Information to show on the preview card
+pub struct PreviewCard {
+    pub title: String,
+    pub version: String,
+    /// Description of the repo, such as `owner/repo`
+    pub repo_desc: String,
+    pub stats: Vec<(String, String)>,
+    /// Lines of the map, already transformed to the raster coordinates of the map
+    pub map_lines: Vec<MapPath>,
+}
+
+impl PreviewCard {
+    /// Create the card from the document
+    pub fn from_doc(doc: &ExecDoc, repo_desc: String) -> Self {
+        let transform = doc
+            .project
+            .map
+            .as_ref()
+            .and_then(|map| map.layers.first())
+            .map(|layer| &layer.transform);
+        let map_lines = doc
+            .route
+            .iter()
+            .flat_map(|section| &section.map.lines)
+            .filter_map(|line| transform_line(line, transform))
+            .collect();
+        let stats = doc
+            .project
+            .stats
+            .iter()
+            // the compile time is different every time, and would change the card
+            .filter(|(k, _)| !METRICS_STATS.contains(&k.as_str()))
+            // the font can't draw the other characters
+            .filter(|(k, v)| font::can_draw(k) && font::can_draw(v))
+            .take(MAX_STATS)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        Self {
+            title: doc.project.meta.title.clone(),
+            version: doc.project.meta.version.clone(),
+            repo_desc,
+            stats,
+            map_lines,
+        }
+    }
+
+    /// Hash of the content of the card, for caching the rendered image
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.title.hash(&mut hasher);
+        self.version.hash(&mut hasher);
+        self.repo_desc.hash(&mut hasher);
+        self.stats.hash(&mut hasher);
+        for (color, points) in &self.map_lines {
+            color.hash(&mut hasher);
+            points.len().hash(&mut hasher);
+            for (x, y) in points {
+                x.to_bits().hash(&mut hasher);
+                y.to_bits().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Get the title to show. The title of the route is replaced with the repo description
+    /// if it has characters that the font can't draw
+    fn title(&self) -> &str {
+        if !self.title.is_empty() && font::can_draw(&self.title) {
+            return &self.title;
+        }
+        if !self.title.is_empty() && font::can_draw(&self.repo_desc) {
+            return &self.repo_desc;
+        }
+        "Celer Route"
+    }
+
+    /// Draw the card and encode it as PNG
+    pub fn render_png(&self) -> Result<Vec<u8>, String> {
+        let mut pixmap = match Pixmap::new(CARD_WIDTH, CARD_HEIGHT) {
+            Some(x) => x,
+            None => return Err("Failed to create image".to_string()),
+        };
+        let (r, g, b) = BACKGROUND;
+        pixmap.fill(Color::from_rgba8(r, g, b, 255));
+        fill_rect(&mut pixmap, 0, 0, ACCENT_WIDTH, CARD_HEIGHT, ACCENT);
+
+        let has_map = self.map_lines.iter().any(|(_, points)| points.len() > 1);
+        let text_right = if has_map {
+            MAP_LEFT - MARGIN / 2
+        } else {
+            CARD_WIDTH - MARGIN
+        };
+        let text_width = text_right - MARGIN;
+
+        let mut y = MARGIN;
+        let title = self.title();
+        let scale = if has_map { 5 } else { 6 };
+        for line in wrap_text(title, max_chars(text_width, scale), 2) {
+            draw_text(&mut pixmap, &line, MARGIN, y, scale, TEXT);
+            y += line_height(scale);
+        }
+        if !self.version.is_empty() && font::can_draw(&self.version) {
+            let version = truncate(&self.version, max_chars(text_width, 3));
+            draw_text(&mut pixmap, &version, MARGIN, y, 3, TEXT_DIM);
+            y += line_height(3);
+        }
+        y += line_height(3);
+        for (key, value) in &self.stats {
+            if y + line_height(3) > CARD_HEIGHT - MARGIN - line_height(4) {
+                break;
+            }
+            let stat = truncate(&format!("{key}: {value}"), max_chars(text_width, 3));
+            draw_text(&mut pixmap, &stat, MARGIN, y, 3, TEXT);
+            y += line_height(3);
+        }
+
+        let footer_y = CARD_HEIGHT - MARGIN - font::GLYPH_HEIGHT * 4;
+        draw_text(&mut pixmap, "Celer", MARGIN, footer_y, 4, ACCENT);
+        let repo_x = MARGIN + font::GLYPH_ADVANCE * 4 * 6;
+        let repo_desc = truncate(
+            &self.repo_desc,
+            max_chars(text_right.saturating_sub(repo_x), 3),
+        );
+        draw_text(
+            &mut pixmap,
+            &repo_desc,
+            repo_x,
+            footer_y + font::GLYPH_HEIGHT,
+            3,
+            TEXT_DIM,
+        );
+
+        if has_map {
+            self.draw_map(&mut pixmap);
+        }
+
+        pixmap
+            .encode_png()
+            .map_err(|e| format!("Failed to encode image: {e}"))
+    }
+
+    /// Draw the map lines in the thumbnail area, scaled to fit
+    fn draw_map(&self, pixmap: &mut Pixmap) {
+        let left = MAP_LEFT as f32;
+        let top = MARGIN as f32;
+        let width = (CARD_WIDTH - MARGIN - MAP_LEFT) as f32;
+        let height = (CARD_HEIGHT - 2 * MARGIN) as f32;
+        fill_rect(
+            pixmap,
+            MAP_LEFT,
+            MARGIN,
+            CARD_WIDTH - MARGIN - MAP_LEFT,
+            CARD_HEIGHT - 2 * MARGIN,
+            MAP_BACKGROUND,
+        );
+
+        let points = self.map_lines.iter().flat_map(|(_, points)| points);
+        let (mut min_x, mut min_y) = (f32::MAX, f32::MAX);
+        let (mut max_x, mut max_y) = (f32::MIN, f32::MIN);
+        for (x, y) in points {
+            min_x = min_x.min(*x);
+            min_y = min_y.min(*y);
+            max_x = max_x.max(*x);
+            max_y = max_y.max(*y);
+        }
+        let padding = 24.0;
+        let span_x = (max_x - min_x).max(1.0);
+        let span_y = (max_y - min_y).max(1.0);
+        // keep the aspect ratio and center the route in the area
+        let scale = ((width - 2.0 * padding) / span_x).min((height - 2.0 * padding) / span_y);
+        let offset_x = left + (width - span_x * scale) / 2.0;
+        let offset_y = top + (height - span_y * scale) / 2.0;
+
+        let stroke = Stroke {
+            width: 3.0,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            ..Default::default()
+        };
+        for ((r, g, b), points) in &self.map_lines {
+            let mut builder = PathBuilder::new();
+            for (i, (x, y)) in points.iter().enumerate() {
+                let x = offset_x + (x - min_x) * scale;
+                let y = offset_y + (y - min_y) * scale;
+                if i == 0 {
+                    builder.move_to(x, y);
+                } else {
+                    builder.line_to(x, y);
+                }
+            }
+            let path = match builder.finish() {
+                Some(path) => path,
+                None => continue,
+            };
+            let mut paint = Paint::default();
+            paint.set_color_rgba8(*r, *g, *b, 255);
+            paint.anti_alias = true;
+            pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+        }
+    }
+}
+
+/// Transform the points of the line to raster coordinates with the transform of the map layer
+fn transform_line(line: &MapLine, transform: Option<&MapTilesetTransform>) -> Option<MapPath> {
+    if line.points.len() < 2 {
+        return None;
+    }
+    let ((scale_x, scale_y), (translate_x, translate_y)) = match transform {
+        Some(t) => (t.scale, t.translate),
+        None => ((1.0, 1.0), (0.0, 0.0)),
+    };
+    let points = line
+        .points
+        .iter()
+        .map(|coord| {
+            let x = coord.0 * scale_x + translate_x;
+            let y = coord.1 * scale_y + translate_y;
+            (x as f32, y as f32)
+        })
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    let color = parse_color(&line.color).unwrap_or(DEFAULT_LINE_COLOR);
+    Some((color, points))
+}
+
+/// Parse a CSS color in hex format or a few common names
+fn parse_color(color: &str) -> Option<Rgb> {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()?;
+        return match digits.len() {
+            3 | 4 => Some((digits[0] * 17, digits[1] * 17, digits[2] * 17)),
+            6 | 8 => Some((
+                digits[0] * 16 + digits[1],
+                digits[2] * 16 + digits[3],
+                digits[4] * 16 + digits[5],
+            )),
+            _ => None,
+        };
+    }
+    let rgb = match color.to_ascii_lowercase().as_str() {
+        "red" => (255, 0, 0),
+        "orange" => (255, 165, 0),
+        "yellow" => (255, 255, 0),
+        "green" => (0, 128, 0),
+        "lime" => (0, 255, 0),
+        "blue" => (0, 0, 255),
+        "cyan" | "aqua" => (0, 255, 255),
+        "purple" => (128, 0, 128),
+        "magenta" | "fuchsia" => (255, 0, 255),
+        "pink" => (255, 192, 203),
+        "white" => (255, 255, 255),
+        "black" => (0, 0, 0),
+        "gray" | "grey" => (128, 128, 128),
+        _ => return None,
+    };
+    Some(rgb)
+}
+
+fn fill_rect(pixmap: &mut Pixmap, x: u32, y: u32, w: u32, h: u32, (r, g, b): Rgb) {
+    if let Some(rect) = Rect::from_xywh(x as f32, y as f32, w as f32, h as f32) {
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(r, g, b, 255);
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+}
+
+/// Draw the text with the bitmap font, where each font pixel is `scale` pixels
+fn draw_text(pixmap: &mut Pixmap, text: &str, x: u32, y: u32, scale: u32, color: Rgb) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph_x = x + i as u32 * font::GLYPH_ADVANCE * scale;
+        for (px, py) in font::glyph_pixels(c) {
+            fill_rect(
+                pixmap,
+                glyph_x + px * scale,
+                y + py * scale,
+                scale,
+                scale,
+                color,
+            );
+        }
+    }
+}
+
+fn line_height(scale: u32) -> u32 {
+    (font::GLYPH_HEIGHT + 3) * scale
+}
+
+/// Max number of characters that fit in the width
+fn max_chars(width: u32, scale: u32) -> usize {
+    (width / (font::GLYPH_ADVANCE * scale)) as usize
+}
+
+/// Truncate the text to the max number of characters, adding `...` if truncated
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated = text
+        .chars()
+        .take(max_chars.saturating_sub(3))
+        .collect::<String>();
+    truncated.push_str("...");
+    truncated
+}
+
+/// Wrap the text by words into at most `max_lines` lines. Words longer than a line are split
+fn wrap_text(text: &str, max_chars: usize, max_lines: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut lines: Vec<String> = vec![];
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word = word.chars().collect::<Vec<_>>();
+        loop {
+            let current_len = current.chars().count();
+            let needed = if current.is_empty() {
+                word.len()
+            } else {
+                current_len + 1 + word.len()
+            };
+            if needed <= max_chars {
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+                current.extend(word.iter());
+                break;
+            }
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+                continue;
+            }
+            // the word itself is longer than a line
+            let rest = word.split_off(max_chars);
+            lines.push(word.into_iter().collect());
+            word = rest;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    if lines.len() > max_lines {
+        let rest = lines.split_off(max_lines - 1).join(" ");
+        lines.push(truncate(&rest, max_chars));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_card(title: &str) -> PreviewCard {
+        PreviewCard {
+            title: title.to_string(),
+            version: "1.0.0".to_string(),
+            repo_desc: "owner/repo".to_string(),
+            stats: vec![("Lines".to_string(), "10".to_string())],
+            map_lines: vec![((255, 0, 0), vec![(0.0, 0.0), (1.0, 1.0)])],
+        }
+    }
+
+    #[test]
+    fn test_title() {
+        assert_eq!(test_card("My Route").title(), "My Route");
+        assert_eq!(test_card("").title(), "Celer Route");
+        assert_eq!(test_card("ルート").title(), "owner/repo");
+        let mut card = test_card("ルート");
+        card.repo_desc = "owner/repo/ルート".to_string();
+        assert_eq!(card.title(), "Celer Route");
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let hash = test_card("My Route").content_hash();
+        assert_eq!(test_card("My Route").content_hash(), hash);
+        assert_ne!(test_card("Other Route").content_hash(), hash);
+        let mut card = test_card("My Route");
+        card.map_lines[0].1.push((2.0, 0.0));
+        assert_ne!(card.content_hash(), hash);
+        let mut card = test_card("My Route");
+        card.stats[0].1 = "11".to_string();
+        assert_ne!(card.content_hash(), hash);
+    }
+}
@@ -0,0 +1,153 @@
+//! The `/preview` API endpoint to render an image card of the route, used as the
+//! OpenGraph image of the view page
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::Path;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing;
+use axum::Router;
+use cached::proc_macro::cached;
+use instant::Instant;
+use tracing::error;
+
+use crate::compiler;
+
+mod card;
+pub use card::{CARD_HEIGHT, CARD_WIDTH};
+mod font;
+
+pub fn init_api() -> Router {
+    Router::new()
+        .route(
+            "/:owner/:repo/:reference",
+            routing::get(preview_owner_repo_ref),
+        )
+        .route(
+            "/:owner/:repo/:reference/*path",
+            routing::get(preview_owner_repo_ref_path),
+        )
+}
+
+/// Get the URL of the preview image of the route
+pub fn get_preview_url(
+    origin: &str,
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+) -> String {
+    let mut url = format!("{origin}/api/v1/preview/{owner}/{repo}/{reference}");
+    if !path.is_empty() {
+        url.push('/');
+        url.push_str(path);
+    }
+    url
+}
+
+async fn preview_owner_repo_ref(
+    Path((owner, repo, reference)): Path<(String, String, String)>,
+) -> Response {
+    preview_internal(&owner, &repo, &reference, "").await
+}
+
+async fn preview_owner_repo_ref_path(
+    Path((owner, repo, reference, path)): Path<(String, String, String, String)>,
+) -> Response {
+    preview_internal(&owner, &repo, &reference, &path).await
+}
+
+async fn preview_internal(owner: &str, repo: &str, reference: &str, path: &str) -> Response {
+    if let Err(e) = compiler::check_repo_access(owner, repo) {
+        return (StatusCode::FORBIDDEN, e).into_response();
+    }
+    match get_preview_png(owner, repo, reference, path).await {
+        Ok(png) => (
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static("image/png")),
+                (
+                    header::CACHE_CONTROL,
+                    HeaderValue::from_static("public,max-age=600"),
+                ),
+            ],
+            png,
+        )
+            .into_response(),
+        Err(e) => {
+            error!("Error rendering preview for project {owner}/{repo}/{reference}/{path}: {e}");
+            (StatusCode::NOT_FOUND, e).into_response()
+        }
+    }
+}
+
+/// Compile the route and render the card. The card is cached for each route,
+/// so repeated requests (like link unfurls) don't compile the route again
+#[cached(
+    size = 256,
+    time = 600,
+    key = "String",
+    convert = r#"{ format!("{owner}/{repo}/{reference}/{path}") }"#,
+    result = true
+)]
+async fn get_preview_png(
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+) -> Result<Bytes, String> {
+    // the preview is public and cached, so no credential is used
+    let credential = None;
+    let budget = compiler::CompileBudget::new();
+    let path_opt = if path.is_empty() { None } else { Some(path) };
+    let future = async {
+        let prep_ctx = compiler::get_context(
+            owner,
+            repo,
+            path_opt,
+            reference,
+            credential,
+            Some(Arc::clone(&budget)),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let expo_ctx = compiler::compile(&prep_ctx, Some(Instant::now()), None, &budget).await?;
+        let repo_desc = get_repo_desc(owner, repo, reference, path);
+        let card = card::PreviewCard::from_doc(&expo_ctx.exec_ctx.exec_doc, repo_desc);
+        render_card(card).await
+    };
+    compiler::run_with_budget(&budget, future).await?
+}
+
+/// Get the description of the repo shown on the card, such as `owner/repo`
+fn get_repo_desc(owner: &str, repo: &str, reference: &str, path: &str) -> String {
+    let mut repo_desc = format!("{owner}/{repo}");
+    if !path.is_empty() {
+        repo_desc.push('/');
+        repo_desc.push_str(path);
+    }
+    if reference != "main" {
+        repo_desc.push_str(" (");
+        repo_desc.push_str(reference);
+        repo_desc.push(')');
+    }
+    repo_desc
+}
+
+/// Render the card as PNG. The image is cached by the content of the card,
+/// so it's updated when anything shown on the card changes
+#[cached(
+    size = 256,
+    time = 86400,
+    key = "u64",
+    convert = r#"{ card.content_hash() }"#,
+    result = true
+)]
+async fn render_card(card: card::PreviewCard) -> Result<Bytes, String> {
+    // drawing is CPU-bound, so it's moved off the async runtime
+    let png = tokio::task::spawn_blocking(move || card.render_png())
+        .await
+        .map_err(|e| format!("Failed to render preview: {e}"))??;
+    Ok(Bytes::from(png))
+}
@@ -0,0 +1,367 @@
+//! Server-side rendering of the document to plain HTML, for crawlers and clients without JS
+//!
+//! The HTML is injected into the view page and replaced when the viewer is loaded.
+//! Only the text content of the document is rendered. The map and icons are not included.
+
+use std::fmt::Write;
+
+use celerc::comp::DocNote;
+use celerc::exec::ExecLine;
+use celerc::lang::{DocRichText, DocRichTextBlock};
+use celerc::prep::{DocTag, DocTagColor, RouteConfig};
+use celerc::util;
+use celerc::ExecDoc;
+
+/// Render the document as HTML
+pub fn render_doc_html(doc: &ExecDoc) -> String {
+    let mut out = String::new();
+    // hide the loading screen, since the content is already visible
+    out.push_str("<style>.pre-loading{display:none}</style>");
+    out.push_str("<main class=\"ssr-doc\" style=\"font-family:sans-serif;max-width:800px;margin:0 auto;padding:8px\">");
+    let title = &doc.project.meta.title;
+    if !title.is_empty() {
+        let _ = write!(out, "<h1>{}</h1>", util::xml_escape(title));
+    }
+    for text in &doc.preface {
+        out.push_str("<p>");
+        render_rich_text(&mut out, text, &doc.project);
+        out.push_str("</p>");
+    }
+    for section in &doc.route {
+        out.push_str("<section>");
+        if !section.name.is_empty() {
+            let _ = write!(out, "<h2>{}</h2>", util::xml_escape(&section.name));
+        }
+        for line in &section.lines {
+            render_line(&mut out, line, &doc.project);
+        }
+        out.push_str("</section>");
+    }
+    out.push_str("</main>");
+    out
+}
+
+/// Get the text of the preface for the description of the page, truncated to `max_len` characters
+pub fn preview_text(doc: &ExecDoc, max_len: usize) -> String {
+    let text = doc
+        .preface
+        .iter()
+        .map(|text| text.to_string())
+        .filter(|text| !text.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max_len {
+        return text;
+    }
+    let mut truncated = text
+        .chars()
+        .take(max_len.saturating_sub(3))
+        .collect::<String>();
+    truncated.push_str("...");
+    truncated
+}
+
+fn render_line(out: &mut String, line: &ExecLine, project: &RouteConfig) {
+    let color = sanitize_css(&line.line_color).unwrap_or_default();
+    let _ = write!(
+        out,
+        "<div class=\"ssr-line\" style=\"border-left:4px solid {color};padding:2px 8px;margin:2px 0\">"
+    );
+    if let Some(counter) = &line.counter_text {
+        out.push_str("<span class=\"ssr-counter\" style=\"float:right\">");
+        render_block(out, counter, project);
+        out.push_str("</span>");
+    }
+    let tag = if line.is_banner { "strong" } else { "span" };
+    let _ = write!(out, "<div><{tag}>");
+    render_rich_text(out, &line.text, project);
+    let _ = write!(out, "</{tag}></div>");
+    if !line.secondary_text.0.is_empty() {
+        out.push_str("<div><small>");
+        render_rich_text(out, &line.secondary_text, project);
+        out.push_str("</small></div>");
+    }
+    for note in &line.notes {
+        match note {
+            DocNote::Text { content } => {
+                out.push_str("<blockquote>");
+                render_rich_text(out, content, project);
+                out.push_str("</blockquote>");
+            }
+            DocNote::Image { link } => {
+                if is_safe_link(link) {
+                    let _ = write!(
+                        out,
+                        "<img src=\"{}\" alt=\"\" style=\"max-width:100%\">",
+                        util::html_attr_escape(link)
+                    );
+                }
+            }
+            DocNote::Video { link } => {
+                if is_safe_link(link) {
+                    let link = util::html_attr_escape(link);
+                    let _ = write!(out, "<a href=\"{link}\">{link}</a>");
+                }
+            }
+        }
+    }
+    out.push_str("</div>");
+}
+
+fn render_rich_text(out: &mut String, text: &DocRichText, project: &RouteConfig) {
+    for block in text.iter() {
+        render_block(out, block, project);
+    }
+}
+
+fn render_block(out: &mut String, block: &DocRichTextBlock, project: &RouteConfig) {
+    let text = util::xml_escape(&block.text);
+    let style = block_style(block, project);
+    let text = if style.is_empty() {
+        text.into_owned()
+    } else {
+        format!("<span style=\"{style}\">{text}</span>")
+    };
+    match block.link.as_deref().filter(|link| is_safe_link(link)) {
+        Some(link) => {
+            let _ = write!(
+                out,
+                "<a href=\"{}\">{text}</a>",
+                util::html_attr_escape(link)
+            );
+        }
+        None => out.push_str(&text),
+    }
+}
+
+/// Compute the inline style of the block from its tags
+fn block_style(block: &DocRichTextBlock, project: &RouteConfig) -> String {
+    let mut tag = DocTag::default();
+    for name in block.tags() {
+        if let Some(t) = project.tags.get(name) {
+            tag.apply_override(t);
+        }
+    }
+    let mut style = String::new();
+    if tag.bold == Some(true) {
+        style.push_str("font-weight:bold;");
+    }
+    if tag.italic == Some(true) {
+        style.push_str("font-style:italic;");
+    }
+    match (tag.underline == Some(true), tag.strikethrough == Some(true)) {
+        (true, true) => style.push_str("text-decoration:underline line-through;"),
+        (true, false) => style.push_str("text-decoration:underline;"),
+        (false, true) => style.push_str("text-decoration:line-through;"),
+        (false, false) => {}
+    }
+    if tag.monospace == Some(true) {
+        style.push_str("font-family:monospace;");
+    }
+    if let Some(color) = tag
+        .color
+        .as_ref()
+        .and_then(light_color)
+        .and_then(sanitize_css)
+    {
+        let _ = write!(style, "color:{color};");
+    }
+    if let Some(color) = tag
+        .background
+        .as_ref()
+        .and_then(light_color)
+        .and_then(sanitize_css)
+    {
+        let _ = write!(style, "background-color:{color};");
+    }
+    style
+}
+
+/// Get the color for light background, since the rendered page doesn't have a theme
+fn light_color(color: &DocTagColor) -> Option<&str> {
+    match color {
+        DocTagColor::Single(color) => Some(color),
+        DocTagColor::LightDark { light, dark } => light.as_deref().or(dark.as_deref()),
+    }
+}
+
+/// Remove characters that are not allowed in a CSS color value
+///
+/// Returns `None` if the value loads a URL or is empty after sanitizing
+fn sanitize_css(value: &str) -> Option<String> {
+    let value = value
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "#(),.% -".contains(*c))
+        .collect::<String>();
+    if value.trim().is_empty() || value.to_ascii_lowercase().contains("url(") {
+        return None;
+    }
+    Some(value)
+}
+
+fn is_safe_link(link: &str) -> bool {
+    link.starts_with("https://") || link.starts_with("http://")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::borrow::Cow;
+
+    use celerc::exec::ExecSection;
+
+    fn tagged_block(outer_tags: &[&str], tag: &str, text: &str) -> DocRichTextBlock {
+        DocRichTextBlock {
+            outer_tags: outer_tags.iter().map(|x| x.to_string()).collect(),
+            ..DocRichTextBlock::with_tag(tag, text)
+        }
+    }
+
+    fn project_with_tags(tags: Vec<(&str, DocTag)>) -> RouteConfig {
+        let mut project = RouteConfig::default();
+        for (name, tag) in tags {
+            project.tags.insert(name.to_string(), tag);
+        }
+        project
+    }
+
+    #[test]
+    fn test_escape_text() {
+        let mut project = RouteConfig::default();
+        project.meta.title = "<script>alert(1)</script>".to_string();
+        let doc = ExecDoc {
+            project: Cow::Owned(project),
+            preface: vec![DocRichText::text("<script>preface</script>")],
+            route: vec![ExecSection {
+                name: "<script>section</script>".to_string(),
+                lines: vec![ExecLine {
+                    text: DocRichText::text("<script>line</script>"),
+                    secondary_text: DocRichText::text("<script>secondary</script>"),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let html = render_doc_html(&doc);
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("<h1>&lt;script&gt;alert(1)&lt;/script&gt;</h1>"));
+        assert!(html.contains("&lt;script&gt;preface&lt;/script&gt;"));
+        assert!(html.contains("<h2>&lt;script&gt;section&lt;/script&gt;</h2>"));
+        assert!(html.contains("&lt;script&gt;line&lt;/script&gt;"));
+        assert!(html.contains("&lt;script&gt;secondary&lt;/script&gt;"));
+    }
+
+    #[test]
+    fn test_safe_link() {
+        assert!(is_safe_link("https://example.com"));
+        assert!(is_safe_link("http://example.com"));
+        assert!(!is_safe_link("javascript:alert(1)"));
+        assert!(!is_safe_link("data:text/html,<script>alert(1)</script>"));
+
+        let project = RouteConfig::default();
+        let mut out = String::new();
+        let block = DocRichTextBlock {
+            link: Some("javascript:alert(1)".to_string()),
+            ..DocRichTextBlock::text("click")
+        };
+        render_block(&mut out, &block, &project);
+        assert_eq!(out, "click");
+
+        let mut out = String::new();
+        let block = DocRichTextBlock {
+            link: Some("https://example.com/?a=\"b\"".to_string()),
+            ..DocRichTextBlock::text("click")
+        };
+        render_block(&mut out, &block, &project);
+        assert!(out.starts_with("<a href=\""));
+        assert!(!out.contains("\"b\""));
+
+        let line = ExecLine {
+            notes: vec![
+                DocNote::Image {
+                    link: "data:image/png;base64,AAAA".to_string(),
+                },
+                DocNote::Video {
+                    link: "javascript:alert(1)".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut out = String::new();
+        render_line(&mut out, &line, &project);
+        assert!(!out.contains("<img"));
+        assert!(!out.contains("<a"));
+    }
+
+    #[test]
+    fn test_sanitize_css() {
+        assert_eq!(sanitize_css("#ff0000"), Some("#ff0000".to_string()));
+        assert_eq!(
+            sanitize_css("rgb(1, 2, 3)"),
+            Some("rgb(1, 2, 3)".to_string())
+        );
+        assert_eq!(
+            sanitize_css("red;}body{display:none"),
+            Some("redbodydisplaynone".to_string())
+        );
+        assert_eq!(sanitize_css("url(https://example.com)"), None);
+        assert_eq!(sanitize_css("red URL(x)"), None);
+        assert_eq!(sanitize_css(";}"), None);
+
+        let project = project_with_tags(vec![(
+            "evil",
+            DocTag {
+                color: Some(DocTagColor::Single("red;}*{x:url(a)".to_string())),
+                background: Some(DocTagColor::LightDark {
+                    light: Some("blue;}".to_string()),
+                    dark: None,
+                }),
+                ..Default::default()
+            },
+        )]);
+        let style = block_style(&DocRichTextBlock::with_tag("evil", "text"), &project);
+        assert_eq!(style, "background-color:blue;");
+    }
+
+    #[test]
+    fn test_outer_tags_style() {
+        let project = project_with_tags(vec![
+            (
+                "item",
+                DocTag {
+                    bold: Some(true),
+                    color: Some(DocTagColor::Single("red".to_string())),
+                    ..Default::default()
+                },
+            ),
+            (
+                "important",
+                DocTag {
+                    underline: Some(true),
+                    color: Some(DocTagColor::Single("blue".to_string())),
+                    ..Default::default()
+                },
+            ),
+            (
+                "old",
+                DocTag {
+                    strikethrough: Some(true),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let block = tagged_block(&["item", "old"], "important", "Master Sword");
+        assert_eq!(
+            block_style(&block, &project),
+            "font-weight:bold;text-decoration:underline line-through;color:blue;"
+        );
+        let block = tagged_block(&["important"], "item", "Master Sword");
+        assert_eq!(
+            block_style(&block, &project),
+            "font-weight:bold;text-decoration:underline;color:red;"
+        );
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::fs;
 use std::io;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 
 use axum::extract::Path;
 use axum::http::header;
@@ -10,6 +10,7 @@ use axum::http::{HeaderValue, StatusCode};
 use axum::routing;
 use axum::Router;
 use cached::proc_macro::cached;
+use instant::Instant;
 use tower::ServiceBuilder;
 use tower_http::compression::CompressionLayer;
 use tower_http::set_header::SetResponseHeaderLayer;
@@ -20,8 +21,11 @@ use celerc::util;
 
 use crate::compiler;
 
-pub fn init_api(app_dir: &str) -> Result<Router, io::Error> {
+use super::{preview, render};
+
+pub fn init_api(app_dir: &str, ssr: bool) -> Result<Router, io::Error> {
     init_view_html(app_dir)?;
+    SSR_ENABLED.get_or_init(|| ssr);
 
     let router = Router::new()
         .route("/:owner/:repo", routing::get(view_owner_repo))
@@ -47,6 +51,16 @@ pub fn init_api(app_dir: &str) -> Result<Router, io::Error> {
 }
 
 const SERVER_INJECTED_TAGS: &str = "<!-- SERVER_INJECTED_TAGS -->";
+const SERVER_INJECTED_BODY: &str = "<!-- SERVER_INJECTED_BODY -->";
+
+/// Max number of characters of the preface to show in the description
+const MAX_PREVIEW_LENGTH: usize = 200;
+
+/// If the route should be compiled and rendered into the page
+static SSR_ENABLED: OnceLock<bool> = OnceLock::new();
+fn is_ssr_enabled() -> bool {
+    SSR_ENABLED.get().copied().unwrap_or(false)
+}
 
 static VIEW_HTML_HEAD: OnceLock<String> = OnceLock::new();
 fn get_head() -> Result<&'static str, StatusCode> {
@@ -145,26 +159,30 @@ async fn view_internal(
     reference: &str,
     path: &str,
 ) -> Result<String, StatusCode> {
-    let mut builder = match compiler::new_context_builder(owner, repo, Some(reference)) {
-        Ok(builder) => builder,
-        Err(e) => {
-            error!("Error creating context builder for project {owner}/{repo}/{reference}: {e}");
-            return view_fallback();
-        }
-    };
-    if !path.is_empty() {
-        builder = builder.entry_point(Some(path.to_string()));
+    if let Err(e) = compiler::check_repo_access(owner, repo) {
+        error!("{e}");
+        return view_fallback();
     }
-
-    let metadata = match builder.get_metadata().await {
-        Err(e) => {
-            error!("Error getting metadata for project {owner}/{repo}/{reference}/{path}: {e}");
-            return view_fallback();
+    let rendered = if is_ssr_enabled() {
+        match render_route(owner, repo, reference, path).await {
+            Ok(rendered) => Some(rendered),
+            Err(e) => {
+                error!("Error rendering project {owner}/{repo}/{reference}/{path}: {e}");
+                None
+            }
         }
-        Ok(metadata) => metadata,
+    } else {
+        None
+    };
+    let rendered = match rendered {
+        Some(rendered) => rendered,
+        None => match get_metadata_only(owner, repo, reference, path).await {
+            Some(rendered) => rendered,
+            None => return view_fallback(),
+        },
     };
-    let title = &metadata.title;
-    let version = &metadata.version;
+    let title = &rendered.title;
+    let version = &rendered.version;
 
     let title = if title.is_empty() {
         "Celer Viewer".to_string()
@@ -185,7 +203,16 @@ async fn view_internal(
             repo_desc.push_str(reference);
             repo_desc.push(')');
         }
-        format!("View {repo_desc} on Celer")
+        let mut description = format!("View {repo_desc} on Celer");
+        if let Some(section_count) = rendered.section_count {
+            let plural = if section_count == 1 { "" } else { "s" };
+            description.push_str(&format!(" - {section_count} section{plural}"));
+        }
+        if !rendered.preview.is_empty() {
+            description.push_str(". ");
+            description.push_str(&rendered.preview);
+        }
+        description
     };
 
     let view_url = {
@@ -202,15 +229,23 @@ async fn view_internal(
     };
     let preload_title_tag = format!(
         "<meta name=\"preload-title\" content=\"{}\">",
-        util::html_attr_escape(&metadata.title)
+        util::html_attr_escape(&rendered.title)
     );
     let title_tag = format!(
         "<meta name=\"og:title\" content=\"{}\">",
         util::html_attr_escape(&title)
     );
+    let description = util::html_attr_escape(&description);
     let description_tag = format!(
-        "<meta name=\"og:description\" content=\"{}\">",
-        util::html_attr_escape(&description)
+        "<meta name=\"og:description\" content=\"{description}\"><meta name=\"description\" content=\"{description}\">"
+    );
+    let image_url =
+        preview::get_preview_url(&env::site::get_origin(), owner, repo, reference, path);
+    let image_tag = format!(
+        "<meta name=\"og:image\" content=\"{}\"><meta name=\"og:image:width\" content=\"{}\"><meta name=\"og:image:height\" content=\"{}\">",
+        util::html_attr_escape(&image_url),
+        preview::CARD_WIDTH,
+        preview::CARD_HEIGHT
     );
     let url_tag = format!(
         "<meta name=\"og:url\" content=\"{}\">",
@@ -219,6 +254,10 @@ async fn view_internal(
 
     let head = get_head()?;
     let tail = get_tail()?;
+    let tail = match &rendered.body {
+        Some(body) => tail.replacen(SERVER_INJECTED_BODY, body, 1),
+        None => tail.to_string(),
+    };
 
     let html = format!(
         "{head}
@@ -226,12 +265,101 @@ async fn view_internal(
          {title_tag}
          {description_tag}
          {url_tag}
+         {image_tag}
          {tail}"
     );
 
     Ok(html)
 }
 
+/// Information about the route to inject into the page
+struct RenderedRoute {
+    title: String,
+    version: String,
+    /// Number of sections, if the route is compiled
+    section_count: Option<usize>,
+    /// Text from the preface to show in the description
+    preview: String,
+    /// Server-rendered HTML of the document, if the route is compiled
+    body: Option<String>,
+}
+
+/// Compile the route and render the document
+async fn render_route(
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+) -> Result<RenderedRoute, String> {
+    // the view is public and cached, so no credential is used
+    let credential = None;
+    let budget = compiler::CompileBudget::new();
+    let path = if path.is_empty() { None } else { Some(path) };
+    let future = async {
+        let prep_ctx = compiler::get_context(
+            owner,
+            repo,
+            path,
+            reference,
+            credential,
+            Some(Arc::clone(&budget)),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let expo_ctx = compiler::compile(&prep_ctx, Some(Instant::now()), None, &budget).await?;
+        let doc = &expo_ctx.exec_ctx.exec_doc;
+        if doc.route.is_empty() {
+            return Err("The route is empty or failed to compile".to_string());
+        }
+        Ok(RenderedRoute {
+            title: doc.project.meta.title.clone(),
+            version: doc.project.meta.version.clone(),
+            section_count: Some(doc.route.len()),
+            preview: render::preview_text(doc, MAX_PREVIEW_LENGTH),
+            body: Some(render::render_doc_html(doc)),
+        })
+    };
+    compiler::run_with_budget(&budget, future).await?
+}
+
+/// Load only the metadata of the project without compiling
+async fn get_metadata_only(
+    owner: &str,
+    repo: &str,
+    reference: &str,
+    path: &str,
+) -> Option<RenderedRoute> {
+    // the view is public and cached, so no credential is used
+    let credential = None;
+    let mut builder =
+        match compiler::new_context_builder(owner, repo, Some(reference), credential, None) {
+            Ok(builder) => builder,
+            Err(e) => {
+                error!(
+                    "Error creating context builder for project {owner}/{repo}/{reference}: {e}"
+                );
+                return None;
+            }
+        };
+    if !path.is_empty() {
+        builder = builder.entry_point(Some(path.to_string()));
+    }
+
+    match builder.get_metadata().await {
+        Err(e) => {
+            error!("Error getting metadata for project {owner}/{repo}/{reference}/{path}: {e}");
+            None
+        }
+        Ok(metadata) => Some(RenderedRoute {
+            title: metadata.title.clone(),
+            version: metadata.version.clone(),
+            section_count: None,
+            preview: String::new(),
+            body: None,
+        }),
+    }
+}
+
 fn view_fallback() -> Result<String, StatusCode> {
     let head = get_head()?;
     let tail = get_tail()?;
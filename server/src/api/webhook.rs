@@ -0,0 +1,193 @@
+//! The `/webhook` API endpoint, for invalidating the caches when a project is pushed
+
+use std::sync::Arc;
+
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::{routing, Router};
+use ring::hmac;
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::compiler;
+
+pub fn init_api(secret: &str) -> Router {
+    let key = Arc::new(hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()));
+    Router::new()
+        .route("/github", routing::post(github_webhook))
+        .with_state(key)
+}
+
+/// The part of the GitHub push event payload that we need
+#[derive(Debug, Deserialize)]
+struct PushEvent {
+    #[serde(rename = "ref")]
+    reference: String,
+    repository: PushEventRepository,
+}
+
+#[derive(Debug, Deserialize)]
+struct PushEventRepository {
+    /// `owner/repo`
+    full_name: String,
+}
+
+async fn github_webhook(
+    State(key): State<Arc<hmac::Key>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, String) {
+    if let Err(e) = verify_signature(&key, &headers, &body) {
+        return (StatusCode::UNAUTHORIZED, e);
+    }
+    let event = headers
+        .get("X-GitHub-Event")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    match event {
+        "ping" => return (StatusCode::OK, "pong".to_string()),
+        "push" => {}
+        _ => return (StatusCode::OK, format!("ignored event: {event}")),
+    }
+
+    let event: PushEvent = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Failed to parse push event: {e}");
+            return (StatusCode::BAD_REQUEST, "Invalid push event".to_string());
+        }
+    };
+    let (owner, repo) = match event.repository.full_name.split_once('/') {
+        Some(x) => x,
+        None => return (StatusCode::BAD_REQUEST, "Invalid repository".to_string()),
+    };
+    let reference = event
+        .reference
+        .strip_prefix("refs/heads/")
+        .or_else(|| event.reference.strip_prefix("refs/tags/"))
+        .unwrap_or(&event.reference);
+
+    info!("received push event for {owner}/{repo}/{reference}");
+    match compiler::purge_project(owner, repo, reference).await {
+        Ok(result) => (
+            StatusCode::OK,
+            format!(
+                "purged {} contexts and {} resources",
+                result.contexts, result.resources
+            ),
+        ),
+        Err(e) => (StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+/// Verify the HMAC-SHA256 signature of the body in the `X-Hub-Signature-256` header
+fn verify_signature(key: &hmac::Key, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+    let signature = match headers.get("X-Hub-Signature-256") {
+        Some(v) => v,
+        None => return Err("Missing signature".to_string()),
+    };
+    let signature = signature
+        .to_str()
+        .ok()
+        .and_then(|v| v.strip_prefix("sha256="))
+        .and_then(decode_hex);
+    let signature = match signature {
+        Some(v) => v,
+        None => return Err("Invalid signature".to_string()),
+    };
+    if hmac::verify(key, body, &signature).is_err() {
+        error!("Webhook signature mismatch");
+        return Err("Invalid signature".to_string());
+    }
+    Ok(())
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use axum::http::HeaderValue;
+
+    fn signed_headers(signature: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Hub-Signature-256",
+            HeaderValue::from_str(signature).unwrap(),
+        );
+        headers
+    }
+
+    fn sign(key: &hmac::Key, body: &[u8]) -> String {
+        let tag = hmac::sign(key, body);
+        let hex = tag
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        format!("sha256={hex}")
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex(""), Some(vec![]));
+        assert_eq!(decode_hex("00ff1A"), Some(vec![0x00, 0xff, 0x1a]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+        assert_eq!(decode_hex("+f"), None);
+        assert_eq!(decode_hex("\u{e9}a"), None);
+    }
+
+    #[test]
+    fn test_verify_signature_valid() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let body = b"{\"ref\":\"refs/heads/main\"}";
+        let headers = signed_headers(&sign(&key, body));
+        assert_eq!(verify_signature(&key, &headers, body), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_signature_invalid() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let body = b"body";
+        // signed with a different secret
+        let other = hmac::Key::new(hmac::HMAC_SHA256, b"other");
+        let headers = signed_headers(&sign(&other, body));
+        assert!(verify_signature(&key, &headers, body).is_err());
+        // signed with a different body
+        let headers = signed_headers(&sign(&key, b"other body"));
+        assert!(verify_signature(&key, &headers, body).is_err());
+    }
+
+    #[test]
+    fn test_verify_signature_malformed() {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+        let body = b"body";
+        assert!(verify_signature(&key, &HeaderMap::new(), body).is_err());
+        let valid = sign(&key, body);
+        let hex = valid.strip_prefix("sha256=").unwrap();
+        for signature in [
+            hex.to_string(),
+            format!("sha1={hex}"),
+            format!("sha256={}", &hex[1..]),
+            format!("sha256={}zz", &hex[2..]),
+            "sha256=".to_string(),
+        ] {
+            let headers = signed_headers(&signature);
+            assert!(
+                verify_signature(&key, &headers, body).is_err(),
+                "{signature}"
+            );
+        }
+    }
+}
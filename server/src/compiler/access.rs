@@ -0,0 +1,180 @@
+use once_cell::sync::OnceCell;
+use tracing::{error, info};
+
+use celerc::res;
+
+static REPO_ACCESS: OnceCell<RepoAccess> = OnceCell::new();
+
+/// Setup which repos can be loaded by the server, from comma separated lists of
+/// `owner/repo` or `owner/*` patterns
+///
+/// If the allowlist is set, only repos in the allowlist can be loaded.
+/// Repos in the denylist cannot be loaded, even if they are in the allowlist.
+pub fn setup_repo_access(allowlist: Option<&str>, denylist: Option<&str>) -> Result<(), String> {
+    let allow = match allowlist.filter(|x| !x.trim().is_empty()) {
+        Some(list) => Some(parse_patterns(list)?),
+        None => None,
+    };
+    let deny = match denylist {
+        Some(list) => parse_patterns(list)?,
+        None => vec![],
+    };
+    if allow.is_none() && deny.is_empty() {
+        return Ok(());
+    }
+    info!("setting up repo access list...");
+    if REPO_ACCESS.set(RepoAccess { allow, deny }).is_err() {
+        error!("failed to set repo access list because it is already set!");
+    }
+    Ok(())
+}
+
+/// Check if the repo can be loaded by the server. The owner could be prefixed
+/// with the remote host, such as `gitlab:owner`
+pub fn check_repo_access(owner: &str, repo: &str) -> Result<(), String> {
+    match REPO_ACCESS.get() {
+        Some(access) => access.check(owner, repo),
+        None => Ok(()),
+    }
+}
+
+/// Check if the URL of a remote resource can be loaded by the server
+///
+/// The repo of the URL is found with the remote hosts. If the allowlist is set,
+/// URLs that are not in a repo on any host cannot be loaded
+pub fn check_url_access(url: &str) -> Result<(), String> {
+    let access = match REPO_ACCESS.get() {
+        Some(access) => access,
+        None => return Ok(()),
+    };
+    let registry = res::remote_hosts::get_registry();
+    match registry.match_url(url) {
+        Some((host, owner, repo)) => {
+            let owner = if host == res::DEFAULT_REMOTE_HOST {
+                owner
+            } else {
+                format!("{host}:{owner}")
+            };
+            access.check(&owner, &repo)
+        }
+        None if access.allow.is_some() => {
+            Err(format!("Loading {url} is not allowed on this server"))
+        }
+        None => Ok(()),
+    }
+}
+
+struct RepoAccess {
+    allow: Option<Vec<RepoPattern>>,
+    deny: Vec<RepoPattern>,
+}
+
+impl RepoAccess {
+    fn check(&self, owner: &str, repo: &str) -> Result<(), String> {
+        // the default host is the same as no host
+        let owner = match res::split_remote_host(owner) {
+            (Some(res::DEFAULT_REMOTE_HOST), owner) => owner,
+            _ => owner,
+        };
+        let is_match = |pattern: &RepoPattern| pattern.matches(owner, repo);
+        let is_allowed = match &self.allow {
+            Some(allow) => allow.iter().any(is_match),
+            None => true,
+        };
+        if !is_allowed || self.deny.iter().any(is_match) {
+            return Err(format!(
+                "Loading {owner}/{repo} is not allowed on this server"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `owner/repo` or `owner/*`, compared case-insensitively
+struct RepoPattern {
+    owner: String,
+    /// None for any repo of the owner
+    repo: Option<String>,
+}
+
+impl RepoPattern {
+    fn matches(&self, owner: &str, repo: &str) -> bool {
+        self.owner.eq_ignore_ascii_case(owner)
+            && self
+                .repo
+                .as_ref()
+                .is_none_or(|x| x.eq_ignore_ascii_case(repo))
+    }
+}
+
+fn parse_patterns(list: &str) -> Result<Vec<RepoPattern>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|pattern| match pattern.split_once('/') {
+            Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() && !repo.contains('/') => {
+                Ok(RepoPattern {
+                    owner: owner.to_string(),
+                    repo: (repo != "*").then(|| repo.to_string()),
+                })
+            }
+            _ => Err(format!("invalid repo pattern: {pattern}")),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_access(allow: Option<&str>, deny: &str) -> RepoAccess {
+        RepoAccess {
+            allow: allow.map(|x| parse_patterns(x).unwrap()),
+            deny: parse_patterns(deny).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_parse_patterns() {
+        let patterns = parse_patterns(" foo/bar, Baz/* ,,").unwrap();
+        assert_eq!(patterns.len(), 2);
+        assert_eq!(patterns[0].owner, "foo");
+        assert_eq!(patterns[0].repo.as_deref(), Some("bar"));
+        assert_eq!(patterns[1].owner, "Baz");
+        assert_eq!(patterns[1].repo, None);
+
+        assert!(parse_patterns("").unwrap().is_empty());
+        assert!(parse_patterns("foo").is_err());
+        assert!(parse_patterns("foo/").is_err());
+        assert!(parse_patterns("/bar").is_err());
+        assert!(parse_patterns("foo/bar/baz").is_err());
+        assert!(parse_patterns("foo/bar,oops").is_err());
+    }
+
+    #[test]
+    fn test_pattern_matches() {
+        let patterns = parse_patterns("foo/bar,baz/*,gitlab:foo/*").unwrap();
+        assert!(patterns[0].matches("foo", "bar"));
+        assert!(patterns[0].matches("FOO", "Bar"));
+        assert!(!patterns[0].matches("foo", "bar2"));
+        assert!(!patterns[0].matches("foo2", "bar"));
+        assert!(patterns[1].matches("baz", "anything"));
+        assert!(!patterns[1].matches("foo", "baz"));
+        assert!(patterns[2].matches("gitlab:foo", "bar"));
+        assert!(!patterns[2].matches("foo", "bar"));
+    }
+
+    #[test]
+    fn test_check_allow_and_deny() {
+        let access = new_access(Some("foo/*"), "foo/secret");
+        assert!(access.check("foo", "bar").is_ok());
+        assert!(access.check("github:foo", "bar").is_ok());
+        assert!(access.check("foo", "secret").is_err());
+        assert!(access.check("other", "bar").is_err());
+        assert!(access.check("gitlab:foo", "bar").is_err());
+
+        let access = new_access(None, "bad/*");
+        assert!(access.check("foo", "bar").is_ok());
+        assert!(access.check("bad", "bar").is_err());
+    }
+}
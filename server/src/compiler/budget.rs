@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use futures::Future;
+use tokio::time::{self, Duration};
+use tracing::warn;
+
+/// Limits on the work done for one compile or export request, so a pathological
+/// project cannot tie up the server
+///
+/// The budget is shared by the loaders created for the request, which charge
+/// each resource loaded against it. Once a limit is exceeded, loading more resources fails,
+/// and the request should fail with the reason from [`CompileBudget::exceeded`].
+/// The time limit is enforced with [`run_with_budget`].
+#[derive(Debug)]
+pub struct CompileBudget {
+    timeout: Duration,
+    max_resources: usize,
+    max_bytes: usize,
+    max_lines: usize,
+    resources: AtomicUsize,
+    bytes: AtomicUsize,
+    /// Reason of the first limit exceeded
+    exceeded: OnceLock<String>,
+}
+
+impl CompileBudget {
+    /// Create a budget with the limits from the compiler config
+    pub fn new() -> Arc<Self> {
+        let config = super::get_compiler_config();
+        Arc::new(Self {
+            timeout: Duration::from_secs(config.compile_timeout_secs),
+            max_resources: config.max_resources_per_compile,
+            max_bytes: config.max_bytes_per_compile,
+            max_lines: config.max_lines_per_compile,
+            resources: AtomicUsize::new(0),
+            bytes: AtomicUsize::new(0),
+            exceeded: OnceLock::new(),
+        })
+    }
+
+    /// Charge a resource of the size against the budget.
+    /// Returns the reason as error if the budget is exceeded
+    pub fn charge_resource(&self, size: usize) -> Result<(), String> {
+        let resources = self.resources.fetch_add(1, Ordering::Relaxed) + 1;
+        if resources > self.max_resources {
+            return Err(self.exceed(format!(
+                "The project loads too many resources (max {})",
+                self.max_resources
+            )));
+        }
+        let bytes = self.bytes.fetch_add(size, Ordering::Relaxed) + size;
+        if bytes > self.max_bytes {
+            return Err(self.exceed(format!(
+                "The project loads too much data (max {} bytes)",
+                self.max_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the number of lines compiled against the budget.
+    /// Returns the reason as error if the budget is exceeded
+    pub fn check_lines(&self, lines: usize) -> Result<(), String> {
+        if lines > self.max_lines {
+            return Err(self.exceed(format!(
+                "The route has too many lines (max {})",
+                self.max_lines
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get the reason if any limit is exceeded
+    pub fn exceeded(&self) -> Option<&str> {
+        self.exceeded.get().map(String::as_str)
+    }
+
+    fn exceed(&self, reason: String) -> String {
+        warn!("compile budget exceeded: {reason}");
+        self.exceeded.get_or_init(|| reason).clone()
+    }
+}
+
+/// Run the future for a request with the budget
+///
+/// The future is dropped if it does not finish within the time limit.
+/// Returns the reason as error if the time limit or any other limit in the budget is exceeded
+pub async fn run_with_budget<T>(
+    budget: &CompileBudget,
+    future: impl Future<Output = T>,
+) -> Result<T, String> {
+    let output = match time::timeout(budget.timeout, future).await {
+        Ok(output) => output,
+        Err(_) => {
+            let reason = format!(
+                "Compilation timed out after {} seconds",
+                budget.timeout.as_secs()
+            );
+            return Err(budget.exceed(reason));
+        }
+    };
+    match budget.exceeded() {
+        Some(reason) => Err(reason.to_string()),
+        None => Ok(output),
+    }
+}
@@ -0,0 +1,376 @@
+//! Compiling projects uploaded as a bundle (zip or tar archive)
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use flate2::read::GzDecoder;
+
+use celerc::macros::async_trait;
+use celerc::prep::PrepResult;
+use celerc::res::{Loader, ResError, ResPath, ResResult, Resource};
+use celerc::{ContextBuilder, PrepCtx};
+
+use super::{CompileBudget, Credential, ServerResourceLoader};
+
+/// Maximum size of all the files in a bundle after extracting
+const MAX_BUNDLE_EXTRACTED_SIZE: u64 = 1024 * 1024 * 50; // 50 MB
+/// Maximum number of files in a bundle
+const MAX_BUNDLE_FILES: usize = 10000;
+
+/// Loader that loads local paths from the files in an uploaded bundle, and remote
+/// paths over the network like [`ServerResourceLoader`]
+///
+/// The bundle is kept in memory. If the bundle does not have `project.yaml` at the root,
+/// but all files are in the same top-level directory (like archives downloaded from GitHub),
+/// that directory is used as the root.
+pub struct BundleLoader {
+    files: HashMap<String, Arc<[u8]>>,
+    remote: Arc<ServerResourceLoader>,
+}
+
+impl BundleLoader {
+    /// Extract the bundle. The format (zip, tar or gzipped tar) is detected from the content.
+    /// Resources loaded from the bundle or over the network are charged against the budget
+    ///
+    /// Extracting is blocking. If any limit in the budget is exceeded (for example, because it
+    /// takes too long), extracting is stopped
+    pub fn new(
+        bundle: &[u8],
+        credential: Option<Credential>,
+        budget: Option<Arc<CompileBudget>>,
+    ) -> Result<Self, String> {
+        let limit = BundleLimit::new(budget.as_deref());
+        let entries = if bundle.starts_with(b"PK\x03\x04") || bundle.starts_with(b"PK\x05\x06") {
+            extract_zip(bundle, limit)?
+        } else if bundle.starts_with(&[0x1f, 0x8b]) {
+            extract_tar(GzDecoder::new(bundle), limit)?
+        } else {
+            extract_tar(bundle, limit)?
+        };
+        let files = strip_top_level_dir(entries);
+        if !files.contains_key("project.yaml") {
+            return Err("The bundle does not have a project.yaml at the root".to_string());
+        }
+        let remote = super::loader::get_loader(credential, budget).map_err(|e| e.to_string())?;
+        Ok(Self { files, remote })
+    }
+}
+
+#[async_trait]
+impl Loader for BundleLoader {
+    async fn load_raw(&self, path: &ResPath) -> ResResult<Arc<[u8]>> {
+        match path {
+            ResPath::Local(_) => match self.files.get(&path.to_string()) {
+                Some(bytes) => {
+                    self.remote
+                        .charge_budget(bytes.len())
+                        .map_err(|e| ResError::FailToLoadFile(path.to_string(), e))?;
+                    Ok(Arc::clone(bytes))
+                }
+                None => Err(ResError::NotFound(path.to_string())),
+            },
+            ResPath::Remote(_, _) => self.remote.load_raw(path).await,
+        }
+    }
+
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        if !path.is_local() {
+            return self.remote.list_dir(path).await;
+        }
+        let dir = path.to_string();
+        let prefix = if dir.is_empty() {
+            dir
+        } else {
+            format!("{dir}/")
+        };
+        let mut entries = BTreeSet::new();
+        for name in self.files.keys() {
+            let rest = match name.strip_prefix(&prefix) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            match rest.split_once('/') {
+                Some((sub_dir, _)) => entries.insert(format!("{sub_dir}/")),
+                None => entries.insert(rest.to_string()),
+            };
+        }
+        if entries.is_empty() {
+            return Err(ResError::FailToListDir(
+                path.to_string(),
+                "Directory not found in bundle".to_string(),
+            ));
+        }
+        Ok(entries.into_iter().collect())
+    }
+}
+
+/// Create a context for a project uploaded as a bundle
+pub async fn new_bundle_context(
+    loader: BundleLoader,
+    entry_path: Option<String>,
+) -> PrepResult<PrepCtx<BundleLoader>> {
+    let res_path = ResPath::new_local_unchecked("project.yaml");
+    let resource = Resource::new(res_path, Arc::new(loader));
+    ContextBuilder::new("bundle".to_string(), resource)
+        .entry_point(entry_path)
+        .with_route_built()
+        .build_context()
+        .await
+}
+
+/// Files extracted from the bundle, keyed by the normalized path
+type BundleEntries = Vec<(String, Vec<u8>)>;
+
+fn extract_zip(bundle: &[u8], mut limit: BundleLimit) -> Result<BundleEntries, String> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(bundle)).map_err(|e| format!("Invalid zip: {e}"))?;
+    let mut entries = vec![];
+    for i in 0..archive.len() {
+        let file = archive
+            .by_index(i)
+            .map_err(|e| format!("Invalid zip: {e}"))?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = match file.enclosed_name() {
+            Some(path) => normalize_path(path)?,
+            None => return Err(format!("Invalid path in bundle: {}", file.name())),
+        };
+        let data = limit.read(file)?;
+        entries.push((path, data));
+    }
+    Ok(entries)
+}
+
+fn extract_tar(reader: impl Read, mut limit: BundleLimit) -> Result<BundleEntries, String> {
+    let mut archive = tar::Archive::new(reader);
+    let mut entries = vec![];
+    let iter = archive.entries().map_err(|e| format!("Invalid tar: {e}"))?;
+    for entry in iter {
+        let entry = entry.map_err(|e| format!("Invalid tar: {e}"))?;
+        // only regular files are extracted, links are ignored
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().map_err(|e| format!("Invalid tar: {e}"))?;
+        let path = normalize_path(&path)?;
+        let data = limit.read(entry)?;
+        entries.push((path, data));
+    }
+    Ok(entries)
+}
+
+/// Normalize the path in the archive, making sure it's inside the bundle
+fn normalize_path(path: &Path) -> Result<String, String> {
+    let path_str = match path.to_str() {
+        Some(path) => path.replace('\\', "/"),
+        None => return Err(format!("Invalid path in bundle: {}", path.display())),
+    };
+    if path_str.starts_with('/') {
+        return Err(format!("Invalid path in bundle: {path_str}"));
+    }
+    match ResPath::new_local(&path_str) {
+        Some(res_path) => Ok(res_path.to_string()),
+        None => Err(format!("Invalid path in bundle: {path_str}")),
+    }
+}
+
+/// If all files are in the same top-level directory and there's no project.yaml at the root,
+/// remove the directory from the paths
+fn strip_top_level_dir(entries: BundleEntries) -> HashMap<String, Arc<[u8]>> {
+    let has_root_project = entries.iter().any(|(path, _)| path == "project.yaml");
+    let top_level = match entries.first() {
+        Some((path, _)) if !has_root_project => path.split_once('/').map(|(dir, _)| dir),
+        _ => None,
+    };
+    let top_level = top_level.filter(|dir| {
+        entries
+            .iter()
+            .all(|(path, _)| matches!(path.split_once('/'), Some((d, _)) if d == *dir))
+    });
+    let prefix = top_level.map(|dir| format!("{dir}/"));
+    entries
+        .into_iter()
+        .map(|(path, data)| {
+            let path = match &prefix {
+                Some(prefix) => path[prefix.len()..].to_string(),
+                None => path,
+            };
+            (path, Arc::from(data))
+        })
+        .collect()
+}
+
+/// Keep track of the limits when extracting the bundle
+struct BundleLimit<'a> {
+    max_size: u64,
+    max_files: usize,
+    total_size: u64,
+    file_count: usize,
+    budget: Option<&'a CompileBudget>,
+}
+
+impl<'a> BundleLimit<'a> {
+    fn new(budget: Option<&'a CompileBudget>) -> Self {
+        Self::with_limits(MAX_BUNDLE_EXTRACTED_SIZE, MAX_BUNDLE_FILES, budget)
+    }
+
+    fn with_limits(max_size: u64, max_files: usize, budget: Option<&'a CompileBudget>) -> Self {
+        Self {
+            max_size,
+            max_files,
+            total_size: 0,
+            file_count: 0,
+            budget,
+        }
+    }
+
+    fn read(&mut self, reader: impl Read) -> Result<Vec<u8>, String> {
+        if let Some(reason) = self.budget.and_then(|b| b.exceeded()) {
+            return Err(reason.to_string());
+        }
+        self.file_count += 1;
+        if self.file_count > self.max_files {
+            return Err(format!(
+                "The bundle has too many files (max {})",
+                self.max_files
+            ));
+        }
+        let remaining = self.max_size - self.total_size;
+        let mut data = vec![];
+        reader
+            .take(remaining + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to extract bundle: {e}"))?;
+        if data.len() as u64 > remaining {
+            return Err(format!(
+                "The bundle is too large when extracted (max {} bytes)",
+                self.max_size
+            ));
+        }
+        self.total_size += data.len() as u64;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Write;
+
+    fn create_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (path, data) in files {
+            writer
+                .start_file(*path, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn create_tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, data) in files {
+            let mut header = tar::Header::new_old();
+            // set the name directly, since the builder rejects paths like `..`
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn paths(entries: &BundleEntries) -> Vec<&str> {
+        entries.iter().map(|(path, _)| path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(normalize_path(Path::new("a/b.yaml")).unwrap(), "a/b.yaml");
+        assert_eq!(normalize_path(Path::new("a\\b.yaml")).unwrap(), "a/b.yaml");
+        assert_eq!(
+            normalize_path(Path::new("./a/../b.yaml")).unwrap(),
+            "b.yaml"
+        );
+        assert!(normalize_path(Path::new("/etc/passwd")).is_err());
+        assert!(normalize_path(Path::new("../b.yaml")).is_err());
+        assert!(normalize_path(Path::new("a/../../b.yaml")).is_err());
+        assert!(normalize_path(Path::new("..\\b.yaml")).is_err());
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        let bundle = create_zip(&[("project.yaml", b"a"), ("sub/route.yaml", b"b")]);
+        let entries = extract_zip(&bundle, BundleLimit::new(None)).unwrap();
+        assert_eq!(paths(&entries), ["project.yaml", "sub/route.yaml"]);
+        assert_eq!(entries[1].1, b"b");
+    }
+
+    #[test]
+    fn test_extract_zip_rejects_escape() {
+        // enclosed_name rejects paths outside of the archive
+        let bundle = create_zip(&[("project.yaml", b"a"), ("../evil.yaml", b"b")]);
+        assert!(extract_zip(&bundle, BundleLimit::new(None)).is_err());
+        let bundle = create_zip(&[("/etc/evil.yaml", b"b")]);
+        assert!(extract_zip(&bundle, BundleLimit::new(None)).is_err());
+    }
+
+    #[test]
+    fn test_extract_tar() {
+        let bundle = create_tar(&[("project.yaml", b"a"), ("sub/route.yaml", b"b")]);
+        let entries = extract_tar(bundle.as_slice(), BundleLimit::new(None)).unwrap();
+        assert_eq!(paths(&entries), ["project.yaml", "sub/route.yaml"]);
+        assert_eq!(entries[1].1, b"b");
+    }
+
+    #[test]
+    fn test_extract_tar_rejects_escape() {
+        let bundle = create_tar(&[("project.yaml", b"a"), ("../evil.yaml", b"b")]);
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::new(None)).is_err());
+        let bundle = create_tar(&[("sub/../../evil.yaml", b"b")]);
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::new(None)).is_err());
+        let bundle = create_tar(&[("/etc/evil.yaml", b"b")]);
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::new(None)).is_err());
+    }
+
+    #[test]
+    fn test_extract_limits() {
+        let files: &[(&str, &[u8])] = &[("a", b"12345"), ("b", b"67890")];
+        let bundle = create_zip(files);
+        assert!(extract_zip(&bundle, BundleLimit::with_limits(10, 2, None)).is_ok());
+        assert!(extract_zip(&bundle, BundleLimit::with_limits(9, 2, None)).is_err());
+        assert!(extract_zip(&bundle, BundleLimit::with_limits(10, 1, None)).is_err());
+
+        let bundle = create_tar(files);
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::with_limits(10, 2, None)).is_ok());
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::with_limits(9, 2, None)).is_err());
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::with_limits(10, 1, None)).is_err());
+    }
+
+    #[test]
+    fn test_extract_stops_when_budget_exceeded() {
+        let budget = CompileBudget::new();
+        let bundle = create_tar(&[("project.yaml", b"a")]);
+        assert!(extract_tar(bundle.as_slice(), BundleLimit::new(Some(&budget))).is_ok());
+        let _ = budget.check_lines(usize::MAX);
+        let result = extract_tar(bundle.as_slice(), BundleLimit::new(Some(&budget)));
+        assert_eq!(result.unwrap_err(), budget.exceeded().unwrap());
+    }
+
+    #[test]
+    fn test_strip_top_level_dir() {
+        let entries = vec![
+            ("repo/project.yaml".to_string(), vec![]),
+            ("repo/sub/route.yaml".to_string(), vec![]),
+        ];
+        let files = strip_top_level_dir(entries);
+        assert!(files.contains_key("project.yaml"));
+        assert!(files.contains_key("sub/route.yaml"));
+    }
+}
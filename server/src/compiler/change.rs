@@ -0,0 +1,57 @@
+//! Notifying live sessions when projects are updated
+
+use once_cell::sync::Lazy;
+use tokio::sync::broadcast;
+
+/// Max number of changes buffered for each subscriber. Subscribers that fall behind
+/// will see the changes as lagged, and should treat it as everything changed
+const CHANGE_CHANNEL_SIZE: usize = 64;
+
+static CHANGES: Lazy<broadcast::Sender<ProjectChange>> =
+    Lazy::new(|| broadcast::channel(CHANGE_CHANNEL_SIZE).0);
+
+/// A change to the source of projects
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectChange {
+    /// The project at the reference is updated (for all entry points)
+    Remote {
+        owner: String,
+        repo: String,
+        reference: String,
+    },
+    /// The local project is updated
+    Local,
+    /// Any project could be updated
+    All,
+}
+
+impl ProjectChange {
+    /// Check if the change affects the project at the reference
+    pub fn affects_remote(&self, owner: &str, repo: &str, reference: &str) -> bool {
+        match self {
+            Self::Remote {
+                owner: o,
+                repo: r,
+                reference: re,
+            } => o == owner && r == repo && re == reference,
+            Self::Local => false,
+            Self::All => true,
+        }
+    }
+
+    /// Check if the change affects the local project
+    pub fn affects_local(&self) -> bool {
+        matches!(self, Self::Local | Self::All)
+    }
+}
+
+/// Subscribe to changes of projects
+pub fn subscribe_changes() -> broadcast::Receiver<ProjectChange> {
+    CHANGES.subscribe()
+}
+
+/// Notify the subscribers about a change
+pub fn notify_change(change: ProjectChange) {
+    // error means there are no subscribers, which is fine
+    let _ = CHANGES.send(change);
+}
@@ -0,0 +1,60 @@
+use once_cell::sync::OnceCell;
+use tracing::{error, info};
+
+static CONFIG: OnceCell<CompilerConfig> = OnceCell::new();
+
+/// Setup the global config for caching and loading resources.
+/// Must be called before the caches are used
+pub fn setup_compiler_config(config: CompilerConfig) {
+    info!("setting up compiler config: {config:?}");
+    if CONFIG.set(config).is_err() {
+        error!("failed to set compiler config because it is already set!");
+    }
+}
+
+/// Get the global config, or the default config if it's not set
+pub fn get_compiler_config() -> &'static CompilerConfig {
+    CONFIG.get_or_init(CompilerConfig::default)
+}
+
+/// Config for caching and loading resources on the server
+#[derive(Debug, Clone)]
+pub struct CompilerConfig {
+    /// Max number of prep contexts in the cache
+    pub prep_cache_size: usize,
+    /// How long a prep context is cached, in seconds
+    pub prep_cache_lifespan_secs: u64,
+    /// Max number of resources in the memory cache
+    pub resource_cache_size: usize,
+    /// How long a cached resource is used without revalidating, in seconds
+    pub resource_fresh_secs: u64,
+    /// Max size of a resource loaded over the network, in bytes
+    pub max_resource_size: usize,
+    /// Max number of attempts to fetch a resource
+    pub fetch_attempts: u32,
+    /// Max time for a compile or export request, in seconds
+    pub compile_timeout_secs: u64,
+    /// Max number of resources loaded in a compile or export request
+    pub max_resources_per_compile: usize,
+    /// Max total size of resources loaded in a compile or export request, in bytes
+    pub max_bytes_per_compile: usize,
+    /// Max number of lines in the route compiled in a request
+    pub max_lines_per_compile: usize,
+}
+
+impl Default for CompilerConfig {
+    fn default() -> Self {
+        Self {
+            prep_cache_size: 32,
+            prep_cache_lifespan_secs: 301,
+            resource_cache_size: 128,
+            resource_fresh_secs: 301,
+            max_resource_size: 1024 * 1024 * 10, // 10 MB
+            fetch_attempts: 3,
+            compile_timeout_secs: 30,
+            max_resources_per_compile: 1024,
+            max_bytes_per_compile: 1024 * 1024 * 100, // 100 MB
+            max_lines_per_compile: 65536,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+use tracing::{error, info};
+
+use celerc::res;
+
+/// Only requests to GitHub raw content are authenticated, so the token is not
+/// leaked to other hosts
+const AUTHENTICATED_URL_PREFIX: &str = "https://raw.githubusercontent.com/";
+
+static DEFAULT_CREDENTIAL: OnceCell<Credential> = OnceCell::new();
+
+/// Setup the credential used when the request does not have one
+pub fn setup_default_credential(token: Option<&str>) {
+    let token = match token.filter(|x| !x.is_empty()) {
+        Some(token) => token,
+        None => return,
+    };
+    info!("setting up default credential...");
+    if DEFAULT_CREDENTIAL.set(Credential::new(token)).is_err() {
+        error!("failed to set default credential because it is already set!");
+    }
+}
+
+/// Get the credential for a request. The token from the request takes
+/// precedence over the default credential
+pub fn get_credential(request_token: Option<&str>) -> Option<Credential> {
+    match request_token.filter(|x| !x.is_empty()) {
+        Some(token) => Some(Credential::new(token)),
+        None => get_default_credential(),
+    }
+}
+
+/// Get the default credential configured for the server
+pub fn get_default_credential() -> Option<Credential> {
+    DEFAULT_CREDENTIAL.get().cloned()
+}
+
+/// Token for accessing private repositories
+///
+/// Resources loaded with a credential are cached separately for each credential
+/// (identified by the hash of the token), so they are not served to requests
+/// without the same credential
+#[derive(Clone)]
+pub struct Credential {
+    token: Arc<str>,
+    id: Arc<str>,
+}
+
+impl Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credential")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Credential {
+    pub fn new(token: &str) -> Self {
+        let hash = res::hash_content(token.as_bytes());
+        let id = hash.strip_prefix("sha256-").unwrap_or(&hash);
+        Self {
+            token: Arc::from(token),
+            id: Arc::from(&id[..16]),
+        }
+    }
+
+    /// Get the id of the credential to use in cache keys. Does not expose the token
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// If the credential should be sent when fetching the url
+    pub fn applies_to(&self, url: &str) -> bool {
+        url.starts_with(AUTHENTICATED_URL_PREFIX)
+    }
+}
@@ -0,0 +1,418 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+use celerc::res;
+
+static DISK_CACHE: OnceCell<DiskCache> = OnceCell::new();
+
+/// Extension of the data files
+const DATA_EXT: &str = "bin";
+/// Extension of the metadata files
+const META_EXT: &str = "json";
+/// Extension of the temporary files being written
+const TEMP_EXT: &str = "tmp";
+/// When evicting, entries are removed until the size is below this fraction of the max size,
+/// so eviction doesn't run on every write
+const EVICT_TARGET_RATIO: f64 = 0.8;
+
+/// Counter for creating unique temporary file names
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Setup the global disk cache for resources in the directory, with the max total size in bytes
+pub async fn setup_disk_cache(dir: &str, max_size: u64) -> io::Result<()> {
+    info!("setting up disk resource cache at {dir}...");
+    let cache = DiskCache::new(dir, max_size).await?;
+    if DISK_CACHE.set(cache).is_err() {
+        error!("failed to set disk resource cache because it is already set!");
+    }
+    Ok(())
+}
+
+/// Get the global disk cache, if it's enabled
+pub fn get_disk_cache() -> Option<&'static DiskCache> {
+    DISK_CACHE.get()
+}
+
+/// Validators from the response, used to revalidate the cached data with
+/// conditional requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheValidators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// If there are any validators to send a conditional request with
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Entry loaded from the disk cache
+pub struct DiskCacheEntry {
+    pub data: Vec<u8>,
+    pub validators: CacheValidators,
+}
+
+/// Metadata stored next to the data on disk
+#[derive(Debug, Serialize, Deserialize)]
+struct DiskCacheMeta {
+    key: String,
+    #[serde(flatten)]
+    validators: CacheValidators,
+}
+
+/// Persistent cache for resources loaded over the network, keyed by URL
+/// (and the scope, see [`ResourceCache::get_or_fetch`](super::ResourceCache::get_or_fetch))
+///
+/// Unlike the in-memory [`ResourceCache`](super::ResourceCache), entries are kept across server
+/// restarts. The entries are revalidated with the server using the ETag or Last-Modified
+/// validators instead of being fetched again.
+///
+/// Files are written to a temporary file first and renamed into place, so a crash never
+/// leaves a partial file. When the total size exceeds the max size, the least recently
+/// used entries are removed.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_size: u64,
+    /// Estimated total size of the files in the cache
+    size: AtomicU64,
+    /// Lock so only one eviction runs at a time
+    evict_lock: Mutex<()>,
+}
+
+impl DiskCache {
+    /// Create the cache in the directory with the max total size in bytes,
+    /// creating the directory if needed
+    pub async fn new(dir: impl AsRef<Path>, max_size: u64) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).await?;
+        let cache = Self {
+            dir,
+            max_size,
+            size: AtomicU64::new(0),
+            evict_lock: Mutex::new(()),
+        };
+        // the temporary files are left by writes that did not finish
+        let size = cache.evict(true).await?;
+        info!("disk resource cache has {size} bytes");
+        Ok(cache)
+    }
+
+    /// Get the entry for the URL in the scope if it's in the cache
+    pub async fn get(&self, url: &str, scope: Option<&str>) -> Option<DiskCacheEntry> {
+        let key = super::make_resource_key(url, scope);
+        let (data_path, meta_path) = self.entry_paths(&key);
+        let meta = fs::read(&meta_path).await.ok()?;
+        let meta: DiskCacheMeta = serde_json::from_slice(&meta).ok()?;
+        if meta.key != key {
+            return None;
+        }
+        let data = fs::read(&data_path).await.ok()?;
+        // the modified time of the metadata is used as the last access time for eviction
+        touch(&meta_path).await;
+        Some(DiskCacheEntry {
+            data,
+            validators: meta.validators,
+        })
+    }
+
+    /// Remove the entry for the URL in the scope, for example if the resource no longer exists
+    pub async fn remove(&self, url: &str, scope: Option<&str>) {
+        let key = super::make_resource_key(url, scope);
+        let (data_path, meta_path) = self.entry_paths(&key);
+        // remove the metadata first, so the entry is not loaded with the data missing
+        let mut removed = 0;
+        for path in [&meta_path, &data_path] {
+            let size = fs::metadata(path)
+                .await
+                .map(|m| m.len())
+                .unwrap_or_default();
+            if fs::remove_file(path).await.is_ok() {
+                removed += size;
+            }
+        }
+        self.sub_size(removed);
+    }
+
+    /// Store the data for the URL in the scope. Errors are logged and ignored
+    pub async fn set(
+        &self,
+        url: &str,
+        scope: Option<&str>,
+        data: &[u8],
+        validators: &CacheValidators,
+    ) {
+        let key = super::make_resource_key(url, scope);
+        let (data_path, meta_path) = self.entry_paths(&key);
+        let meta = DiskCacheMeta {
+            key,
+            validators: validators.clone(),
+        };
+        let meta = match serde_json::to_vec(&meta) {
+            Ok(meta) => meta,
+            Err(e) => {
+                error!("failed to serialize disk cache metadata for {url}: {e}");
+                return;
+            }
+        };
+        // write the data first, so the metadata never points to incomplete data
+        let old_size = self.entry_size(&data_path, &meta_path).await;
+        let _ = fs::remove_file(&meta_path).await;
+        if let Err(e) = self.write_atomic(&data_path, data).await {
+            error!("failed to write disk cache data for {url}: {e}");
+            let _ = fs::remove_file(&data_path).await;
+            self.sub_size(old_size);
+            return;
+        }
+        if let Err(e) = self.write_atomic(&meta_path, &meta).await {
+            error!("failed to write disk cache metadata for {url}: {e}");
+        }
+        let new_size = self.entry_size(&data_path, &meta_path).await;
+        self.sub_size(old_size);
+        let size = self.size.fetch_add(new_size, Ordering::Relaxed) + new_size;
+        if size > self.max_size {
+            if let Err(e) = self.evict(false).await {
+                error!("failed to evict disk cache entries: {e}");
+            }
+        }
+    }
+
+    /// Write the file to a temporary path and rename it into place
+    async fn write_atomic(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        let id = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let temp_path = path.with_extension(format!("{id}.{TEMP_EXT}"));
+        if let Err(e) = fs::write(&temp_path, data).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        if let Err(e) = fs::rename(&temp_path, path).await {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Get the size of the files of the entry on disk
+    async fn entry_size(&self, data_path: &Path, meta_path: &Path) -> u64 {
+        let mut size = 0;
+        for path in [data_path, meta_path] {
+            if let Ok(metadata) = fs::metadata(path).await {
+                size += metadata.len();
+            }
+        }
+        size
+    }
+
+    fn sub_size(&self, size: u64) {
+        let _ = self
+            .size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                Some(x.saturating_sub(size))
+            });
+    }
+
+    /// Scan the directory, and remove the least recently used entries if the total size
+    /// is over the max size. If `cleanup` is true, temporary files and data without
+    /// metadata left by incomplete writes are also removed.
+    /// Returns the total size after evicting
+    async fn evict(&self, cleanup: bool) -> io::Result<u64> {
+        // skip if another eviction is running
+        let _guard = match self.evict_lock.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return Ok(self.size.load(Ordering::Relaxed)),
+        };
+        let mut entries = vec![];
+        let mut data_paths = vec![];
+        let mut size = 0;
+        let mut read_dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            let metadata = match entry.metadata().await {
+                Ok(metadata) if metadata.is_file() => metadata,
+                _ => continue,
+            };
+            let ext = path
+                .extension()
+                .and_then(|x| x.to_str())
+                .unwrap_or_default();
+            match ext {
+                TEMP_EXT if cleanup => {
+                    let _ = fs::remove_file(&path).await;
+                }
+                DATA_EXT => {
+                    size += metadata.len();
+                    data_paths.push((metadata.len(), path));
+                }
+                META_EXT => {
+                    size += metadata.len();
+                    let accessed = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    entries.push((accessed, path));
+                }
+                _ => {}
+            }
+        }
+        if cleanup {
+            for (data_size, data_path) in data_paths {
+                let has_meta = fs::try_exists(data_path.with_extension(META_EXT))
+                    .await
+                    .unwrap_or(true);
+                if !has_meta && fs::remove_file(&data_path).await.is_ok() {
+                    size = size.saturating_sub(data_size);
+                }
+            }
+        }
+        if size > self.max_size {
+            let target = (self.max_size as f64 * EVICT_TARGET_RATIO) as u64;
+            warn!("disk resource cache has {size} bytes, evicting entries...");
+            entries.sort();
+            for (_, meta_path) in entries {
+                if size <= target {
+                    break;
+                }
+                let data_path = meta_path.with_extension(DATA_EXT);
+                size = size.saturating_sub(self.entry_size(&data_path, &meta_path).await);
+                let _ = fs::remove_file(&meta_path).await;
+                let _ = fs::remove_file(&data_path).await;
+            }
+        }
+        self.size.store(size, Ordering::Relaxed);
+        Ok(size)
+    }
+
+    /// Get the path of the data and metadata files for the cache key
+    fn entry_paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        let key = res::hash_content(key.as_bytes());
+        (
+            self.dir.join(format!("{key}.{DATA_EXT}")),
+            self.dir.join(format!("{key}.{META_EXT}")),
+        )
+    }
+}
+
+/// Update the modified time of the file to now. Errors are ignored
+async fn touch(path: &Path) {
+    let path = path.to_path_buf();
+    let _ = tokio::task::spawn_blocking(move || {
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+    })
+    .await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tokio::time::{self, Duration};
+
+    const URL: &str = "https://example.com/file.txt";
+
+    /// Create an empty directory for the test
+    async fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "celery-disk-cache-test-{}-{name}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir).await;
+        dir
+    }
+
+    fn etag(value: &str) -> CacheValidators {
+        CacheValidators {
+            etag: Some(value.to_string()),
+            last_modified: None,
+        }
+    }
+
+    async fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = vec![];
+        let mut read_dir = fs::read_dir(dir).await.unwrap();
+        while let Some(entry) = read_dir.next_entry().await.unwrap() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_set_get_remove() {
+        let dir = test_dir("set-get").await;
+        let cache = DiskCache::new(&dir, 1024 * 1024).await.unwrap();
+        assert!(cache.get(URL, None).await.is_none());
+
+        cache.set(URL, None, b"hello", &etag("1")).await;
+        let entry = cache.get(URL, None).await.unwrap();
+        assert_eq!(entry.data, b"hello");
+        assert_eq!(entry.validators, etag("1"));
+        // scopes are separate
+        assert!(cache.get(URL, Some("user")).await.is_none());
+
+        cache.set(URL, None, b"world", &etag("2")).await;
+        let entry = cache.get(URL, None).await.unwrap();
+        assert_eq!(entry.data, b"world");
+        assert_eq!(entry.validators, etag("2"));
+        // no temporary files are left
+        assert_eq!(file_names(&dir).await.len(), 2);
+
+        cache.remove(URL, None).await;
+        assert!(cache.get(URL, None).await.is_none());
+        assert!(file_names(&dir).await.is_empty());
+        assert_eq!(cache.size.load(Ordering::Relaxed), 0);
+
+        let _ = fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_incomplete_writes() {
+        let dir = test_dir("cleanup").await;
+        let cache = DiskCache::new(&dir, 1024 * 1024).await.unwrap();
+        cache.set(URL, None, b"hello", &etag("1")).await;
+        drop(cache);
+        fs::write(dir.join("abc.bin"), b"orphan").await.unwrap();
+        fs::write(dir.join("abc.3.tmp"), b"partial").await.unwrap();
+
+        let cache = DiskCache::new(&dir, 1024 * 1024).await.unwrap();
+        assert_eq!(file_names(&dir).await.len(), 2);
+        assert_eq!(cache.get(URL, None).await.unwrap().data, b"hello");
+
+        let _ = fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_evict_least_recently_used() {
+        let dir = test_dir("evict").await;
+        let data = vec![0u8; 400];
+        let cache = DiskCache::new(&dir, 1200).await.unwrap();
+        let url = |i: usize| format!("{URL}{i}");
+        cache.set(&url(0), None, &data, &etag("0")).await;
+        // make sure the access times are different
+        time::sleep(Duration::from_millis(20)).await;
+        cache.set(&url(1), None, &data, &etag("1")).await;
+        time::sleep(Duration::from_millis(20)).await;
+        // access 0 so 1 is the least recently used
+        assert!(cache.get(&url(0), None).await.is_some());
+        time::sleep(Duration::from_millis(20)).await;
+        cache.set(&url(2), None, &data, &etag("2")).await;
+
+        assert!(cache.get(&url(0), None).await.is_some());
+        assert!(cache.get(&url(1), None).await.is_none());
+        assert!(cache.get(&url(2), None).await.is_some());
+        assert!(cache.size.load(Ordering::Relaxed) <= 1200);
+
+        let _ = fs::remove_dir_all(&dir).await;
+    }
+}
@@ -2,21 +2,33 @@ use instant::Instant;
 
 use celerc::pack::PackError;
 use celerc::plugin;
+use celerc::res::Loader;
 use celerc::{Compiler, ExpoDoc, ExportRequest, PrepCtx};
 
-use super::ServerResourceLoader;
+use crate::metrics::{self, CompilePhase};
 
-pub async fn export(
-    prep_ctx: &PrepCtx<ServerResourceLoader>,
+use super::CompileBudget;
+
+pub async fn export<L>(
+    prep_ctx: &PrepCtx<L>,
     start_time: Option<Instant>,
     plugin_options: Option<plugin::Options>,
     req: ExportRequest,
-) -> ExpoDoc {
+    budget: &CompileBudget,
+) -> ExpoDoc
+where
+    L: Loader,
+{
     let mut comp_ctx = prep_ctx.new_compilation(start_time).await;
     match comp_ctx.configure_plugins(plugin_options).await {
         Err(e) => export_with_pack_error(e),
         Ok(_) => match prep_ctx.create_compiler(comp_ctx).await {
-            Ok(x) => export_with_compiler(x, req).await,
+            Ok(x) => {
+                if let Some(start_time) = start_time {
+                    metrics::record_compile_phase(CompilePhase::Prep, start_time.elapsed());
+                }
+                export_with_compiler(x, req, budget).await
+            }
             Err((e, _)) => export_with_pack_error(e),
         },
     }
@@ -26,11 +38,22 @@ fn export_with_pack_error(error: PackError) -> ExpoDoc {
     ExpoDoc::Error(error.to_string())
 }
 
-async fn export_with_compiler(compiler: Compiler<'_>, req: ExportRequest) -> ExpoDoc {
+async fn export_with_compiler(
+    compiler: Compiler<'_>,
+    req: ExportRequest,
+    budget: &CompileBudget,
+) -> ExpoDoc {
+    let start_time = Instant::now();
     let mut comp_doc = compiler.compile().await;
+    metrics::record_compile_phase(CompilePhase::Comp, start_time.elapsed());
+    if let Err(e) = budget.check_lines(super::count_lines(&comp_doc)) {
+        return ExpoDoc::Error(e);
+    }
     if let Some(expo_doc) = comp_doc.run_exporter(&req).await {
         return expo_doc;
     }
+    let start_time = Instant::now();
     let exec_ctx = comp_doc.execute().await;
+    metrics::record_compile_phase(CompilePhase::Exec, start_time.elapsed());
     exec_ctx.run_exporter(req).await
 }
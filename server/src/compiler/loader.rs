@@ -1,23 +1,32 @@
 use std::sync::Arc;
 
-use axum::body::Bytes;
 use once_cell::sync::Lazy;
+use reqwest::header::{self, HeaderValue};
 use reqwest::{Client, StatusCode};
-use tracing::{error, info};
+use tokio::time::Duration;
+use tracing::{error, info, warn};
 
 use celerc::macros::async_trait;
 use celerc::res::{Loader, LoaderFactory, ResError, ResPath, ResResult};
 
-use super::ResourceCache;
+use crate::metrics;
 
-const MAX_RESOURCE_SIZE: usize = 1024 * 1024 * 10; // 10 MB
-static CACHE: Lazy<ResourceCache> = Lazy::new(ResourceCache::new);
+use super::{CacheValidators, CompileBudget, Credential, FetchOutput, ResourceCache};
+
+static CACHE: Lazy<ResourceCache> = Lazy::new(|| {
+    let config = super::get_compiler_config();
+    ResourceCache::new(
+        config.resource_cache_size,
+        Duration::from_secs(config.resource_fresh_secs),
+    )
+});
 
 struct ServerResourceLoaderFactory;
 impl LoaderFactory for ServerResourceLoaderFactory {
     fn create_loader(&self) -> ResResult<Arc<dyn Loader>> {
         let cache = CACHE.clone();
-        let loader = ServerResourceLoader::with_cache(cache)?;
+        let loader =
+            ServerResourceLoader::with_cache(cache, super::get_default_credential(), None)?;
         Ok(Arc::new(loader))
     }
 }
@@ -30,50 +39,135 @@ pub fn setup_global_loader() {
     }
 }
 
-pub fn get_loader() -> ResResult<Arc<ServerResourceLoader>> {
+/// Get a loader that loads resources with the credential, charging them against the budget
+pub fn get_loader(
+    credential: Option<Credential>,
+    budget: Option<Arc<CompileBudget>>,
+) -> ResResult<Arc<ServerResourceLoader>> {
     let cache = CACHE.clone();
-    let loader = ServerResourceLoader::with_cache(cache)?;
+    let loader = ServerResourceLoader::with_cache(cache, credential, budget)?;
     Ok(Arc::new(loader))
 }
 
+/// Remove the cached resources with URL starting with the prefix.
+/// Returns the number of resources removed
+pub async fn invalidate_resources(url_prefix: &str) -> usize {
+    CACHE.invalidate(url_prefix).await
+}
+
+/// Remove all cached resources. Returns the number of resources removed
+pub async fn clear_resources() -> usize {
+    CACHE.clear().await
+}
+
 /// Loader for loading resources from the web
 pub struct ServerResourceLoader {
     http_client: Client,
     cache: ResourceCache,
+    credential: Option<Credential>,
+    budget: Option<Arc<CompileBudget>>,
 }
 
 impl ServerResourceLoader {
-    pub fn with_cache(cache: ResourceCache) -> ResResult<Self> {
+    pub fn with_cache(
+        cache: ResourceCache,
+        credential: Option<Credential>,
+        budget: Option<Arc<CompileBudget>>,
+    ) -> ResResult<Self> {
         let http_client = create_http_client()?;
-        Ok(Self { http_client, cache })
+        Ok(Self {
+            http_client,
+            cache,
+            credential,
+            budget,
+        })
+    }
+
+    /// Charge a loaded resource against the budget of the loader, if any
+    pub fn charge_budget(&self, size: usize) -> Result<(), String> {
+        match &self.budget {
+            Some(budget) => budget.charge_resource(size),
+            None => Ok(()),
+        }
     }
 
     /// Load a resource from Url. Automatically retry if the request fails with retriable error
+    ///
+    /// Expired data in the memory cache, or data in the disk cache if enabled, is revalidated
+    /// with a conditional request, and is used if the request fails with a transient error
+    /// (network error or server error). Rejected requests are not retried
+    ///
+    /// Resources loaded with the credential are cached separately for the credential
     async fn load_url(&self, url: &str) -> ResResult<Arc<[u8]>> {
+        let credential = self.credential.as_ref().filter(|c| c.applies_to(url));
+        let scope = credential.map(|c| c.id());
         self.cache
-            .get_or_fetch(url, || async {
+            .get_or_fetch(url, scope, |validators| async move {
+                let disk_cache = super::get_disk_cache();
+                // the data in memory is at least as new as the data on disk
+                let cached = match (&validators, disk_cache) {
+                    (None, Some(disk_cache)) => disk_cache.get(url, scope).await,
+                    _ => None,
+                };
+                let validators = match &cached {
+                    Some(entry) => Some(&entry.validators).filter(|v| !v.is_empty()),
+                    None => validators.as_ref(),
+                };
                 // send the request, retry if failed
-                let retry = 3;
+                let config = super::get_compiler_config();
                 let mut last_error = None;
-                for _ in 0..retry {
-                    match self.fetch(url).await {
-                        Ok(data) => {
-                            if data.len() > MAX_RESOURCE_SIZE {
+                for i in 0..config.fetch_attempts {
+                    if i > 0 {
+                        metrics::record_fetch_retry();
+                    }
+                    match self.fetch(url, validators, credential).await {
+                        Ok(FetchOutput::NotFound) => {
+                            // don't retry if the resource does not exist
+                            info!("Resource not found: {url}");
+                            if let Some(disk_cache) = disk_cache {
+                                disk_cache.remove(url, scope).await;
+                            }
+                            return Ok(FetchOutput::NotFound);
+                        }
+                        Ok(FetchOutput::Rejected(e)) => {
+                            // don't retry or use the cached data if the request is rejected
+                            warn!("Resource request rejected: {e}");
+                            if let Some(disk_cache) = disk_cache {
+                                disk_cache.remove(url, scope).await;
+                            }
+                            return Ok(FetchOutput::Rejected(e));
+                        }
+                        Ok(FetchOutput::NotModified) => {
+                            return Ok(match cached {
+                                Some(entry) => FetchOutput::Modified(entry.data, entry.validators),
+                                None => FetchOutput::NotModified,
+                            });
+                        }
+                        Ok(FetchOutput::Modified(data, validators)) => {
+                            if data.len() > config.max_resource_size {
                                 // don't retry if the resource is too big
                                 let err = ResError::FailToLoadUrl(
                                     url.to_string(),
                                     "Resource is too large".to_string(),
                                 );
-                                return Err(err);
+                                return Ok(FetchOutput::Rejected(err));
+                            }
+                            if let Some(disk_cache) = disk_cache {
+                                disk_cache.set(url, scope, &data, &validators).await;
                             }
-                            return Ok(data.to_vec());
+                            return Ok(FetchOutput::Modified(data, validators));
                         }
                         Err(e) => {
+                            metrics::record_fetch_failure();
                             error!("Failed to fetch resource: {e}");
                             last_error = Some(e);
                         }
                     }
                 }
+                if let Some(entry) = cached {
+                    warn!("Failed to revalidate resource, using data from disk cache: {url}");
+                    return Ok(FetchOutput::Modified(entry.data, entry.validators));
+                }
                 error!("Failed to load resource after max retries!");
 
                 let error = last_error.unwrap_or_else(|| {
@@ -85,28 +179,85 @@ impl ServerResourceLoader {
             .await
     }
 
-    async fn fetch(&self, url: &str) -> Result<Bytes, ResError> {
-        let response = self.http_client.get(url).send().await.map_err(|e| {
+    /// Send the request for the URL. If validators are provided, a conditional request
+    /// is sent and the response could be not modified
+    async fn fetch(
+        &self,
+        url: &str,
+        validators: Option<&CacheValidators>,
+        credential: Option<&Credential>,
+    ) -> Result<FetchOutput, ResError> {
+        let mut request = self.http_client.get(url);
+        if let Some(credential) = credential {
+            let auth = format!("Bearer {}", credential.token());
+            let mut auth = HeaderValue::from_str(&auth).map_err(|_| {
+                ResError::FailToLoadUrl(url.to_string(), "Invalid credential".to_string())
+            })?;
+            auth.set_sensitive(true);
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        let conditional = match validators {
+            Some(validators) if !validators.is_empty() => {
+                if let Some(etag) = &validators.etag {
+                    request = request.header(header::IF_NONE_MATCH, etag);
+                }
+                if let Some(last_modified) = &validators.last_modified {
+                    request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+                }
+                true
+            }
+            _ => false,
+        };
+        let response = request.send().await.map_err(|e| {
             ResError::FailToLoadUrl(url.to_string(), format!("Failed to send request: {e}"))
         })?;
 
         let status = response.status();
+        if conditional && status == StatusCode::NOT_MODIFIED {
+            return Ok(FetchOutput::NotModified);
+        }
+        if status == StatusCode::NOT_FOUND || status == StatusCode::GONE {
+            return Ok(FetchOutput::NotFound);
+        }
         if status != StatusCode::OK {
             let err = ResError::FailToLoadUrl(
                 url.to_string(),
                 format!("Got response with status: {status}"),
             );
+            if is_rejected(status) {
+                return Ok(FetchOutput::Rejected(err));
+            }
             return Err(err);
         }
 
+        let headers = response.headers();
+        let get_header = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(|v| v.to_string())
+        };
+        let validators = CacheValidators {
+            etag: get_header(header::ETAG),
+            last_modified: get_header(header::LAST_MODIFIED),
+        };
+
         let bytes = response.bytes().await.map_err(|e| {
             ResError::FailToLoadUrl(url.to_string(), format!("Failed to parse response: {e}"))
         })?;
 
-        Ok(bytes)
+        Ok(FetchOutput::Modified(bytes.to_vec(), validators))
     }
 }
 
+/// Check if the status means the request is rejected, instead of failing
+/// temporarily like server errors. Timeout and rate limit errors are temporary
+fn is_rejected(status: StatusCode) -> bool {
+    status.is_client_error()
+        && status != StatusCode::REQUEST_TIMEOUT
+        && status != StatusCode::TOO_MANY_REQUESTS
+}
+
 fn create_http_client() -> ResResult<Client> {
     Client::builder()
         .user_agent("celery")
@@ -131,8 +282,12 @@ impl Loader for ServerResourceLoader {
             }
             ResPath::Remote(prefix, path) => format!("{prefix}{path}"),
         };
+        super::check_url_access(&url).map_err(|e| ResError::FailToLoadUrl(url.clone(), e))?;
         info!("Loading resource url: {url}");
 
-        self.load_url(&url).await
+        let data = self.load_url(&url).await?;
+        self.charge_budget(data.len())
+            .map_err(|e| ResError::FailToLoadUrl(url, e))?;
+        Ok(data)
     }
 }
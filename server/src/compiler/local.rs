@@ -0,0 +1,163 @@
+//! Compiling a project from a local directory, for running the server in local dev mode
+//!
+//! The directory is polled for changes, and live sessions subscribed to the local
+//! project are notified when any file is modified.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+use tokio::time::{self, Duration};
+use tracing::{error, info};
+
+use celerc::macros::async_trait;
+use celerc::prep::PrepResult;
+use celerc::res::{Loader, LocalLoader, ResError, ResPath, ResResult, Resource};
+use celerc::{ContextBuilder, PrepCtx};
+
+use super::{CompileBudget, ProjectChange};
+
+/// How often the local project is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Max number of files to check for changes. Files after this are ignored
+const MAX_WATCHED_FILES: usize = 10000;
+
+static LOCAL_ROOT: OnceCell<PathBuf> = OnceCell::new();
+
+/// Setup the local project directory and start watching it for changes
+pub fn setup_local_project(dir: &str) -> Result<(), String> {
+    info!("setting up local project at {dir}...");
+    // make sure the directory can be loaded
+    let loader = LocalLoader::new(dir).map_err(|e| e.to_string())?;
+    let root = loader.root().to_path_buf();
+    if LOCAL_ROOT.set(root.clone()).is_err() {
+        error!("failed to set local project because it is already set!");
+        return Ok(());
+    }
+    tokio::spawn(watch_local_project(root));
+    Ok(())
+}
+
+/// Check if the server has a local project
+pub fn has_local_project() -> bool {
+    LOCAL_ROOT.get().is_some()
+}
+
+/// Create a context for the local project. The project is not cached,
+/// since it's expected to change often
+pub async fn new_local_context(
+    entry_path: Option<String>,
+    budget: Option<Arc<CompileBudget>>,
+) -> PrepResult<PrepCtx<LocalProjectLoader>> {
+    let root = match LOCAL_ROOT.get() {
+        Some(root) => root,
+        None => {
+            return Err(ResError::Create("Local project is not enabled".to_string()).into());
+        }
+    };
+    let config = super::get_compiler_config();
+    let local = LocalLoader::new(root)?.with_max_size(config.max_resource_size as u64);
+    let remote = super::loader::get_loader(None, budget)?;
+    let loader = LocalProjectLoader { local, remote };
+    let res_path = ResPath::new_local_unchecked("project.yaml");
+    let resource = Resource::new(res_path, Arc::new(loader));
+    ContextBuilder::new("local".to_string(), resource)
+        .entry_point(entry_path)
+        .with_route_built()
+        .build_context()
+        .await
+}
+
+/// Loader that loads local paths from the local project, and remote
+/// paths over the network like [`ServerResourceLoader`](super::ServerResourceLoader)
+pub struct LocalProjectLoader {
+    local: LocalLoader,
+    remote: Arc<super::ServerResourceLoader>,
+}
+
+#[async_trait]
+impl Loader for LocalProjectLoader {
+    async fn load_raw(&self, path: &ResPath) -> ResResult<Arc<[u8]>> {
+        match path {
+            ResPath::Local(_) => {
+                let bytes = self.local.load_raw(path).await?;
+                self.remote
+                    .charge_budget(bytes.len())
+                    .map_err(|e| ResError::FailToLoadFile(path.to_string(), e))?;
+                Ok(bytes)
+            }
+            ResPath::Remote(_, _) => self.remote.load_raw(path).await,
+        }
+    }
+
+    async fn list_dir(&self, path: &ResPath) -> ResResult<Vec<String>> {
+        match path {
+            ResPath::Local(_) => self.local.list_dir(path).await,
+            ResPath::Remote(_, _) => self.remote.list_dir(path).await,
+        }
+    }
+}
+
+/// Poll the local project, and notify the subscribers when it changes
+async fn watch_local_project(root: PathBuf) {
+    let mut last = None;
+    loop {
+        time::sleep(POLL_INTERVAL).await;
+        let dir = root.clone();
+        let fingerprint = match tokio::task::spawn_blocking(move || fingerprint_dir(&dir)).await {
+            Ok(x) => x,
+            Err(e) => {
+                error!("failed to check local project for changes: {e}");
+                continue;
+            }
+        };
+        if last.is_some() && last != Some(fingerprint) {
+            info!("local project changed");
+            super::notify_change(ProjectChange::Local);
+        }
+        last = Some(fingerprint);
+    }
+}
+
+/// Compute a hash of the paths, sizes and modified times of the files in the directory.
+/// Hidden files and directories (starting with `.`) are skipped
+fn fingerprint_dir(root: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut stack = vec![root.to_path_buf()];
+    let mut count = 0;
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        let mut entries = entries.filter_map(|e| e.ok()).collect::<Vec<_>>();
+        // the order from read_dir is not guaranteed
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() {
+                stack.push(entry.path());
+                continue;
+            }
+            count += 1;
+            if count > MAX_WATCHED_FILES {
+                return hasher.finish();
+            }
+            entry.path().hash(&mut hasher);
+            metadata.len().hash(&mut hasher);
+            if let Ok(modified) = metadata.modified() {
+                modified.hash(&mut hasher);
+            }
+        }
+    }
+    hasher.finish()
+}
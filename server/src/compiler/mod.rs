@@ -1,10 +1,16 @@
+use std::sync::Arc;
+
 use celerc::pack::PackError;
 use instant::Instant;
+use tracing::{error, info};
 
+use celerc::json::{RouteBlobArrayIterResult, RouteBlobRef, RouteBlobSingleKeyObjectResult};
 use celerc::plugin::Options as PluginOptions;
-use celerc::res::{self, ResPath, ResResult, Resource};
+use celerc::res::{self, Loader, ResError, ResPath, ResResult, Resource};
 use celerc::{CompDoc, CompileContext, Compiler, ContextBuilder, ExpoContext, PrepCtx};
 
+use crate::metrics::{self, CompilePhase};
+
 mod loader;
 pub use loader::*;
 mod export;
@@ -15,14 +21,52 @@ mod prepctx_cache;
 pub use prepctx_cache::*;
 mod resource_cache;
 pub use resource_cache::*;
+mod disk_cache;
+pub use disk_cache::*;
+mod credential;
+pub use credential::*;
+mod bundle;
+pub use bundle::*;
+mod purge;
+pub use purge::*;
+mod config;
+pub use config::*;
+mod budget;
+pub use budget::*;
+mod access;
+pub use access::*;
+mod change;
+pub use change::*;
+mod local;
+pub use local::*;
+
+/// Setup the global registry of remote hosts, adding the hosts from the config
+/// on top of the default ones
+pub fn setup_remote_hosts(config: Option<&str>) -> Result<(), String> {
+    info!("setting up remote hosts...");
+    let mut registry = res::RemoteHostRegistry::default();
+    if let Some(config) = config {
+        registry
+            .register_from_config(config)
+            .map_err(|e| format!("invalid remote host config: {e}"))?;
+    }
+    if res::remote_hosts::set(Arc::new(registry)).is_err() {
+        error!("failed to set remote hosts because it is already set!");
+    }
+    Ok(())
+}
 
 /// Create a context builder for a project
+///
+/// The owner can be prefixed with the name of the remote host, such as `gitlab:owner`
 pub fn new_context_builder(
     owner: &str,
     repo: &str,
     reference: Option<&str>,
+    credential: Option<Credential>,
+    budget: Option<Arc<CompileBudget>>,
 ) -> ResResult<ContextBuilder<ServerResourceLoader>> {
-    let resource = new_root_resource(owner, repo, reference);
+    let resource = new_root_resource(owner, repo, reference, credential, budget);
     let source = format!("{}/{}/{}", owner, repo, reference.unwrap_or("main"));
     Ok(ContextBuilder::new(source, resource?))
 }
@@ -32,25 +76,46 @@ pub fn new_root_resource(
     owner: &str,
     repo: &str,
     reference: Option<&str>,
+    credential: Option<Credential>,
+    budget: Option<Arc<CompileBudget>>,
 ) -> ResResult<Resource<'static, ServerResourceLoader>> {
-    let loader = loader::get_loader();
-    let base_url = res::base_url(owner, repo, reference);
+    let loader = loader::get_loader(credential, budget);
+    let (host, owner) = res::split_remote_host(owner);
+    let base_url = match res::base_url(host, owner, repo, reference) {
+        Some(url) => url,
+        None => {
+            return Err(ResError::UnknownRemoteHost(
+                host.unwrap_or_default().to_string(),
+            ))
+        }
+    };
     let res_path = ResPath::new_remote_unchecked(base_url, "project.yaml");
     Ok(Resource::new(res_path, loader?))
 }
 
-pub async fn compile(
-    prep_ctx: &PrepCtx<ServerResourceLoader>,
+/// Compile the document. Returns the reason as error if the number of lines
+/// exceeds the budget
+pub async fn compile<'p, L>(
+    prep_ctx: &'p PrepCtx<L>,
     start_time: Option<Instant>,
     plugin_options: Option<PluginOptions>,
-) -> ExpoContext {
+    budget: &CompileBudget,
+) -> Result<ExpoContext<'p>, String>
+where
+    L: Loader,
+{
     let mut comp_ctx = prep_ctx.new_compilation(start_time).await;
     if let Err(e) = comp_ctx.configure_plugins(plugin_options).await {
-        return compile_with_pack_error(comp_ctx, e).await;
+        return Ok(compile_with_pack_error(comp_ctx, e).await);
     }
     match prep_ctx.create_compiler(comp_ctx).await {
-        Ok(x) => compile_with_compiler(x).await,
-        Err((e, comp_ctx)) => compile_with_pack_error(comp_ctx, e).await,
+        Ok(x) => {
+            if let Some(start_time) = start_time {
+                metrics::record_compile_phase(CompilePhase::Prep, start_time.elapsed());
+            }
+            compile_with_compiler(x, budget).await
+        }
+        Err((e, comp_ctx)) => Ok(compile_with_pack_error(comp_ctx, e).await),
     }
 }
 
@@ -60,8 +125,81 @@ async fn compile_with_pack_error(context: CompileContext<'_>, error: PackError)
     exec_ctx.prepare_exports().await
 }
 
-async fn compile_with_compiler(compiler: Compiler<'_>) -> ExpoContext {
+async fn compile_with_compiler<'p>(
+    compiler: Compiler<'p>,
+    budget: &CompileBudget,
+) -> Result<ExpoContext<'p>, String> {
+    // check the built route first, so large routes are not compiled at all
+    budget.check_lines(count_route_lines(RouteBlobRef::Blob(&compiler.route)))?;
+    let start_time = Instant::now();
     let comp_doc = compiler.compile().await;
+    metrics::record_compile_phase(CompilePhase::Comp, start_time.elapsed());
+    // plugins could add lines when compiling
+    budget.check_lines(count_lines(&comp_doc))?;
+    let start_time = Instant::now();
     let exec_ctx = comp_doc.execute().await;
-    exec_ctx.prepare_exports().await
+    metrics::record_compile_phase(CompilePhase::Exec, start_time.elapsed());
+    Ok(exec_ctx.prepare_exports().await)
+}
+
+/// Count the number of lines in the built route before compiling, which are the entries
+/// in the sections. Prefaces and invalid sections are not counted
+fn count_route_lines(route: RouteBlobRef<'_>) -> usize {
+    let sections = match route.try_as_array_iter() {
+        RouteBlobArrayIterResult::Ok(sections) => sections,
+        _ => return 0,
+    };
+    sections
+        .map(|section| match section.try_as_single_key_object() {
+            RouteBlobSingleKeyObjectResult::Ok(_, lines) => match lines.try_as_array_iter() {
+                RouteBlobArrayIterResult::Ok(lines) => lines.count(),
+                _ => 0,
+            },
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Count the number of lines in the compiled route
+fn count_lines(comp_doc: &CompDoc<'_>) -> usize {
+    comp_doc
+        .route
+        .iter()
+        .map(|section| section.lines.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use celerc::json::RouteBlob;
+    use serde_json::json;
+
+    #[test]
+    fn test_count_route_lines() {
+        let route = RouteBlob::Prim(json!([
+            "preface",
+            { "Section 1": ["line 1", "line 2", { "line 3": {} }] },
+            { "Section 2": [] },
+            { "Section 3": ["line 4"] },
+            { "Invalid": "not lines" },
+        ]));
+        assert_eq!(count_route_lines(RouteBlobRef::Blob(&route)), 4);
+
+        let route = RouteBlob::Array(vec![
+            RouteBlob::Object(
+                [(
+                    "Section".to_string(),
+                    RouteBlob::Array(vec![json!("line 1").into(), json!("line 2").into()]),
+                )]
+                .into(),
+            ),
+            RouteBlob::Prim(json!({ "Section 2": ["line 3"] })),
+        ]);
+        assert_eq!(count_route_lines(RouteBlobRef::Blob(&route)), 3);
+
+        let route = RouteBlob::Prim(json!("not an array"));
+        assert_eq!(count_route_lines(RouteBlobRef::Blob(&route)), 0);
+    }
 }
@@ -1,17 +1,29 @@
 //! Server-side plugin options parsing
 
 use celerc::plugin;
-use celerc::res::Resource;
-
-use super::ServerResourceLoader;
+use celerc::res::{Loader, Resource};
 
 /// Parse the plugin options, and return the error message if the options are invalid
-pub async fn parse_plugin_options(
+pub async fn parse_plugin_options<L>(
     plugin_options: &str,
-    root_resource: &Resource<'_, ServerResourceLoader>,
-) -> Result<plugin::Options, String> {
+    root_resource: &Resource<'_, L>,
+) -> Result<plugin::Options, String>
+where
+    L: Loader,
+{
     let raw_options: plugin::OptionsRaw =
         serde_json::from_str(plugin_options).map_err(|e| e.to_string())?;
+    parse_plugin_options_raw(raw_options, root_resource).await
+}
+
+/// Parse the plugin options already deserialized from the request
+pub async fn parse_plugin_options_raw<L>(
+    raw_options: plugin::OptionsRaw,
+    root_resource: &Resource<'_, L>,
+) -> Result<plugin::Options, String>
+where
+    L: Loader,
+{
     let options = raw_options
         .parse(root_resource)
         .await
@@ -7,17 +7,37 @@ use tokio::sync::Mutex;
 use celerc::prep::{PrepCtxData, PrepResult};
 use celerc::PrepCtx;
 
-use super::ServerResourceLoader;
+use crate::metrics::{self, CacheKind};
 
-static CACHE: Lazy<PrepCtxCache> = Lazy::new(PrepCtxCache::new);
+use super::{CompileBudget, Credential, ServerResourceLoader};
+
+static CACHE: Lazy<PrepCtxCache> = Lazy::new(|| {
+    let config = super::get_compiler_config();
+    PrepCtxCache::new(config.prep_cache_size, config.prep_cache_lifespan_secs)
+});
 
 pub async fn get_context(
     owner: &str,
     repo: &str,
     path: Option<&str>,
     reference: &str,
+    credential: Option<Credential>,
+    budget: Option<Arc<CompileBudget>>,
 ) -> PrepResult<PrepCtx<ServerResourceLoader>> {
-    CACHE.get_or_create(owner, repo, path, reference).await
+    CACHE
+        .get_or_create(owner, repo, path, reference, credential, budget)
+        .await
+}
+
+/// Remove the cached contexts for all entry points of the project at the reference.
+/// Returns the number of contexts removed
+pub async fn invalidate_contexts(owner: &str, repo: &str, reference: &str) -> usize {
+    CACHE.invalidate(owner, repo, reference).await
+}
+
+/// Remove all cached contexts. Returns the number of contexts removed
+pub async fn clear_contexts() -> usize {
+    CACHE.clear().await
 }
 
 /// Cache for the output of prep phase
@@ -25,43 +45,49 @@ pub struct PrepCtxCache {
     cache: Mutex<TimedSizedCache<String, Arc<PrepCtxData>>>,
 }
 
-impl Default for PrepCtxCache {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl PrepCtxCache {
-    pub fn new() -> Self {
+    /// Create a cache with the max number of contexts, and how long (in seconds)
+    /// each context is cached
+    pub fn new(size: usize, lifespan_secs: u64) -> Self {
         Self {
-            cache: Mutex::new(TimedSizedCache::with_size_and_lifespan(32, 301)),
+            cache: Mutex::new(TimedSizedCache::with_size_and_lifespan(size, lifespan_secs)),
         }
     }
 
     /// Get a context for the given project, either from cache or newly created
     ///
-    /// A new resource loader with the global resource cache will be created for the context
+    /// A new resource loader with the global resource cache will be created for the context.
+    /// Contexts created with a credential are cached separately for the credential.
+    /// Resources loaded by the context are charged against the budget
     pub async fn get_or_create(
         &self,
         owner: &str,
         repo: &str,
         path: Option<&str>,
         reference: &str,
+        credential: Option<Credential>,
+        budget: Option<Arc<CompileBudget>>,
     ) -> PrepResult<PrepCtx<ServerResourceLoader>> {
-        let key = make_key(owner, repo, path, reference);
+        let mut key = make_key(owner, repo, path, reference);
+        if let Some(credential) = &credential {
+            key = format!("{}:{key}", credential.id());
+        }
 
         // check if the context is in the cache
         {
             let mut cache = self.cache.lock().await;
             if let Some(data) = cache.cache_get(&key) {
-                let loader = super::loader::get_loader()?;
+                metrics::record_cache_lookup(CacheKind::PrepCtx, true);
+                let loader = super::loader::get_loader(credential, budget)?;
                 return Ok(PrepCtx::from_data(Arc::clone(data), loader));
             }
         }
 
         // build a new context
+        metrics::record_cache_lookup(CacheKind::PrepCtx, false);
         let mut builder =
-            super::new_context_builder(owner, repo, Some(reference))?.with_route_built();
+            super::new_context_builder(owner, repo, Some(reference), credential, budget)?
+                .with_route_built();
         if let Some(path) = path {
             builder = builder.entry_point(Some(path.to_string()));
         }
@@ -73,6 +99,34 @@ impl PrepCtxCache {
         }
         Ok(ctx)
     }
+
+    /// Remove the contexts for all entry points of the project at the reference,
+    /// created with or without credentials. Returns the number of contexts removed
+    pub async fn invalidate(&self, owner: &str, repo: &str, reference: &str) -> usize {
+        let project = make_key(owner, repo, None, reference);
+        let is_match = |key: &str| key == project || key.starts_with(&format!("{project}/"));
+        let mut cache = self.cache.lock().await;
+        let keys = cache
+            .key_order()
+            .filter(|key| {
+                // the key could be prefixed with the credential id
+                is_match(key) || matches!(key.split_once(':'), Some((_, key)) if is_match(key))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            cache.cache_remove(key);
+        }
+        keys.len()
+    }
+
+    /// Remove all contexts. Returns the number of contexts removed
+    pub async fn clear(&self) -> usize {
+        let mut cache = self.cache.lock().await;
+        let size = cache.cache_size();
+        cache.cache_clear();
+        size
+    }
 }
 
 /// Create a cache key for a project reference
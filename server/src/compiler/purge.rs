@@ -0,0 +1,64 @@
+//! Invalidating the server caches when projects are updated
+
+use serde::Serialize;
+use tracing::info;
+
+use celerc::res::{self, ResError, ResResult};
+
+use super::ProjectChange;
+
+/// Number of entries removed from the caches
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgeResult {
+    /// Number of prep contexts removed
+    pub contexts: usize,
+    /// Number of resources removed
+    pub resources: usize,
+}
+
+/// Remove the cached contexts and resources of the project at the reference,
+/// and notify the live sessions of the project
+///
+/// The owner can be prefixed with the name of the remote host, such as `gitlab:owner`.
+/// Resources from other projects (for example, loaded with `use`) are not removed.
+pub async fn purge_project(owner: &str, repo: &str, reference: &str) -> ResResult<PurgeResult> {
+    let (host, owner_name) = res::split_remote_host(owner);
+    let base_url = match res::base_url(host, owner_name, repo, Some(reference)) {
+        Some(url) => url,
+        None => {
+            return Err(ResError::UnknownRemoteHost(
+                host.unwrap_or_default().to_string(),
+            ))
+        }
+    };
+    let result = PurgeResult {
+        contexts: super::invalidate_contexts(owner, repo, reference).await,
+        resources: super::invalidate_resources(&base_url).await,
+    };
+    info!(
+        "purged cache for {owner}/{repo}/{reference}: {} contexts, {} resources",
+        result.contexts, result.resources
+    );
+    super::notify_change(ProjectChange::Remote {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        reference: reference.to_string(),
+    });
+    Ok(result)
+}
+
+/// Remove all cached contexts and resources, and notify all live sessions.
+/// The disk cache is kept, since the entries there are always revalidated before being used
+pub async fn purge_all() -> PurgeResult {
+    let result = PurgeResult {
+        contexts: super::clear_contexts().await,
+        resources: super::clear_resources().await,
+    };
+    info!(
+        "purged all cache: {} contexts, {} resources",
+        result.contexts, result.resources
+    );
+    super::notify_change(ProjectChange::All);
+    result
+}
@@ -1,49 +1,126 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use cached::{Cached, TimedSizedCache};
+use cached::{Cached, SizedCache};
 use futures::Future;
 use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
 
 use celerc::res::{ResError, ResResult};
 
+use crate::metrics::{self, CacheKind};
+
+use super::CacheValidators;
+
 /// Cache for resources loaded over the network with URL
 ///
 /// Cloning the cache instance is cheap and cloned instance will share
 /// the same cache data.
-/// The cache does not sync writes. When multiple requests
-/// to the same resources are made at the same time, both requests
-/// will be fetched and the last one that came back will be cached.
+/// Concurrent requests to the same resource are coalesced, so only one of them
+/// fetches the resource and the others wait for the result.
+/// When an entry expires, it is kept to be revalidated with a conditional request,
+/// and is used if the resource is not modified, or if the request fails with a transient error
+/// (such as a network error or a server error). If the request is rejected (such as with a client
+/// error), the entry is removed.
+/// Resources that don't exist are also cached, so they are not requested again until expired.
 ///
 /// The cache also parses data urls internally without caching
 pub struct ResourceCache {
-    inner: Arc<Mutex<TimedSizedCache<String, Arc<[u8]>>>>,
+    inner: Arc<Mutex<SizedCache<String, CacheEntry>>>,
+    /// Locks for the URLs currently being fetched
+    in_flight: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+    /// How long a cached resource is used without revalidating
+    fresh_duration: Duration,
+}
+
+struct CacheEntry {
+    /// The data, or None if the resource does not exist
+    data: Option<Arc<[u8]>>,
+    validators: CacheValidators,
+    fetched_at: Instant,
+}
+
+impl CacheEntry {
+    fn new(data: Arc<[u8]>, validators: CacheValidators) -> Self {
+        Self {
+            data: Some(data),
+            validators,
+            fetched_at: Instant::now(),
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            data: None,
+            validators: Default::default(),
+            fetched_at: Instant::now(),
+        }
+    }
+
+    /// Get the data, or the not found error for the URL
+    fn get(&self, url: &str) -> ResResult<Arc<[u8]>> {
+        match &self.data {
+            Some(data) => Ok(Arc::clone(data)),
+            None => Err(ResError::NotFound(url.to_string())),
+        }
+    }
+
+    fn is_fresh(&self, fresh_duration: Duration) -> bool {
+        self.fetched_at.elapsed() < fresh_duration
+    }
+}
+
+/// Output of fetching a resource
+pub enum FetchOutput {
+    /// The resource is fetched, with the validators for caching
+    Modified(Vec<u8>, CacheValidators),
+    /// The cached resource is still valid
+    NotModified,
+    /// The resource does not exist. The cached data should not be used
+    NotFound,
+    /// The request is rejected (for example, with a 4xx status). Unlike other errors,
+    /// the cached data should not be used and is removed
+    Rejected(ResError),
 }
 
 impl Clone for ResourceCache {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            in_flight: Arc::clone(&self.in_flight),
+            fresh_duration: self.fresh_duration,
         }
     }
 }
 
 impl ResourceCache {
-    pub fn new() -> Self {
-        let cache = TimedSizedCache::with_size_and_lifespan(128, 301);
+    /// Create a cache with the max number of entries, and how long an entry is used
+    /// without revalidating
+    pub fn new(size: usize, fresh_duration: Duration) -> Self {
+        let cache = SizedCache::with_size(size);
         Self {
             inner: Arc::new(Mutex::new(cache)),
+            in_flight: Default::default(),
+            fresh_duration,
         }
     }
 
     /// Get the data from cache or fetch it with the callback
+    ///
+    /// The scope (such as the credential used to fetch the data) is part of the cache key,
+    /// so data in one scope is not returned for another.
+    /// If the data is cached but expired, the validators of the cached data are passed to the
+    /// callback to send a conditional request
     pub async fn get_or_fetch<TFunc, TFuture>(
         &self,
         url: &str,
+        scope: Option<&str>,
         fetch: TFunc,
     ) -> ResResult<Arc<[u8]>>
     where
-        TFunc: FnOnce() -> TFuture,
-        TFuture: Future<Output = ResResult<Vec<u8>>>,
+        TFunc: FnOnce(Option<CacheValidators>) -> TFuture,
+        TFuture: Future<Output = ResResult<FetchOutput>>,
     {
         // handle data urls first, since parsing them is quick
         // and we don't need to cache them
@@ -60,21 +137,336 @@ impl ResourceCache {
             return Ok(Arc::from(data));
         }
 
+        let key = make_resource_key(url, scope);
+        if let Some(result) = self.get_fresh(url, &key).await {
+            metrics::record_cache_lookup(CacheKind::Resource, true);
+            return result;
+        }
+
+        // only one request should fetch the same url at a time
+        let lock = {
+            let mut in_flight = self.in_flight.lock().await;
+            Arc::clone(in_flight.entry(key.clone()).or_default())
+        };
+        let result = {
+            let _guard = lock.lock().await;
+            self.fetch_locked(url, &key, fetch).await
+        };
         {
-            let mut cache = self.inner.lock().await;
-            if let Some(v) = cache.cache_get(url) {
-                return Ok(Arc::clone(v));
+            let mut in_flight = self.in_flight.lock().await;
+            // the map and this request are the only ones holding the lock
+            if Arc::strong_count(&lock) <= 2 {
+                in_flight.remove(&key);
             }
         }
 
-        let v = fetch().await?;
+        result
+    }
+
+    /// Remove the entries with URL starting with the prefix, in all scopes.
+    /// Returns the number of entries removed
+    pub async fn invalidate(&self, url_prefix: &str) -> usize {
+        let mut cache = self.inner.lock().await;
+        let keys = cache
+            .key_order()
+            .filter(|key| {
+                // the url could be prefixed with the scope
+                key.starts_with(url_prefix)
+                    || matches!(key.split_once(':'), Some((_, url)) if url.starts_with(url_prefix))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in &keys {
+            cache.cache_remove(key);
+        }
+        keys.len()
+    }
+
+    /// Remove all entries. Returns the number of entries removed
+    pub async fn clear(&self) -> usize {
+        let mut cache = self.inner.lock().await;
+        let size = cache.cache_size();
+        cache.cache_clear();
+        size
+    }
+
+    /// Get the data (or the not found error) if it's cached and not expired
+    async fn get_fresh(&self, url: &str, key: &str) -> Option<ResResult<Arc<[u8]>>> {
+        let mut cache = self.inner.lock().await;
+        cache
+            .cache_get(key)
+            .filter(|entry| entry.is_fresh(self.fresh_duration))
+            .map(|entry| entry.get(url))
+    }
+
+    /// Fetch the data while holding the lock for the url
+    async fn fetch_locked<TFunc, TFuture>(
+        &self,
+        url: &str,
+        key: &str,
+        fetch: TFunc,
+    ) -> ResResult<Arc<[u8]>>
+    where
+        TFunc: FnOnce(Option<CacheValidators>) -> TFuture,
+        TFuture: Future<Output = ResResult<FetchOutput>>,
+    {
+        // the data could be fetched by another request while waiting for the lock
+        let stale = {
+            let mut cache = self.inner.lock().await;
+            match cache.cache_get(key) {
+                Some(entry) if entry.is_fresh(self.fresh_duration) => {
+                    metrics::record_cache_lookup(CacheKind::Resource, true);
+                    return entry.get(url);
+                }
+                Some(entry) => entry
+                    .data
+                    .as_ref()
+                    .map(|data| (Arc::clone(data), entry.validators.clone())),
+                None => None,
+            }
+        };
+
+        metrics::record_cache_lookup(CacheKind::Resource, false);
+        let validators = stale
+            .as_ref()
+            .map(|(_, validators)| validators.clone())
+            .filter(|validators| !validators.is_empty());
+        let entry = match (fetch(validators).await, stale) {
+            (Ok(FetchOutput::Modified(data, validators)), _) => {
+                CacheEntry::new(Arc::from(data), validators)
+            }
+            (Ok(FetchOutput::NotModified), Some((data, validators))) => {
+                CacheEntry::new(data, validators)
+            }
+            (Ok(FetchOutput::NotFound), _) => CacheEntry::not_found(),
+            (Ok(FetchOutput::Rejected(e)), _) => {
+                let mut cache = self.inner.lock().await;
+                cache.cache_remove(key);
+                return Err(e);
+            }
+            (Ok(FetchOutput::NotModified), None) => {
+                return Err(ResError::FailToLoadUrl(
+                    url.to_string(),
+                    "Unexpected not modified response".to_string(),
+                ));
+            }
+            (Err(e), Some((data, _))) => {
+                // only transient errors are returned as error
+                warn!("Failed to revalidate resource, using expired data: {e}");
+                return Ok(data);
+            }
+            (Err(e), None) => return Err(e),
+        };
 
-        let v = Arc::from(v);
+        let result = entry.get(url);
         {
             let mut cache = self.inner.lock().await;
-            cache.cache_set(url.to_string(), Arc::clone(&v));
+            cache.cache_set(key.to_string(), entry);
+        }
+
+        result
+    }
+}
+
+/// Create the cache key for the url in the scope
+pub fn make_resource_key(url: &str, scope: Option<&str>) -> String {
+    match scope {
+        Some(scope) => format!("{scope}:{url}"),
+        None => url.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const URL: &str = "https://example.com/file.txt";
+
+    fn etag(value: &str) -> CacheValidators {
+        CacheValidators {
+            etag: Some(value.to_string()),
+            last_modified: None,
         }
+    }
+
+    fn modified(data: &str, value: &str) -> ResResult<FetchOutput> {
+        Ok(FetchOutput::Modified(data.as_bytes().to_vec(), etag(value)))
+    }
+
+    fn transient_error() -> ResResult<FetchOutput> {
+        Err(ResError::FailToLoadUrl(
+            URL.to_string(),
+            "timeout".to_string(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_fresh_entry_is_not_fetched() {
+        let cache = ResourceCache::new(10, Duration::from_secs(60));
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { modified("a", "1") })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { panic!("should not fetch") })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+    }
+
+    #[tokio::test]
+    async fn test_scope_is_separate() {
+        let cache = ResourceCache::new(10, Duration::from_secs(60));
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { modified("a", "1") })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+        let data = cache
+            .get_or_fetch(URL, Some("user"), |validators| async move {
+                assert_eq!(validators, None);
+                modified("b", "2")
+            })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"b");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_are_coalesced() {
+        let cache = ResourceCache::new(10, Duration::from_secs(60));
+        let count = Arc::new(AtomicUsize::new(0));
+        let tasks = (0..5)
+            .map(|_| {
+                let cache = cache.clone();
+                let count = Arc::clone(&count);
+                tokio::spawn(async move {
+                    cache
+                        .get_or_fetch(URL, None, |_| async move {
+                            count.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                            modified("a", "1")
+                        })
+                        .await
+                })
+            })
+            .collect::<Vec<_>>();
+        for task in tasks {
+            assert_eq!(task.await.unwrap().unwrap().as_ref(), b"a");
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(cache.in_flight.lock().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_revalidated() {
+        let cache = ResourceCache::new(10, Duration::ZERO);
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, None);
+                modified("a", "1")
+            })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+        // not modified uses the cached data
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, Some(etag("1")));
+                Ok(FetchOutput::NotModified)
+            })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+        // modified replaces the cached data and validators
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, Some(etag("1")));
+                modified("b", "2")
+            })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"b");
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, Some(etag("2")));
+                Ok(FetchOutput::NotModified)
+            })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"b");
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_used_on_transient_error() {
+        let cache = ResourceCache::new(10, Duration::ZERO);
+        let _ = cache
+            .get_or_fetch(URL, None, |_| async { modified("a", "1") })
+            .await;
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { transient_error() })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+        // the entry is kept for the next revalidation
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, Some(etag("1")));
+                Ok(FetchOutput::NotModified)
+            })
+            .await;
+        assert_eq!(data.unwrap().as_ref(), b"a");
+    }
+
+    #[tokio::test]
+    async fn test_expired_entry_is_removed_on_rejected() {
+        let cache = ResourceCache::new(10, Duration::ZERO);
+        let _ = cache
+            .get_or_fetch(URL, None, |_| async { modified("a", "1") })
+            .await;
+        let data = cache
+            .get_or_fetch(URL, None, |_| async move {
+                Ok(FetchOutput::Rejected(ResError::FailToLoadUrl(
+                    URL.to_string(),
+                    "forbidden".to_string(),
+                )))
+            })
+            .await;
+        assert!(matches!(data, Err(ResError::FailToLoadUrl(_, e)) if e == "forbidden"));
+        // the entry is removed, so the next request is not conditional
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, None);
+                transient_error()
+            })
+            .await;
+        assert!(data.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_not_found_is_cached() {
+        let cache = ResourceCache::new(10, Duration::from_secs(60));
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { Ok(FetchOutput::NotFound) })
+            .await;
+        assert_eq!(data, Err(ResError::NotFound(URL.to_string())));
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { panic!("should not fetch") })
+            .await;
+        assert_eq!(data, Err(ResError::NotFound(URL.to_string())));
+    }
 
-        Ok(v)
+    #[tokio::test]
+    async fn test_not_found_replaces_expired_entry() {
+        let cache = ResourceCache::new(10, Duration::ZERO);
+        let _ = cache
+            .get_or_fetch(URL, None, |_| async { modified("a", "1") })
+            .await;
+        let data = cache
+            .get_or_fetch(URL, None, |_| async { Ok(FetchOutput::NotFound) })
+            .await;
+        assert_eq!(data, Err(ResError::NotFound(URL.to_string())));
+        // not found entries don't have data to fall back to
+        let data = cache
+            .get_or_fetch(URL, None, |validators| async move {
+                assert_eq!(validators, None);
+                transient_error()
+            })
+            .await;
+        assert!(data.is_err());
     }
 }
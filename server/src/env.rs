@@ -1,9 +1,21 @@
 //! Server environment settings
+use axum::http::HeaderName;
 use axum_server::tls_rustls::RustlsConfig;
 use envconfig::Envconfig;
 use std::path::Path;
+use tokio::time::Duration;
 use tracing::{error, info, Level};
 
+use crate::compiler::CompilerConfig;
+use crate::services::RateLimitLayer;
+
+/// Max value allowed for `CELERSERVER_MAX_RESOURCE_SIZE`
+const MAX_RESOURCE_SIZE_LIMIT: usize = 1024 * 1024 * 100; // 100 MB
+/// Max value allowed for `CELERSERVER_FETCH_ATTEMPTS`
+const MAX_FETCH_ATTEMPTS: u32 = 10;
+/// Min value allowed for `CELERSERVER_IDLE_RESTART_SECS`, other than 0
+const MIN_IDLE_RESTART_SECS: u64 = 60;
+
 #[derive(Envconfig)]
 pub struct Environment {
     /// Server version
@@ -34,6 +46,127 @@ pub struct Environment {
     #[envconfig(from = "CELERSERVER_GZIP", default = "false")]
     pub gzip: bool,
 
+    /// Additional remote hosts to load projects from, in the format of
+    /// `name=url_template,name2=url_template2`. See `RemoteHostRegistry` in the compiler
+    #[envconfig(from = "CELERSERVER_REMOTE_HOSTS")]
+    pub remote_hosts: Option<String>,
+
+    /// Directory to persist resources loaded over the network, so they are not lost
+    /// when the server restarts. The cache is disabled if not set
+    #[envconfig(from = "CELERSERVER_RESOURCE_CACHE_DIR")]
+    pub resource_cache_dir: Option<String>,
+
+    /// Max total size of the resources persisted in `CELERSERVER_RESOURCE_CACHE_DIR`, in bytes.
+    /// The least recently used resources are removed when the size is exceeded
+    #[envconfig(
+        from = "CELERSERVER_RESOURCE_CACHE_DIR_MAX_SIZE",
+        default = "1073741824"
+    )]
+    pub resource_cache_dir_max_size: u64,
+
+    /// GitHub token for loading projects from private repositories, used when the request
+    /// does not have the `Celer-GitHub-Token` header. The token should be read-only
+    #[envconfig(from = "CELERSERVER_GITHUB_TOKEN")]
+    pub github_token: Option<String>,
+
+    /// Directory of a local project to compile, for running the server in local dev mode.
+    /// Clients can subscribe to the local project with `/api/v1/live-local`, and get the
+    /// updated route when files change. Disabled if not set
+    #[envconfig(from = "CELERSERVER_LOCAL_PROJECT_DIR")]
+    pub local_project_dir: Option<String>,
+
+    /// Secret for verifying the signature of webhook requests. The webhook endpoint
+    /// is disabled if not set
+    #[envconfig(from = "CELERSERVER_WEBHOOK_SECRET")]
+    pub webhook_secret: Option<String>,
+
+    /// Token for authenticating requests to the admin endpoints and `/metrics`.
+    /// They are disabled if not set
+    #[envconfig(from = "CELERSERVER_ADMIN_TOKEN")]
+    pub admin_token: Option<String>,
+
+    /// Compile the route when serving `/view`, and inject the rendered document and
+    /// more details into the page, so the route can be read without running the viewer
+    #[envconfig(from = "CELERSERVER_VIEW_SSR", default = "false")]
+    pub view_ssr: bool,
+
+    /// Restart the server when it's idle for this many seconds after handling a request,
+    /// to free up resources. The server exits gracefully and is restarted by the boot process.
+    /// Set to 0 to disable
+    #[envconfig(from = "CELERSERVER_IDLE_RESTART_SECS", default = "900")]
+    pub idle_restart_secs: u64,
+
+    /// Max number of prep contexts to cache
+    #[envconfig(from = "CELERSERVER_PREP_CACHE_SIZE", default = "32")]
+    pub prep_cache_size: usize,
+
+    /// How long a prep context is cached, in seconds
+    #[envconfig(from = "CELERSERVER_PREP_CACHE_LIFESPAN_SECS", default = "301")]
+    pub prep_cache_lifespan_secs: u64,
+
+    /// Max number of resources loaded over the network to cache in memory
+    #[envconfig(from = "CELERSERVER_RESOURCE_CACHE_SIZE", default = "128")]
+    pub resource_cache_size: usize,
+
+    /// How long a cached resource is used without revalidating, in seconds
+    #[envconfig(from = "CELERSERVER_RESOURCE_CACHE_FRESH_SECS", default = "301")]
+    pub resource_cache_fresh_secs: u64,
+
+    /// Max size of a resource loaded over the network, in bytes
+    #[envconfig(from = "CELERSERVER_MAX_RESOURCE_SIZE", default = "10485760")]
+    pub max_resource_size: usize,
+
+    /// Max number of attempts to fetch a resource over the network
+    #[envconfig(from = "CELERSERVER_FETCH_ATTEMPTS", default = "3")]
+    pub fetch_attempts: u32,
+
+    /// Max time for a compile or export request, in seconds
+    #[envconfig(from = "CELERSERVER_COMPILE_TIMEOUT_SECS", default = "30")]
+    pub compile_timeout_secs: u64,
+
+    /// Max number of resources loaded in a compile or export request
+    #[envconfig(from = "CELERSERVER_MAX_RESOURCES_PER_COMPILE", default = "1024")]
+    pub max_resources_per_compile: usize,
+
+    /// Max total size of resources loaded in a compile or export request, in bytes
+    #[envconfig(from = "CELERSERVER_MAX_BYTES_PER_COMPILE", default = "104857600")]
+    pub max_bytes_per_compile: usize,
+
+    /// Max number of lines in the route compiled in a compile or export request
+    #[envconfig(from = "CELERSERVER_MAX_LINES_PER_COMPILE", default = "65536")]
+    pub max_lines_per_compile: usize,
+
+    /// Max number of API requests a client can make at once. Set to 0 to disable rate limiting
+    #[envconfig(from = "CELERSERVER_RATE_LIMIT_BURST", default = "30")]
+    pub rate_limit_burst: u32,
+
+    /// Number of API requests a client can make per minute after the burst
+    #[envconfig(from = "CELERSERVER_RATE_LIMIT_PER_MINUTE", default = "60")]
+    pub rate_limit_per_minute: u32,
+
+    /// Header to get the client IP from for rate limiting and live session limits, such as `X-Forwarded-For`.
+    /// Only set this if the server is behind a reverse proxy that sets the header,
+    /// otherwise the IP of the connection is used
+    #[envconfig(from = "CELERSERVER_CLIENT_IP_HEADER")]
+    pub client_ip_header: Option<String>,
+
+    /// Max number of live sessions on the server at the same time
+    #[envconfig(from = "CELERSERVER_MAX_LIVE_SESSIONS", default = "1000")]
+    pub max_live_sessions: usize,
+
+    /// Max number of live sessions from the same client IP at the same time
+    #[envconfig(from = "CELERSERVER_MAX_LIVE_SESSIONS_PER_CLIENT", default = "10")]
+    pub max_live_sessions_per_client: usize,
+
+    /// Comma separated list of repos (`owner/repo` or `owner/*`) that can be loaded.
+    /// All repos can be loaded if not set
+    #[envconfig(from = "CELERSERVER_REPO_ALLOWLIST")]
+    pub repo_allowlist: Option<String>,
+
+    /// Comma separated list of repos (`owner/repo` or `owner/*`) that cannot be loaded
+    #[envconfig(from = "CELERSERVER_REPO_DENYLIST")]
+    pub repo_denylist: Option<String>,
+
     #[envconfig(from = "CELERSERVER_HTTPS_CERT")]
     cert_path: Option<String>,
 
@@ -44,7 +177,7 @@ pub struct Environment {
 impl Environment {
     /// Parse environment from command line arguments and environment variables
     pub fn parse() -> Self {
-        match Environment::init_from_env() {
+        let env = match Environment::init_from_env() {
             Ok(env) => env,
             Err(envconfig::Error::EnvVarMissing { name }) => {
                 panic!("Server cannot start due to missing environment variable: {name}");
@@ -54,6 +187,119 @@ impl Environment {
                     "Server cannot start due to failure when parsing environment variable: {name}"
                 );
             }
+        };
+        if let Err(e) = env.validate() {
+            panic!("Server cannot start due to invalid environment variable: {e}");
+        }
+        env
+    }
+
+    /// Check the values are in the allowed range
+    fn validate(&self) -> Result<(), String> {
+        if self.idle_restart_secs != 0 && self.idle_restart_secs < MIN_IDLE_RESTART_SECS {
+            return Err(format!(
+                "CELERSERVER_IDLE_RESTART_SECS must be 0 or at least {MIN_IDLE_RESTART_SECS}"
+            ));
+        }
+        if self.prep_cache_size == 0 {
+            return Err("CELERSERVER_PREP_CACHE_SIZE must be at least 1".to_string());
+        }
+        if self.prep_cache_lifespan_secs == 0 {
+            return Err("CELERSERVER_PREP_CACHE_LIFESPAN_SECS must be at least 1".to_string());
+        }
+        if self.resource_cache_size == 0 {
+            return Err("CELERSERVER_RESOURCE_CACHE_SIZE must be at least 1".to_string());
+        }
+        if self.resource_cache_dir_max_size < self.max_resource_size as u64 {
+            return Err(
+                "CELERSERVER_RESOURCE_CACHE_DIR_MAX_SIZE must be at least CELERSERVER_MAX_RESOURCE_SIZE"
+                    .to_string(),
+            );
+        }
+        if self.max_resource_size == 0 || self.max_resource_size > MAX_RESOURCE_SIZE_LIMIT {
+            return Err(format!(
+                "CELERSERVER_MAX_RESOURCE_SIZE must be between 1 and {MAX_RESOURCE_SIZE_LIMIT}"
+            ));
+        }
+        if self.fetch_attempts == 0 || self.fetch_attempts > MAX_FETCH_ATTEMPTS {
+            return Err(format!(
+                "CELERSERVER_FETCH_ATTEMPTS must be between 1 and {MAX_FETCH_ATTEMPTS}"
+            ));
+        }
+        if self.rate_limit_burst != 0 && self.rate_limit_per_minute == 0 {
+            return Err("CELERSERVER_RATE_LIMIT_PER_MINUTE must be at least 1".to_string());
+        }
+        if let Some(name) = &self.client_ip_header {
+            if HeaderName::try_from(name.as_str()).is_err() {
+                return Err(format!(
+                    "CELERSERVER_CLIENT_IP_HEADER is not a valid header: {name}"
+                ));
+            }
+        }
+        if self.max_live_sessions == 0 {
+            return Err("CELERSERVER_MAX_LIVE_SESSIONS must be at least 1".to_string());
+        }
+        if self.max_live_sessions_per_client == 0 {
+            return Err("CELERSERVER_MAX_LIVE_SESSIONS_PER_CLIENT must be at least 1".to_string());
+        }
+        if self.compile_timeout_secs == 0 {
+            return Err("CELERSERVER_COMPILE_TIMEOUT_SECS must be at least 1".to_string());
+        }
+        if self.max_resources_per_compile == 0 {
+            return Err("CELERSERVER_MAX_RESOURCES_PER_COMPILE must be at least 1".to_string());
+        }
+        if self.max_bytes_per_compile < self.max_resource_size {
+            return Err(
+                "CELERSERVER_MAX_BYTES_PER_COMPILE must be at least CELERSERVER_MAX_RESOURCE_SIZE"
+                    .to_string(),
+            );
+        }
+        if self.max_lines_per_compile == 0 {
+            return Err("CELERSERVER_MAX_LINES_PER_COMPILE must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Get how long the server should idle before restarting, or None if disabled
+    pub fn idle_restart(&self) -> Option<Duration> {
+        match self.idle_restart_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Get the rate limiting layer for the API, or None if disabled
+    pub fn rate_limit_layer(&self) -> Option<RateLimitLayer> {
+        if self.rate_limit_burst == 0 {
+            return None;
+        }
+        Some(RateLimitLayer::new(
+            self.rate_limit_burst,
+            self.rate_limit_per_minute,
+            self.client_ip_header(),
+        ))
+    }
+
+    /// Get the header to get the client IP from, or None to use the IP of the connection
+    pub fn client_ip_header(&self) -> Option<HeaderName> {
+        self.client_ip_header
+            .as_deref()
+            .and_then(|x| HeaderName::try_from(x).ok())
+    }
+
+    /// Get the config for caching and loading resources
+    pub fn compiler_config(&self) -> CompilerConfig {
+        CompilerConfig {
+            prep_cache_size: self.prep_cache_size,
+            prep_cache_lifespan_secs: self.prep_cache_lifespan_secs,
+            resource_cache_size: self.resource_cache_size,
+            resource_fresh_secs: self.resource_cache_fresh_secs,
+            max_resource_size: self.max_resource_size,
+            fetch_attempts: self.fetch_attempts,
+            compile_timeout_secs: self.compile_timeout_secs,
+            max_resources_per_compile: self.max_resources_per_compile,
+            max_bytes_per_compile: self.max_bytes_per_compile,
+            max_lines_per_compile: self.max_lines_per_compile,
         }
     }
 
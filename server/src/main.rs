@@ -5,6 +5,7 @@
 //! Alternatively, you can use a CDN such as Cloudflare to proxy the website.
 
 use std::io::{self, IsTerminal};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -24,6 +25,7 @@ mod api;
 mod compiler;
 mod env;
 use env::Environment;
+mod metrics;
 mod services;
 use services::{AddHtmlExtService, NestedRouteRedirectService};
 mod boot;
@@ -44,7 +46,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         &env.site_origin,
     )
     .await?;
+    compiler::setup_compiler_config(env.compiler_config());
+    compiler::setup_default_credential(env.github_token.as_deref());
     compiler::setup_global_loader();
+    compiler::setup_remote_hosts(env.remote_hosts.as_deref())?;
+    compiler::setup_repo_access(env.repo_allowlist.as_deref(), env.repo_denylist.as_deref())?;
+    if let Some(dir) = &env.resource_cache_dir {
+        compiler::setup_disk_cache(dir, env.resource_cache_dir_max_size).await?;
+    }
+    if let Some(dir) = &env.local_project_dir {
+        compiler::setup_local_project(dir)?;
+    }
     if env.gzip {
         info!("compressing assets...");
         boot::gzip_static_assets(PathBuf::from(&env.docs_dir), PathBuf::from(&env.app_dir)).await?;
@@ -53,7 +65,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     info!("configuring routes...");
 
-    let globals = Globals::new();
+    let globals = Globals::new(env.idle_restart());
+    if globals.idle_restart.is_none() {
+        info!("idle restart is disabled");
+    }
 
     let router = Router::new();
     let router = init_home(router);
@@ -65,6 +80,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         &["/celerc", "/static", "/assets", "/themes"],
     )?;
     let router = api::init_api(router, &env)?;
+    let router = match env.admin_token.as_deref().filter(|x| !x.is_empty()) {
+        Some(token) => metrics::init_metrics(router, token),
+        None => {
+            info!("admin token not set, metrics endpoint is disabled");
+            router
+        }
+    };
 
     let shutdown = middleware::from_fn_with_state(globals.clone(), shutdown_middleware);
 
@@ -76,6 +98,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .on_request(DefaultOnRequest::new().level(Level::INFO))
                     .on_response(DefaultOnResponse::new().level(Level::INFO)),
             )
+            .layer(middleware::from_fn(metrics::track_requests))
             .layer(shutdown),
     );
 
@@ -85,13 +108,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         info!("starting server on https://{address}");
         axum_server::bind_rustls(address, tls_config)
             .handle(handle)
-            .serve(router.into_make_service())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
             .await?;
     } else {
         info!("starting server on http://{address}");
         Server::bind(address)
             .handle(handle)
-            .serve(router.into_make_service())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
             .await?;
     }
 
@@ -158,23 +181,38 @@ fn init_static(
 struct Globals {
     pub handle: Handle,
     pub last_shutdown_check: Arc<Mutex<Instant>>,
+    /// How long the server should idle before restarting, None to never restart
+    pub idle_restart: Option<Duration>,
 }
 
 impl Globals {
-    pub fn new() -> Self {
+    pub fn new(idle_restart: Option<Duration>) -> Self {
         Self {
             handle: Handle::new(),
             last_shutdown_check: Arc::new(Mutex::new(Instant::now())),
+            idle_restart,
         }
     }
 }
 
+/// Get the last time the server is active, either from the last request, or from live sessions,
+/// which are long-running and should keep the server from restarting
+fn last_activity(last_request: Instant) -> Instant {
+    match api::last_session_activity() {
+        Some(x) => x.max(last_request),
+        None => last_request,
+    }
+}
+
 async fn shutdown_middleware(State(globals): State<Globals>, req: Request, next: Next) -> Response {
-    let restart_interval = Duration::from_secs(15 * 60); // 15 minutes
     let response = next.run(req).await;
+    let restart_interval = match globals.idle_restart {
+        Some(x) => x,
+        None => return response,
+    };
     let mut last_shutdown_check = globals.last_shutdown_check.lock().await;
     let now = Instant::now();
-    let should_restart = now.duration_since(*last_shutdown_check) > restart_interval;
+    let should_restart = now.duration_since(last_activity(*last_shutdown_check)) > restart_interval;
     *last_shutdown_check = now;
     if should_restart {
         info!("server has idled for too long, queueing restart...");
@@ -186,7 +224,8 @@ async fn shutdown_middleware(State(globals): State<Globals>, req: Request, next:
                 {
                     let last_shutdown_check = last_shutdown_check.lock().await;
                     let now = Instant::now();
-                    if now.duration_since(*last_shutdown_check) > Duration::from_secs(5) {
+                    let last_active = last_activity(*last_shutdown_check);
+                    if now.duration_since(last_active) > Duration::from_secs(5) {
                         break;
                     }
                 }
@@ -0,0 +1,277 @@
+//! Metrics for monitoring the server, exposed at `/metrics` in the Prometheus text format
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::{routing, Router};
+use instant::Instant;
+use once_cell::sync::Lazy;
+use tracing::{debug, warn};
+
+use celerc::res;
+
+use crate::api;
+
+/// Upper bounds of the histogram buckets, in seconds
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// Setup the /metrics route, authenticated with the admin token
+pub fn init_metrics(router: Router, token: &str) -> Router {
+    debug!("/metrics");
+    // compare the hashes so the time taken does not depend on the token
+    let token_hash: Arc<str> = Arc::from(res::hash_content(token.as_bytes()));
+    let metrics = Router::new()
+        .route("/metrics", routing::get(get_metrics))
+        .with_state(token_hash);
+    router.merge(metrics)
+}
+
+/// Middleware to record the count and latency of requests, by the matched route
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    // use the route instead of the actual path, so the number of labels is bounded
+    let route = match req.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let method = req.method().to_string();
+    let start_time = Instant::now();
+    let response = next.run(req).await;
+    let key = RequestKey {
+        route,
+        method,
+        status: response.status().as_u16(),
+    };
+    METRICS.record_request(key, start_time.elapsed());
+    response
+}
+
+/// Phase of the compilation, same as the phases measured by the `Metrics` plugin
+///
+/// The server measures the phases itself instead of using the plugin, since the plugin
+/// only runs when the project enables it, and only reports the times as text in the stats
+#[derive(Debug, Clone, Copy)]
+pub enum CompilePhase {
+    /// Prep and pack phase, before the compiler is created
+    Prep,
+    Comp,
+    Exec,
+}
+
+/// Record the time taken by a phase of the compilation
+pub fn record_compile_phase(phase: CompilePhase, duration: Duration) {
+    let histogram = match phase {
+        CompilePhase::Prep => &METRICS.compile_prep,
+        CompilePhase::Comp => &METRICS.compile_comp,
+        CompilePhase::Exec => &METRICS.compile_exec,
+    };
+    histogram.observe(duration);
+}
+
+/// Cache that the hits and misses are recorded for
+#[derive(Debug, Clone, Copy)]
+pub enum CacheKind {
+    PrepCtx,
+    Resource,
+}
+
+/// Record a lookup in the cache
+pub fn record_cache_lookup(cache: CacheKind, hit: bool) {
+    let (hits, misses) = match cache {
+        CacheKind::PrepCtx => (&METRICS.prep_ctx_cache_hits, &METRICS.prep_ctx_cache_misses),
+        CacheKind::Resource => (&METRICS.resource_cache_hits, &METRICS.resource_cache_misses),
+    };
+    if hit {
+        hits.inc();
+    } else {
+        misses.inc();
+    }
+}
+
+/// Record a failed attempt to fetch a resource over the network
+pub fn record_fetch_failure() {
+    METRICS.fetch_failures.inc();
+}
+
+/// Record a retry to fetch a resource over the network after a failed attempt
+pub fn record_fetch_retry() {
+    METRICS.fetch_retries.inc();
+}
+
+async fn get_metrics(State(token_hash): State<Arc<str>>, headers: HeaderMap) -> Response {
+    if !api::is_admin_authorized(&token_hash, &headers) {
+        warn!("Unauthorized request to metrics endpoint");
+        return (StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+        .into_response()
+}
+
+#[derive(Default)]
+struct Metrics {
+    requests: Mutex<BTreeMap<RequestKey, Histogram>>,
+    compile_prep: Histogram,
+    compile_comp: Histogram,
+    compile_exec: Histogram,
+    prep_ctx_cache_hits: Counter,
+    prep_ctx_cache_misses: Counter,
+    resource_cache_hits: Counter,
+    resource_cache_misses: Counter,
+    fetch_failures: Counter,
+    fetch_retries: Counter,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey {
+    route: String,
+    method: String,
+    status: u16,
+}
+
+impl Metrics {
+    fn record_request(&self, key: RequestKey, duration: Duration) {
+        let mut requests = match self.requests.lock() {
+            Ok(x) => x,
+            // the data is still usable if another thread panicked
+            Err(e) => e.into_inner(),
+        };
+        requests.entry(key).or_default().observe(duration);
+    }
+
+    /// Render the metrics in the Prometheus text format
+    fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str(
+            "# HELP celery_http_request_duration_seconds Latency of HTTP requests by route\n",
+        );
+        out.push_str("# TYPE celery_http_request_duration_seconds histogram\n");
+        {
+            let requests = match self.requests.lock() {
+                Ok(x) => x,
+                Err(e) => e.into_inner(),
+            };
+            for (key, histogram) in requests.iter() {
+                let labels = format!(
+                    "route=\"{}\",method=\"{}\",status=\"{}\"",
+                    escape_label(&key.route),
+                    escape_label(&key.method),
+                    key.status
+                );
+                histogram.render(&mut out, "celery_http_request_duration_seconds", &labels);
+            }
+        }
+
+        out.push_str("# HELP celery_compile_phase_duration_seconds Time taken by each phase of compilation\n");
+        out.push_str("# TYPE celery_compile_phase_duration_seconds histogram\n");
+        for (phase, histogram) in [
+            ("prep", &self.compile_prep),
+            ("comp", &self.compile_comp),
+            ("exec", &self.compile_exec),
+        ] {
+            let labels = format!("phase=\"{phase}\"");
+            histogram.render(&mut out, "celery_compile_phase_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP celery_cache_lookups_total Lookups in the server caches\n");
+        out.push_str("# TYPE celery_cache_lookups_total counter\n");
+        for (cache, result, counter) in [
+            ("prep_ctx", "hit", &self.prep_ctx_cache_hits),
+            ("prep_ctx", "miss", &self.prep_ctx_cache_misses),
+            ("resource", "hit", &self.resource_cache_hits),
+            ("resource", "miss", &self.resource_cache_misses),
+        ] {
+            let _ = writeln!(
+                out,
+                "celery_cache_lookups_total{{cache=\"{cache}\",result=\"{result}\"}} {}",
+                counter.get()
+            );
+        }
+
+        out.push_str("# HELP celery_fetch_failures_total Failed attempts to fetch resources\n");
+        out.push_str("# TYPE celery_fetch_failures_total counter\n");
+        let _ = writeln!(
+            out,
+            "celery_fetch_failures_total {}",
+            self.fetch_failures.get()
+        );
+        out.push_str(
+            "# HELP celery_fetch_retries_total Retries to fetch resources after failed attempts\n",
+        );
+        out.push_str("# TYPE celery_fetch_retries_total counter\n");
+        let _ = writeln!(
+            out,
+            "celery_fetch_retries_total {}",
+            self.fetch_retries.get()
+        );
+
+        out
+    }
+}
+
+#[derive(Default)]
+struct Counter(AtomicU64);
+
+impl Counter {
+    fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Histogram with the fixed [`BUCKETS`]
+#[derive(Default)]
+struct Histogram {
+    /// Number of observations in each bucket (not cumulative)
+    buckets: [AtomicU64; BUCKETS.len()],
+    count: AtomicU64,
+    /// Sum of the observations in microseconds
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(i) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, bucket) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {cumulative}");
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {count}");
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+        let _ = writeln!(out, "{name}_count{{{labels}}} {count}");
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -2,3 +2,5 @@ mod add_html_ext;
 pub use add_html_ext::*;
 mod nested_route_redirect;
 pub use nested_route_redirect::*;
+mod rate_limit;
+pub use rate_limit::*;
@@ -0,0 +1,299 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use axum::extract::ConnectInfo;
+use axum::http::{header, HeaderName, HeaderValue, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use cached::{Cached, SizedCache};
+use futures::future::{self, Either, Ready};
+use tokio::time::{Duration, Instant};
+use tower::{Layer, Service};
+use tracing::warn;
+
+/// Max number of clients to keep track of. The least recently seen clients
+/// are removed when there are more
+const MAX_TRACKED_CLIENTS: usize = 10000;
+/// IP used for requests where the client IP cannot be determined. These requests
+/// share the same bucket
+const UNKNOWN_CLIENT_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+
+/// Layer that limits the rate of requests from each client IP with a token bucket
+///
+/// Each client can make `burst` requests at once, and the bucket refills at `per_minute`
+/// tokens per minute. Requests over the limit get `429 Too Many Requests` with `Retry-After`.
+///
+/// The client IP is taken from the connection, which requires the server to be
+/// started with `into_make_service_with_connect_info::<SocketAddr>`.
+/// When behind a reverse proxy, set `ip_header` to the header with the client IP
+/// (such as `X-Forwarded-For`) instead. The last IP in the header is used, since that's
+/// the one added by the proxy, and the ones before it could be set by the client.
+/// If the client IP cannot be determined, the request is limited with a shared bucket.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    state: Arc<RateLimitState>,
+}
+
+impl RateLimitLayer {
+    pub fn new(burst: u32, per_minute: u32, ip_header: Option<HeaderName>) -> Self {
+        Self {
+            state: Arc::new(RateLimitState {
+                capacity: f64::from(burst),
+                refill_per_sec: f64::from(per_minute) / 60.0,
+                ip_header,
+                buckets: Mutex::new(SizedCache::with_size(MAX_TRACKED_CLIENTS)),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            state: Arc::clone(&self.state),
+        }
+    }
+}
+
+/// Service created by [`RateLimitLayer`]
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    state: Arc<RateLimitState>,
+}
+
+impl<ReqBody, S> Service<Request<ReqBody>> for RateLimitService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, Ready<Result<Response, S::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let ip = self.state.get_client_ip(&req);
+        match self.state.acquire(ip, Instant::now()) {
+            Ok(()) => Either::Left(self.inner.call(req)),
+            Err(retry_after) => {
+                warn!("rate limit exceeded for {ip}");
+                Either::Right(future::ok(too_many_requests(retry_after)))
+            }
+        }
+    }
+}
+
+struct RateLimitState {
+    /// Max number of tokens in the bucket
+    capacity: f64,
+    /// Number of tokens added to the bucket per second
+    refill_per_sec: f64,
+    /// Header to get the client IP from, instead of the connection
+    ip_header: Option<HeaderName>,
+    /// Buckets of the clients, the least recently used ones are removed when full
+    buckets: Mutex<SizedCache<IpAddr, Bucket>>,
+}
+
+impl std::fmt::Debug for RateLimitState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimitState")
+            .field("capacity", &self.capacity)
+            .field("refill_per_sec", &self.refill_per_sec)
+            .field("ip_header", &self.ip_header)
+            .finish_non_exhaustive()
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimitState {
+    fn get_client_ip<B>(&self, req: &Request<B>) -> IpAddr {
+        get_client_ip(req, self.ip_header.as_ref())
+    }
+
+    /// Take a token from the bucket of the client. Returns the time to wait
+    /// until the next token as error if the bucket is empty
+    fn acquire(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let mut buckets = match self.buckets.lock() {
+            Ok(x) => x,
+            // the data is still usable if another thread panicked
+            Err(e) => e.into_inner(),
+        };
+        let bucket = buckets.cache_get_or_set_with(ip, || Bucket {
+            tokens: self.capacity,
+            last_refill: now,
+        });
+        let tokens = self.refill(bucket, now);
+        if tokens >= 1.0 {
+            bucket.tokens = tokens - 1.0;
+            return Ok(());
+        }
+        let wait = if self.refill_per_sec > 0.0 {
+            (1.0 - tokens) / self.refill_per_sec
+        } else {
+            60.0
+        };
+        Err(Duration::from_secs_f64(wait))
+    }
+
+    /// Refill the tokens in the bucket and return the number of tokens
+    fn refill(&self, bucket: &mut Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        bucket.last_refill = now;
+        bucket.tokens
+    }
+}
+
+/// Get the IP of the client that made the request
+///
+/// If `ip_header` is set, the last IP in the header is used, since that's the one
+/// added by the proxy. Otherwise, the IP is taken from the connection.
+/// Returns the unspecified IP if it cannot be determined
+pub fn get_client_ip<B>(req: &Request<B>, ip_header: Option<&HeaderName>) -> IpAddr {
+    let from_header = ip_header.and_then(|name| {
+        // X-Forwarded-For could have a list of IPs, and each proxy appends to the end.
+        // Only the last one is added by our proxy, the others could be spoofed
+        req.headers()
+            .get_all(name)
+            .iter()
+            .next_back()
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|v| v.trim().parse().ok())
+    });
+    from_header
+        .or_else(|| {
+            req.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|info| info.0.ip())
+        })
+        .unwrap_or(UNKNOWN_CLIENT_IP)
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    // round up so the client does not retry too early
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let retry_after = HeaderValue::from(secs.max(1));
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after)],
+        "Too many requests, please try again later",
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_state(burst: u32, per_minute: u32, ip_header: Option<&str>) -> Arc<RateLimitState> {
+        let ip_header = ip_header.map(|x| HeaderName::try_from(x).unwrap());
+        RateLimitLayer::new(burst, per_minute, ip_header).state
+    }
+
+    fn new_request(forwarded_for: &[&str], addr: Option<&str>) -> Request<()> {
+        let mut req = Request::new(());
+        for value in forwarded_for {
+            req.headers_mut()
+                .append("x-forwarded-for", HeaderValue::from_str(value).unwrap());
+        }
+        if let Some(addr) = addr {
+            req.extensions_mut()
+                .insert(ConnectInfo::<SocketAddr>(addr.parse().unwrap()));
+        }
+        req
+    }
+
+    #[test]
+    fn test_client_ip_from_connection() {
+        let state = new_state(1, 1, None);
+        let req = new_request(&["1.1.1.1"], Some("2.2.2.2:1234"));
+        assert_eq!(
+            state.get_client_ip(&req),
+            "2.2.2.2".parse::<IpAddr>().unwrap()
+        );
+        let req = new_request(&[], None);
+        assert_eq!(state.get_client_ip(&req), UNKNOWN_CLIENT_IP);
+    }
+
+    #[test]
+    fn test_client_ip_from_header_uses_last() {
+        let state = new_state(1, 1, Some("x-forwarded-for"));
+        let req = new_request(&["1.1.1.1, 3.3.3.3"], Some("2.2.2.2:1234"));
+        assert_eq!(
+            state.get_client_ip(&req),
+            "3.3.3.3".parse::<IpAddr>().unwrap()
+        );
+        let req = new_request(&["1.1.1.1", "3.3.3.3,4.4.4.4"], None);
+        assert_eq!(
+            state.get_client_ip(&req),
+            "4.4.4.4".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_client_ip_from_header_fallback() {
+        let state = new_state(1, 1, Some("x-forwarded-for"));
+        let req = new_request(&["not an ip"], Some("2.2.2.2:1234"));
+        assert_eq!(
+            state.get_client_ip(&req),
+            "2.2.2.2".parse::<IpAddr>().unwrap()
+        );
+        let req = new_request(&["1.1.1.1, "], None);
+        assert_eq!(state.get_client_ip(&req), UNKNOWN_CLIENT_IP);
+        let req = new_request(&[], None);
+        assert_eq!(state.get_client_ip(&req), UNKNOWN_CLIENT_IP);
+    }
+
+    #[test]
+    fn test_acquire_burst_and_refill() {
+        let state = new_state(2, 60, None);
+        let ip = "1.1.1.1".parse().unwrap();
+        let now = Instant::now();
+        assert_eq!(state.acquire(ip, now), Ok(()));
+        assert_eq!(state.acquire(ip, now), Ok(()));
+        assert_eq!(state.acquire(ip, now), Err(Duration::from_secs(1)));
+        // other clients have their own bucket
+        assert_eq!(state.acquire("2.2.2.2".parse().unwrap(), now), Ok(()));
+
+        let later = now + Duration::from_millis(500);
+        assert_eq!(state.acquire(ip, later), Err(Duration::from_millis(500)));
+        let later = now + Duration::from_secs(1);
+        assert_eq!(state.acquire(ip, later), Ok(()));
+        assert!(state.acquire(ip, later).is_err());
+
+        // refill is capped at the burst
+        let later = now + Duration::from_secs(60);
+        assert_eq!(state.acquire(ip, later), Ok(()));
+        assert_eq!(state.acquire(ip, later), Ok(()));
+        assert!(state.acquire(ip, later).is_err());
+    }
+
+    #[test]
+    fn test_tracked_clients_are_capped() {
+        let state = new_state(1, 1, None);
+        let now = Instant::now();
+        let first = IpAddr::from([10, 0, 0, 0]);
+        assert_eq!(state.acquire(first, now), Ok(()));
+        assert!(state.acquire(first, now).is_err());
+        for i in 0..MAX_TRACKED_CLIENTS as u32 {
+            let ip = IpAddr::from((i + 1).to_be_bytes());
+            assert_eq!(state.acquire(ip, now), Ok(()));
+        }
+        let buckets = state.buckets.lock().unwrap();
+        assert_eq!(buckets.cache_size(), MAX_TRACKED_CLIENTS);
+        drop(buckets);
+        // the least recently seen client is removed
+        assert_eq!(state.acquire(first, now), Ok(()));
+    }
+}
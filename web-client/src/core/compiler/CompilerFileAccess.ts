@@ -11,4 +11,7 @@ export interface CompilerFileAccess {
         path: string,
         checkChanged: boolean,
     ): Promise<FsResult<Uint8Array>>;
+
+    /// Get the entries in a directory. Directory names end with "/"
+    getDirEntries(path: string): Promise<FsResult<string[]>>;
 }
@@ -32,4 +32,17 @@ export interface CompilerKernel {
     ///
     /// Any error will be stored in the return value. This function will not throw
     export(request: ExportRequest): Promise<ExpoDoc>;
+
+    /// Get the content of the lock file (celer.lock) for the last compiled document,
+    /// which pins the content of the remote resources loaded
+    ///
+    /// Any error will be stored in the return value. This function will not throw
+    getLockFile(): Promise<Result<string, string>>;
+
+    /// Load the remote resources needed to compile the document, so they
+    /// can be saved to the vendor directory for offline compilation
+    ///
+    /// Returns a map of the path in the project to the content of the file.
+    /// Any error will be stored in the return value. This function will not throw
+    vendorDocument(): Promise<Result<Map<string, Uint8Array>, string>>;
 }
@@ -23,7 +23,9 @@ import {
     compile_document,
     export_document,
     get_entry_points,
+    get_lock_file,
     set_plugin_options,
+    vendor_document,
 } from "low/celerc";
 import {
     setWorker,
@@ -122,6 +124,14 @@ export class CompilerKernelImpl implements CompilerKernel {
                 if (bytes.err) {
                     if (bytes.err.code === FsErr.NotModified) {
                         worker.postMessage(["file", 0, path, [false]]);
+                    } else if (bytes.err.code === FsErr.NotFound) {
+                        // the compiler treats missing files differently from other errors
+                        worker.postMessage([
+                            "file",
+                            1,
+                            path,
+                            { ...bytes.err, notFound: true },
+                        ]);
                     } else {
                         worker.postMessage(["file", 1, path, bytes.err]);
                     }
@@ -130,6 +140,26 @@ export class CompilerKernelImpl implements CompilerKernel {
                 worker.postMessage(["file", 0, path, [true, bytes.val]]);
             },
         );
+        registerWorkerHandler("list_dir", async ([path]: [string]) => {
+            if (!this.fileAccess) {
+                worker.postMessage([
+                    "dir",
+                    1,
+                    path,
+                    {
+                        code: FsErr.Fail,
+                        message: "file access not available",
+                    } satisfies FsError,
+                ]);
+                return;
+            }
+            const entries = await this.fileAccess.getDirEntries(path);
+            if (entries.err) {
+                worker.postMessage(["dir", 1, path, entries.err]);
+                return;
+            }
+            worker.postMessage(["dir", 0, path, entries.val]);
+        });
 
         await setWorker(worker, console);
         this.store.dispatch(viewActions.setCompilerReady(true));
@@ -270,6 +300,68 @@ export class CompilerKernelImpl implements CompilerKernel {
         });
     }
 
+    public async getLockFile(): Promise<Result<string, string>> {
+        if (!this.fileAccess) {
+            return {
+                err: "Compiler not available. Please make sure a project is loaded.",
+            };
+        }
+
+        if (!(await this.ensureReady())) {
+            return {
+                err: "Compiler is not ready. Please try again later.",
+            };
+        }
+
+        return await this.compilerLock.lockedScope(undefined, async () => {
+            const result = await tryAsync(get_lock_file);
+            if ("err" in result) {
+                console.error(result.err);
+                return { err: errstr(result.err) };
+            }
+            if (result.val === undefined) {
+                return {
+                    err: "The project is not compiled yet. Please compile the project first.",
+                };
+            }
+            return { val: result.val };
+        });
+    }
+
+    public async vendorDocument(): Promise<
+        Result<Map<string, Uint8Array>, string>
+    > {
+        if (!this.fileAccess) {
+            return {
+                err: "Compiler not available. Please make sure a project is loaded.",
+            };
+        }
+
+        if (!(await this.ensureReady())) {
+            return {
+                err: "Compiler is not ready. Please try again later.",
+            };
+        }
+
+        const validatedEntryPath = await this.validateEntryPath();
+        if ("err" in validatedEntryPath) {
+            return {
+                err: "Compiler entry path is invalid. Please check your settings.",
+            };
+        }
+
+        return await this.compilerLock.lockedScope(undefined, async () => {
+            const result = await tryAsync(() =>
+                vendor_document(validatedEntryPath.val),
+            );
+            if ("err" in result) {
+                console.error(result.err);
+                return { err: errstr(result.err) };
+            }
+            return { val: result.val as Map<string, Uint8Array> };
+        });
+    }
+
     /// Try to wait for the compiler to be ready. Returns true if it becomes ready eventually.
     ///
     /// A timeout of 1 minute is implemented to prevent infinite wait.
@@ -195,6 +195,10 @@ class ExternalEditorKernel implements EditorKernel, CompilerFileAccess {
         return bytes;
     }
 
+    public getDirEntries(path: string): Promise<FsResult<string[]>> {
+        return this.fs.listDir(path);
+    }
+
     // === Stub implementations ===
     public async listDir(): Promise<string[]> {
         return [];
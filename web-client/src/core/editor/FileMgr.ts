@@ -404,6 +404,12 @@ export class FileMgr implements CompilerFileAccess {
         return await fsFile.getBytes();
     }
 
+    public getDirEntries(path: string): Promise<FsResult<string[]>> {
+        return this.fs.scopedRead((fs) => {
+            return fs.listDir(path);
+        });
+    }
+
     private async attachEditor() {
         let div = EditorContainerDOM.get();
         while (!div) {
@@ -10,6 +10,7 @@ export * from "./ReentrantLock.ts";
 export * from "./WorkerHost.ts";
 export * from "./Yielder.ts";
 export * from "./logging.ts";
+export * from "./tar.ts";
 
 export const shallowArrayEqual = <T>(a: T[], b: T[]): boolean => {
     if (a.length !== b.length) {
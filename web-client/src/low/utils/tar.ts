@@ -0,0 +1,88 @@
+//! Minimal tar archive writer, for downloading multiple files at once
+
+const BLOCK_SIZE = 512;
+const NAME_SIZE = 100;
+const PREFIX_SIZE = 155;
+
+/// Create a tar archive (ustar format) with the files, keyed by path
+///
+/// Throws if a path is too long to be stored in the archive
+export const createTar = (files: Map<string, Uint8Array>): Uint8Array => {
+    const encoder = new TextEncoder();
+    const blocks: Uint8Array[] = [];
+    let size = 0;
+    files.forEach((content, path) => {
+        const header = createTarHeader(encoder, path, content.length);
+        blocks.push(header, content);
+        size += header.length + padToBlock(content.length);
+    });
+    // the archive ends with 2 empty blocks
+    size += BLOCK_SIZE * 2;
+
+    const output = new Uint8Array(size);
+    let offset = 0;
+    for (const block of blocks) {
+        output.set(block, offset);
+        offset += padToBlock(block.length);
+    }
+    return output;
+};
+
+const padToBlock = (size: number): number => {
+    return Math.ceil(size / BLOCK_SIZE) * BLOCK_SIZE;
+};
+
+const createTarHeader = (
+    encoder: TextEncoder,
+    path: string,
+    size: number,
+): Uint8Array => {
+    const [prefix, name] = splitTarPath(encoder.encode(path), path);
+    const header = new Uint8Array(BLOCK_SIZE);
+    header.set(name, 0);
+    writeOctal(header, 100, 8, 0o644); // mode
+    writeOctal(header, 108, 8, 0); // uid
+    writeOctal(header, 116, 8, 0); // gid
+    writeOctal(header, 124, 12, size);
+    writeOctal(header, 136, 12, Math.floor(Date.now() / 1000)); // mtime
+    header[156] = "0".charCodeAt(0); // regular file
+    header.set(encoder.encode("ustar\x0000"), 257);
+    header.set(prefix, 345);
+
+    // checksum is computed with the checksum field filled with spaces
+    header.fill(" ".charCodeAt(0), 148, 156);
+    const checksum = header.reduce((sum, x) => sum + x, 0);
+    writeOctal(header, 148, 7, checksum);
+    return header;
+};
+
+/// Split the path into the prefix and name fields, at a `/`
+const splitTarPath = (
+    bytes: Uint8Array,
+    path: string,
+): [Uint8Array, Uint8Array] => {
+    if (bytes.length <= NAME_SIZE) {
+        return [new Uint8Array(0), bytes];
+    }
+    const slash = "/".charCodeAt(0);
+    for (let i = bytes.length - NAME_SIZE - 1; i <= PREFIX_SIZE; i++) {
+        if (i > 0 && bytes[i] === slash) {
+            return [bytes.subarray(0, i), bytes.subarray(i + 1)];
+        }
+    }
+    throw new Error(`Path is too long to be stored in tar: ${path}`);
+};
+
+/// Write the number as a null-terminated octal string in the field
+const writeOctal = (
+    header: Uint8Array,
+    offset: number,
+    length: number,
+    value: number,
+) => {
+    const octal = value.toString(8).padStart(length - 1, "0");
+    for (let i = 0; i < length - 1; i++) {
+        header[offset + i] = octal.charCodeAt(i);
+    }
+    header[offset + length - 1] = 0;
+};
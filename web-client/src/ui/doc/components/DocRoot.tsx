@@ -11,7 +11,7 @@ import {
     DocPrefaceBlockClass,
     DocPrefaceContainer,
 } from "./dom";
-import { Rich } from "./Rich";
+import { Rich, RichIconsContext } from "./Rich";
 import { DocDiagnosticBlock } from "./DocDiagnosticBlock";
 import { useDocStyles } from "./styles";
 
@@ -44,40 +44,42 @@ export const DocRoot: React.FC<DocRootProps> = ({
     const styles = useDocStyles();
 
     return (
-        <DocContainerComp onScroll={onScroll}>
-            <div id={DocDiagnosticContainer.id}>
-                {document.diagnostics.map((diagnostic, i) => (
-                    <DocDiagnosticBlock
-                        key={i}
-                        diagnostic={diagnostic}
-                        showCaret={false}
+        <RichIconsContext.Provider value={document.project.icons}>
+            <DocContainerComp onScroll={onScroll}>
+                <div id={DocDiagnosticContainer.id}>
+                    {document.diagnostics.map((diagnostic, i) => (
+                        <DocDiagnosticBlock
+                            key={i}
+                            diagnostic={diagnostic}
+                            showCaret={false}
+                        />
+                    ))}
+                </div>
+                <div id={DocPrefaceContainer.id}>
+                    {document.preface.map((text, i) => (
+                        <div
+                            key={i}
+                            className={smartMergeClasses(
+                                styles,
+                                DocPrefaceBlockClass,
+                            )}
+                        >
+                            <Rich content={text} size={400} />
+                        </div>
+                    ))}
+                </div>
+                <div
+                    id={DocContentContainer.id}
+                    className={styles.docContentContainer}
+                >
+                    <DocMainPanel
+                        document={document}
+                        splitTypes={new Set(splitTypes)}
                     />
-                ))}
-            </div>
-            <div id={DocPrefaceContainer.id}>
-                {document.preface.map((text, i) => (
-                    <div
-                        key={i}
-                        className={smartMergeClasses(
-                            styles,
-                            DocPrefaceBlockClass,
-                        )}
-                    >
-                        <Rich content={text} size={400} />
-                    </div>
-                ))}
-            </div>
-            <div
-                id={DocContentContainer.id}
-                className={styles.docContentContainer}
-            >
-                <DocMainPanel
-                    document={document}
-                    splitTypes={new Set(splitTypes)}
-                />
-                <DocNotePanel document={document} />
-            </div>
-            <DocEndComp />
-        </DocContainerComp>
+                    <DocNotePanel document={document} />
+                </div>
+                <DocEndComp />
+            </DocContainerComp>
+        </RichIconsContext.Provider>
     );
 };
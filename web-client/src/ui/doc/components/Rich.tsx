@@ -1,5 +1,6 @@
 //! Rich text component
 
+import { createContext, useContext } from "react";
 import type { TextProps } from "@fluentui/react-components";
 import { Text } from "@fluentui/react-components";
 
@@ -9,10 +10,16 @@ import { smartMergeClasses } from "low/utils";
 import {
     DocLineTextRichClass,
     RichTextColorClass,
+    RichTextIconClass,
     getTagClassName,
 } from "./dom";
 import { useDocStyles } from "./styles";
 
+/// Icon urls by id, for displaying inline icons in rich text
+export const RichIconsContext = createContext<Readonly<Record<string, string>>>(
+    {},
+);
+
 /// Rich text display component
 type RichProps = {
     /// The text to display
@@ -38,8 +45,16 @@ export const Rich: React.FC<RichProps> = ({ content, size }) => {
 /// Internal rich text display component
 type RichBlockProps = DocRichTextBlock & Partial<TextProps>;
 
-const RichBlock: React.FC<RichBlockProps> = ({ text, tag, link, ...rest }) => {
+const RichBlock: React.FC<RichBlockProps> = ({
+    text,
+    tag,
+    outerTags,
+    link,
+    icon,
+    ...rest
+}) => {
     const styles = useDocStyles();
+    const icons = useContext(RichIconsContext);
     if (!tag) {
         return (
             <Text as="span" {...rest}>
@@ -48,7 +63,7 @@ const RichBlock: React.FC<RichBlockProps> = ({ text, tag, link, ...rest }) => {
         );
     }
 
-    return (
+    const inner = (
         <Text
             as="span"
             className={smartMergeClasses(
@@ -59,7 +74,14 @@ const RichBlock: React.FC<RichBlockProps> = ({ text, tag, link, ...rest }) => {
             )}
             {...rest}
         >
-            {link ? (
+            {icon && icons[icon] ? (
+                <img
+                    src={icons[icon]}
+                    alt={text}
+                    title={text}
+                    className={smartMergeClasses(styles, RichTextIconClass)}
+                />
+            ) : link ? (
                 <a href={link} target="_blank">
                     {text}
                 </a>
@@ -68,4 +90,23 @@ const RichBlock: React.FC<RichBlockProps> = ({ text, tag, link, ...rest }) => {
             )}
         </Text>
     );
+    if (!outerTags?.length) {
+        return inner;
+    }
+    // wrap the text with the outer tags, so styles of the inner tags
+    // take precedence over the outer ones
+    return outerTags.reduceRight(
+        (content, outerTag) => (
+            <span
+                className={smartMergeClasses(
+                    styles,
+                    RichTextColorClass,
+                    getTagClassName(outerTag),
+                )}
+            >
+                {content}
+            </span>
+        ),
+        inner,
+    );
 };
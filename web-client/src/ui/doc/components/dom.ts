@@ -88,6 +88,8 @@ export const DocContainerWidthVariable = new CSSVariable(
 // Rich Text
 /// Marks receiver of rich text color
 export const RichTextColorClass = new DOMClass("rich-text-color");
+/// Inline icon in rich text
+export const RichTextIconClass = new DOMClass("rich-text-icon");
 export const RichTextVariables = {
     fg: {
         light: new CSSVariable("--tag-fg-l"),
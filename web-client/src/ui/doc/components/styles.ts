@@ -22,6 +22,7 @@ import {
     DocPrefaceBlockClass,
     DocSectionHeadClass,
     RichTextColorClass,
+    RichTextIconClass,
     RichTextVariables,
 } from "./dom";
 
@@ -199,4 +200,8 @@ export const useDocStyles = makeStyles({
             backgroundColor: RichTextVariables.bg.dark.fallback("inherit"),
         },
     },
+    [RichTextIconClass.className]: {
+        height: "1.2em",
+        verticalAlign: "text-bottom",
+    },
 });
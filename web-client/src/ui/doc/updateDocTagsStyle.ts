@@ -14,6 +14,7 @@ export function updateDocTagsStyle(tags: Readonly<Record<string, DocTag>>) {
     const underlineClasses: string[] = [];
     const strikethroughClasses: string[] = [];
     const underlineStrikethroughClasses: string[] = [];
+    const monospaceClasses: string[] = [];
     tagKeys.forEach((tag) => {
         const data = tags[tag];
         const tagClass = "." + getTagClassName(tag);
@@ -30,6 +31,9 @@ export function updateDocTagsStyle(tags: Readonly<Record<string, DocTag>>) {
         } else if (data.strikethrough) {
             strikethroughClasses.push(tagClass);
         }
+        if (data.monospace) {
+            monospaceClasses.push(tagClass);
+        }
         if (data.color) {
             addColor(data.color, "fg", colorSelectors, tagClass);
         }
@@ -75,6 +79,11 @@ export function updateDocTagsStyle(tags: Readonly<Record<string, DocTag>>) {
         )}{text-decoration:underline line-through;}`;
     }
     injectDOMStyle("rich-text-us", underlineStrikethroughCss);
+    let monospaceCss = "";
+    if (monospaceClasses.length > 0) {
+        monospaceCss = `${monospaceClasses.join(",")}{font-family:monospace;}`;
+    }
+    injectDOMStyle("rich-text-code", monospaceCss);
 
     console.info("rich text css updated.");
 }
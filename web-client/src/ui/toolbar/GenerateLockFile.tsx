@@ -0,0 +1,78 @@
+//! Control for generating the lock file (celer.lock) of the project
+//!
+//! The lock file pins the content of the remote resources loaded in the last compilation.
+//! It is downloaded, and should be put at the root of the project.
+
+import { forwardRef, useCallback } from "react";
+import { useSelector } from "react-redux";
+import { MenuItem, ToolbarButton, Tooltip } from "@fluentui/react-components";
+import { LockClosed20Regular } from "@fluentui/react-icons";
+
+import { fsSave } from "@pistonite/pure/fs";
+
+import { useKernel } from "core/kernel";
+import { viewSelector } from "core/store";
+import { console } from "low/utils";
+
+import type { ToolbarControl } from "./util";
+
+/// Name of the lock file, same as the one the compiler loads
+const LOCK_FILE = "celer.lock";
+
+export const GenerateLockFile: ToolbarControl = {
+    ToolbarButton: forwardRef<HTMLButtonElement>((_, ref) => {
+        const { handler, disabled, tooltip } = useGenerateLockFileControl();
+        return (
+            <Tooltip content={tooltip} relationship="label">
+                <ToolbarButton
+                    ref={ref}
+                    icon={<LockClosed20Regular />}
+                    disabled={disabled}
+                    onClick={handler}
+                />
+            </Tooltip>
+        );
+    }),
+    MenuItem: () => {
+        const { handler, disabled, tooltip } = useGenerateLockFileControl();
+        return (
+            <Tooltip content={tooltip} relationship="label">
+                <MenuItem
+                    icon={<LockClosed20Regular />}
+                    disabled={disabled}
+                    onClick={handler}
+                >
+                    Generate lock file
+                </MenuItem>
+            </Tooltip>
+        );
+    },
+};
+
+function useGenerateLockFileControl() {
+    const kernel = useKernel();
+    const { rootPath, compileInProgress, compilerReady } =
+        useSelector(viewSelector);
+
+    const handler = useCallback(async () => {
+        const compiler = await kernel.asEdit().ensureCompiler();
+        const result = await compiler.getLockFile();
+        if ("err" in result) {
+            await kernel.alertMgr.show({
+                title: "Error",
+                message: `Failed to generate the lock file: ${result.err}`,
+                okButton: "Close",
+            });
+            return;
+        }
+        console.info(`saving file: ${LOCK_FILE}`);
+        fsSave(result.val, LOCK_FILE);
+    }, [kernel]);
+
+    const disabled = !rootPath || compileInProgress || !compilerReady;
+    const tooltip = rootPath
+        ? `Download ${LOCK_FILE} to pin the remote resources used in the last compilation. Put it at the root of the project.`
+        : "Generate lock file";
+
+    return { handler, disabled, tooltip };
+}
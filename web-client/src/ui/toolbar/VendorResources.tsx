@@ -0,0 +1,99 @@
+//! Control for vendoring the remote resources of the project
+//!
+//! The remote resources needed to compile the project are downloaded as an archive,
+//! which should be extracted at the root of the project to create the vendor directory.
+
+import { forwardRef, useCallback } from "react";
+import { useSelector } from "react-redux";
+import { MenuItem, ToolbarButton, Tooltip } from "@fluentui/react-components";
+import { CloudArrowDown20Regular } from "@fluentui/react-icons";
+import { gzip } from "pako";
+
+import { fsSave } from "@pistonite/pure/fs";
+
+import { useKernel } from "core/kernel";
+import { viewSelector } from "core/store";
+import { console, createTar } from "low/utils";
+
+import type { ToolbarControl } from "./util";
+
+/// Name of the downloaded archive
+const VENDOR_ARCHIVE = "vendor.tar.gz";
+
+export const VendorResources: ToolbarControl = {
+    ToolbarButton: forwardRef<HTMLButtonElement>((_, ref) => {
+        const { handler, disabled, tooltip } = useVendorResourcesControl();
+        return (
+            <Tooltip content={tooltip} relationship="label">
+                <ToolbarButton
+                    ref={ref}
+                    icon={<CloudArrowDown20Regular />}
+                    disabled={disabled}
+                    onClick={handler}
+                />
+            </Tooltip>
+        );
+    }),
+    MenuItem: () => {
+        const { handler, disabled, tooltip } = useVendorResourcesControl();
+        return (
+            <Tooltip content={tooltip} relationship="label">
+                <MenuItem
+                    icon={<CloudArrowDown20Regular />}
+                    disabled={disabled}
+                    onClick={handler}
+                >
+                    Vendor remote resources
+                </MenuItem>
+            </Tooltip>
+        );
+    },
+};
+
+function useVendorResourcesControl() {
+    const kernel = useKernel();
+    const { rootPath, compileInProgress, compilerReady } =
+        useSelector(viewSelector);
+
+    const handler = useCallback(async () => {
+        const compiler = await kernel.asEdit().ensureCompiler();
+        const result = await compiler.vendorDocument();
+        if ("err" in result) {
+            await kernel.alertMgr.show({
+                title: "Error",
+                message: `Failed to vendor the remote resources: ${result.err}`,
+                okButton: "Close",
+            });
+            return;
+        }
+        if (result.val.size === 0) {
+            await kernel.alertMgr.show({
+                title: "Vendor remote resources",
+                message: "The project does not use any remote resources.",
+                okButton: "Close",
+            });
+            return;
+        }
+        let archive: Uint8Array;
+        try {
+            archive = gzip(createTar(result.val));
+        } catch (e) {
+            console.error(e);
+            await kernel.alertMgr.show({
+                title: "Error",
+                message: `Failed to create the archive: ${e}`,
+                okButton: "Close",
+            });
+            return;
+        }
+        console.info(`saving file: ${VENDOR_ARCHIVE}`);
+        fsSave(archive, VENDOR_ARCHIVE);
+    }, [kernel]);
+
+    const disabled = !rootPath || compileInProgress || !compilerReady;
+    const tooltip = rootPath
+        ? `Download ${VENDOR_ARCHIVE} with the remote resources of the project. Extract it at the root of the project to compile without internet access.`
+        : "Vendor remote resources";
+
+    return { handler, disabled, tooltip };
+}
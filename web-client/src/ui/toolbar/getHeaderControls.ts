@@ -15,6 +15,8 @@ import { SaveProject } from "./SaveProject";
 import { ReloadDocument } from "./ReloadDocument";
 import { OpenDocs } from "./OpenDocs";
 import { Export } from "./Export";
+import { GenerateLockFile } from "./GenerateLockFile";
+import { VendorResources } from "./VendorResources";
 
 /// Header controls.
 ///
@@ -64,7 +66,19 @@ export const getHeaderControls = (
 
 const getEditorControls = (editorMode: EditorMode): ToolbarControl[] => {
     if (editorMode === "web") {
-        return [ReloadDocument, SaveProject, SyncProject, OpenCloseProject];
+        return [
+            ReloadDocument,
+            SaveProject,
+            SyncProject,
+            GenerateLockFile,
+            VendorResources,
+            OpenCloseProject,
+        ];
     }
-    return [ReloadDocument, OpenCloseProject];
+    return [
+        ReloadDocument,
+        GenerateLockFile,
+        VendorResources,
+        OpenCloseProject,
+    ];
 };